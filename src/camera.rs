@@ -9,6 +9,19 @@ pub struct Camera {
     v: Vec3,
     w: Vec3,
     lens_radius: f64,
+    focus_dist: f64,
+}
+
+// Where a point in the scene shows up on the film, as seen from a point on the lens.
+pub struct CameraSample {
+    // Film coordinates of the point, as taken by `Camera::get_ray`.
+    pub s: f64,
+    pub t: f64,
+    pub lens_point: Point3,
+    // Density of the camera generating the direction from the lens point towards the point, per
+    // unit solid angle, for film coordinates spread uniformly over [0, 1]^2. This is also the
+    // importance that the camera gives to light arriving from that direction.
+    pub pdf: f64,
 }
 
 fn degrees_to_radians(degrees: f64) -> f64 {
@@ -52,6 +65,7 @@ impl Camera {
             v,
             w,
             lens_radius,
+            focus_dist,
         }
     }
 
@@ -66,4 +80,77 @@ impl Camera {
                 - offset,
         )
     }
+
+    // Density per unit solid angle of the directions of the rays generated by `get_ray`, for film
+    // coordinates spread uniformly over [0, 1]^2, which doesn't depend on the point on the lens.
+    pub fn direction_pdf(&self, direction: Vec3) -> f64 {
+        let cos_theta = direction.into_unit_vec().dot(self.w.mul_scalar(-1.0));
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+
+        let film_area = self.horizontal.length() * self.vertical.length();
+        self.focus_dist * self.focus_dist / (film_area * cos_theta * cos_theta * cos_theta)
+    }
+
+    // Projects a point in the scene onto the film through a random point on the lens, or returns
+    // None if the point is behind the camera.
+    pub fn project(&self, point: Point3) -> Option<CameraSample> {
+        let lens = Vec3::random_in_unit_disk().mul_scalar(self.lens_radius);
+        let lens_point = self.origin + self.u.mul_scalar(lens.x()) + self.v.mul_scalar(lens.y());
+
+        let direction = point - lens_point;
+        let depth = direction.dot(self.w.mul_scalar(-1.0));
+        if depth <= 0.0 {
+            return None;
+        }
+
+        // Rays through the same point of the focus plane reach the same point of the film.
+        let film_point = lens_point + direction.mul_scalar(self.focus_dist / depth);
+        let offset = film_point - self.lower_left_corner;
+        Some(CameraSample {
+            s: offset.dot(self.u) / self.horizontal.length(),
+            t: offset.dot(self.v) / self.vertical.length(),
+            lens_point,
+            pdf: self.direction_pdf(direction),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Points on the focus plane are in focus, so they project back to the film coordinates of the
+    // rays that reach them from any point of the lens.
+    #[test]
+    fn points_in_focus_project_to_their_rays() {
+        let camera = Camera::new(
+            Point3::new(1.0, 2.0, 5.0),
+            Point3::default(),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.5,
+            0.2,
+            5.0,
+        );
+
+        for &(s, t) in &[(0.5, 0.5), (0.1, 0.8), (0.9, 0.2)] {
+            let ray = camera.get_ray(s, t);
+            let sample = camera.project(*ray.origin() + *ray.direction()).unwrap();
+            assert!(
+                (sample.s - s).abs() < 1e-9,
+                "s {} instead of {}",
+                sample.s,
+                s
+            );
+            assert!(
+                (sample.t - t).abs() < 1e-9,
+                "t {} instead of {}",
+                sample.t,
+                t
+            );
+        }
+        assert!(camera.project(Point3::new(2.0, 4.0, 10.0)).is_none());
+    }
 }
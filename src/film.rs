@@ -0,0 +1,164 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::linalg::Color;
+use crate::sampler;
+
+// Image being rendered, which maps pixels to the film coordinates taken by `Camera::get_ray`, and
+// collects the light that integrators splat onto arbitrary pixels (e.g. when tracing paths from the
+// lights to the camera) from any thread.
+pub struct Film {
+    width: u32,
+    height: u32,
+    // Sums of the splatted light for each pixel, as the bits of f64 values so that they can be added
+    // to atomically.
+    splats: Vec<[AtomicU64; 3]>,
+    // Whether the image is rendered with alpha, where the pixels that don't see an opaque surface
+    // are composited by the render loop instead of taking splats.
+    alpha: bool,
+}
+
+pub(crate) fn atomic_add(value: &AtomicU64, addend: f64) {
+    let mut current = value.load(Ordering::Relaxed);
+    loop {
+        let new = (f64::from_bits(current) + addend).to_bits();
+        match value.compare_exchange_weak(current, new, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => return,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        let splats = (0..width * height)
+            .map(|_| {
+                [
+                    AtomicU64::new(0.0_f64.to_bits()),
+                    AtomicU64::new(0.0_f64.to_bits()),
+                    AtomicU64::new(0.0_f64.to_bits()),
+                ]
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            splats,
+            alpha: false,
+        }
+    }
+
+    pub fn with_alpha(mut self, alpha: bool) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn alpha(&self) -> bool {
+        self.alpha
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // Film coordinates of a random point in a pixel, with pixel rows going from the top of the image
+    // to the bottom.
+    pub fn sample_pixel(&self, i: u32, j: u32) -> (f64, f64) {
+        let s = (i as f64 + sampler::random()) / (self.width - 1) as f64;
+        let t = ((self.height - j) as f64 + sampler::random()) / (self.height - 1) as f64;
+
+        (s, t)
+    }
+
+    // Film coordinates of a point anywhere on the pixels, picked from two uniform numbers in [0, 1).
+    pub fn sample(&self, u: f64, v: f64) -> (f64, f64) {
+        let s = u * self.width as f64 / (self.width - 1) as f64;
+        let t = (1.0 + v * self.height as f64) / (self.height - 1) as f64;
+
+        (s, t)
+    }
+
+    // Pixel that contains a point of the film, if any.
+    pub fn pixel_at(&self, s: f64, t: f64) -> Option<(u32, u32)> {
+        let column = (s * (self.width - 1) as f64).floor();
+        let row = (t * (self.height - 1) as f64).floor();
+        if column < 0.0 || column >= self.width as f64 || row < 1.0 || row > self.height as f64 {
+            return None;
+        }
+
+        Some((column as u32, self.height - row as u32))
+    }
+
+    pub fn pixel_num(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    // Area covered by the pixels in film coordinates, which is a bit larger than the unit square
+    // since `sample_pixel` maps the last row and column past it.
+    pub fn area(&self) -> f64 {
+        self.pixel_num() as f64 / ((self.width - 1) as f64 * (self.height - 1) as f64)
+    }
+
+    // Adds light to the pixel that contains a point of the film. The light is an estimate of the
+    // radiance arriving at the camera from a sample of the film spread uniformly over [0, 1]^2.
+    pub fn add_splat(&self, s: f64, t: f64, color: Color) {
+        if let Some((i, j)) = self.pixel_at(s, t) {
+            let splat = &self.splats[(j * self.width + i) as usize];
+            atomic_add(&splat[0], color.r());
+            atomic_add(&splat[1], color.g());
+            atomic_add(&splat[2], color.b());
+        }
+    }
+
+    // Light splatted onto a pixel, once every pixel has taken the given number of samples and
+    // splatted one estimate each.
+    pub fn splat_color(&self, i: u32, j: u32, samples_per_pixel: u32) -> Color {
+        let splat = &self.splats[(j * self.width + i) as usize];
+        let sum = Color::new(
+            f64::from_bits(splat[0].load(Ordering::Relaxed)),
+            f64::from_bits(splat[1].load(Ordering::Relaxed)),
+            f64::from_bits(splat[2].load(Ordering::Relaxed)),
+        );
+
+        // Each pixel covers 1 / ((width - 1) * (height - 1)) of the film.
+        let pixel_area = 1.0 / ((self.width - 1) as f64 * (self.height - 1) as f64);
+        let estimate_num = self.pixel_num() as f64 * samples_per_pixel as f64;
+        sum.div_scalar(pixel_area * estimate_num)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sampled_points_are_in_their_pixel() {
+        let film = Film::new(7, 5);
+        for j in 0..5 {
+            for i in 0..7 {
+                let (s, t) = film.sample_pixel(i, j);
+                assert_eq!(film.pixel_at(s, t), Some((i, j)));
+            }
+        }
+        assert_eq!(film.pixel_at(-0.1, 0.5), None);
+        assert_eq!(film.pixel_at(0.5, 0.1), None);
+    }
+
+    // The number of estimates is larger than a u32 holds.
+    #[test]
+    fn splats_are_averaged_over_many_samples() {
+        let film = Film::new(2, 2);
+        let samples_per_pixel = 1 << 31;
+        let (s, t) = film.sample_pixel(1, 0);
+        film.add_splat(s, t, Color::from_scalar(4.0 * samples_per_pixel as f64));
+        assert_eq!(
+            film.splat_color(1, 0, samples_per_pixel),
+            Color::from_scalar(1.0)
+        );
+        assert_eq!(film.splat_color(0, 0, samples_per_pixel), Color::default());
+    }
+}
@@ -1,11 +1,13 @@
 use std::sync::Arc;
 
-use rand::Rng;
-
 use crate::linalg::{Point3, Ray, Vec3};
 use crate::materials::Material;
+use crate::sampler;
 
-use super::{BoundingBox, Hit, HitRecord};
+use super::{
+    surface_emission_cone, surface_emitted_power, surface_record, Area, BoundingBox, EmissionCone,
+    Hit, HitRecord,
+};
 
 pub struct XYRect {
     x_min: f64,
@@ -36,6 +38,12 @@ impl XYRect {
     }
 }
 
+impl Area for XYRect {
+    fn area(&self) -> f64 {
+        (self.x_max - self.x_min) * (self.y_max - self.y_min)
+    }
+}
+
 impl Hit for XYRect {
     fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let t = (self.z - ray.origin().z()) / ray.direction().z();
@@ -57,6 +65,11 @@ impl Hit for XYRect {
             false,
         );
         hit_record.set_face_normal(ray, Vec3::new(0.0, 0.0, 1.0));
+        hit_record.set_tangent(Vec3::new(1.0, 0.0, 0.0));
+        hit_record.set_texture_coords(
+            (x - self.x_min) / (self.x_max - self.x_min),
+            (y - self.y_min) / (self.y_max - self.y_min),
+        );
         Some(hit_record)
     }
 
@@ -66,6 +79,43 @@ impl Hit for XYRect {
             Point3::new(self.x_max, self.y_max, self.z + 0.0001),
         ))
     }
+
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        if let Some(hit_record) = self.hit(Ray::new(hit_point, direction), 0.001, f64::INFINITY) {
+            let distance_sq = hit_record.t * hit_record.t * direction.length_sq();
+            let cos_theta = direction.dot(hit_record.normal).abs() / direction.length();
+
+            distance_sq / (cos_theta * self.area())
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        let rand_point = Point3::new(
+            sampler::random_range(self.x_min, self.x_max),
+            sampler::random_range(self.y_min, self.y_max),
+            self.z,
+        );
+
+        rand_point - origin
+    }
+
+    fn emitted_power(&self) -> f64 {
+        surface_emitted_power(&self.material, self.area())
+    }
+
+    fn emission_cone(&self) -> EmissionCone {
+        surface_emission_cone(&self.material, Vec3::new(0.0, 0.0, 1.0))
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        surface_record(
+            self,
+            self.random(Point3::default()),
+            Vec3::new(0.0, 0.0, 1.0),
+        )
+    }
 }
 
 pub struct XZRect {
@@ -97,6 +147,12 @@ impl XZRect {
     }
 }
 
+impl Area for XZRect {
+    fn area(&self) -> f64 {
+        (self.x_max - self.x_min) * (self.z_max - self.z_min)
+    }
+}
+
 impl Hit for XZRect {
     fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let t = (self.y - ray.origin().y()) / ray.direction().y();
@@ -118,6 +174,11 @@ impl Hit for XZRect {
             false,
         );
         hit_record.set_face_normal(ray, Vec3::new(0.0, 1.0, 0.0));
+        hit_record.set_tangent(Vec3::new(1.0, 0.0, 0.0));
+        hit_record.set_texture_coords(
+            (x - self.x_min) / (self.x_max - self.x_min),
+            (z - self.z_min) / (self.z_max - self.z_min),
+        );
         Some(hit_record)
     }
 
@@ -129,29 +190,41 @@ impl Hit for XZRect {
     }
 
     fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
-        if let Some(hit_record) =
-            self.hit(Ray::new(hit_point, direction), 0.001, std::f64::INFINITY)
-        {
-            let area = (self.x_max - self.x_min) * (self.z_max - self.z_min);
+        if let Some(hit_record) = self.hit(Ray::new(hit_point, direction), 0.001, f64::INFINITY) {
             let distance_sq = hit_record.t * hit_record.t * direction.length_sq();
             let cos_theta = direction.dot(hit_record.normal).abs() / direction.length();
 
-            distance_sq / (cos_theta * area)
+            distance_sq / (cos_theta * self.area())
         } else {
             0.0
         }
     }
 
     fn random(&self, origin: Vec3) -> Vec3 {
-        let mut rng = rand::thread_rng();
         let rand_point = Point3::new(
-            rng.gen_range(self.x_min, self.x_max),
+            sampler::random_range(self.x_min, self.x_max),
             self.y,
-            rng.gen_range(self.z_min, self.z_max),
+            sampler::random_range(self.z_min, self.z_max),
         );
 
         rand_point - origin
     }
+
+    fn emitted_power(&self) -> f64 {
+        surface_emitted_power(&self.material, self.area())
+    }
+
+    fn emission_cone(&self) -> EmissionCone {
+        surface_emission_cone(&self.material, Vec3::new(0.0, 1.0, 0.0))
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        surface_record(
+            self,
+            self.random(Point3::default()),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+    }
 }
 
 pub struct YZRect {
@@ -183,6 +256,12 @@ impl YZRect {
     }
 }
 
+impl Area for YZRect {
+    fn area(&self) -> f64 {
+        (self.y_max - self.y_min) * (self.z_max - self.z_min)
+    }
+}
+
 impl Hit for YZRect {
     fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let t = (self.x - ray.origin().x()) / ray.direction().x();
@@ -204,6 +283,11 @@ impl Hit for YZRect {
             false,
         );
         hit_record.set_face_normal(ray, Vec3::new(1.0, 0.0, 0.0));
+        hit_record.set_tangent(Vec3::new(0.0, 1.0, 0.0));
+        hit_record.set_texture_coords(
+            (y - self.y_min) / (self.y_max - self.y_min),
+            (z - self.z_min) / (self.z_max - self.z_min),
+        );
         Some(hit_record)
     }
 
@@ -213,4 +297,41 @@ impl Hit for YZRect {
             Point3::new(self.x + 0.0001, self.y_max, self.z_max),
         ))
     }
+
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        if let Some(hit_record) = self.hit(Ray::new(hit_point, direction), 0.001, f64::INFINITY) {
+            let distance_sq = hit_record.t * hit_record.t * direction.length_sq();
+            let cos_theta = direction.dot(hit_record.normal).abs() / direction.length();
+
+            distance_sq / (cos_theta * self.area())
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        let rand_point = Point3::new(
+            self.x,
+            sampler::random_range(self.y_min, self.y_max),
+            sampler::random_range(self.z_min, self.z_max),
+        );
+
+        rand_point - origin
+    }
+
+    fn emitted_power(&self) -> f64 {
+        surface_emitted_power(&self.material, self.area())
+    }
+
+    fn emission_cone(&self) -> EmissionCone {
+        surface_emission_cone(&self.material, Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        surface_record(
+            self,
+            self.random(Point3::default()),
+            Vec3::new(1.0, 0.0, 0.0),
+        )
+    }
 }
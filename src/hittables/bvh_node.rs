@@ -2,11 +2,16 @@ use rand::Rng;
 use std::cmp::Ordering;
 use std::sync::Arc;
 
-use super::{BoundingBox, Hit, HitRecord, World};
-use crate::linalg::Ray;
+use super::{collect_emitters, BoundingBox, Hit, HitRecord, World};
+use crate::linalg::{Point3, Ray, Vec3};
+use crate::sampler;
 
 // TODO(dpopacristobal): Could we replace this and just use generics with traits?
-fn b_box_compare(hittable_a: Arc<dyn Hit>, hittable_b: Arc<dyn Hit>, axis: i32) -> Ordering {
+pub(crate) fn b_box_compare(
+    hittable_a: Arc<dyn Hit>,
+    hittable_b: Arc<dyn Hit>,
+    axis: i32,
+) -> Ordering {
     // TODO(dpopacristobal): Should we add proper error-handling here?
     let box_a = hittable_a.bounding_box(0.0, 0.0).unwrap();
     let box_b = hittable_b.bounding_box(0.0, 0.0).unwrap();
@@ -61,10 +66,10 @@ impl BvhNode {
                     comparator(hittable_a.clone(), hittable_b.clone())
                 });
                 let mid = object_num / 2;
-                let (mut slice_left, mut slice_right) = objects.split_at_mut(mid);
+                let (slice_left, slice_right) = objects.split_at_mut(mid);
                 (
-                    Arc::new(Self::new(&mut slice_left, time_0, time_1)) as Arc<dyn Hit>,
-                    Arc::new(Self::new(&mut slice_right, time_0, time_1)) as Arc<dyn Hit>,
+                    Arc::new(Self::new(slice_left, time_0, time_1)) as Arc<dyn Hit>,
+                    Arc::new(Self::new(slice_right, time_0, time_1)) as Arc<dyn Hit>,
                 )
             }
         };
@@ -90,12 +95,21 @@ impl Hit for BvhNode {
     fn hit(&self, ray: Ray, t_min: f64, mut t_max: f64) -> Option<HitRecord> {
         self.b_box.hit(ray, t_min, t_max)?;
 
-        let hit_left_opt = self.left_child_node.hit(ray, t_min, t_max);
+        let ray_type = ray.ray_type();
+        let hit_left_opt = if self.left_child_node.visibility().is_visible_to(ray_type) {
+            self.left_child_node.hit(ray, t_min, t_max)
+        } else {
+            None
+        };
         if let Some(hit_left) = hit_left_opt.as_ref() {
             t_max = hit_left.t;
         }
 
-        let hit_right_opt = self.right_child_node.hit(ray, t_min, t_max);
+        let hit_right_opt = if self.right_child_node.visibility().is_visible_to(ray_type) {
+            self.right_child_node.hit(ray, t_min, t_max)
+        } else {
+            None
+        };
 
         if hit_right_opt.is_some() {
             hit_right_opt
@@ -109,4 +123,49 @@ impl Hit for BvhNode {
     fn bounding_box(&self, _t_min: f64, _t_max: f64) -> Option<BoundingBox> {
         Some(self.b_box.clone())
     }
+
+    // Sampling a BVH as a light picks either of the children of each node with equal probability, so
+    // subtrees whose bounding box the direction misses can be skipped when computing the PDF.
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        if self
+            .b_box
+            .hit(Ray::new(hit_point, direction), 0.001, f64::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+
+        if Arc::ptr_eq(&self.left_child_node, &self.right_child_node) {
+            return self.left_child_node.pdf_value(hit_point, direction);
+        }
+
+        0.5 * self.left_child_node.pdf_value(hit_point, direction)
+            + 0.5 * self.right_child_node.pdf_value(hit_point, direction)
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        if sampler::random() < 0.5 {
+            self.left_child_node.random(origin)
+        } else {
+            self.right_child_node.random(origin)
+        }
+    }
+
+    fn emitted_power(&self) -> f64 {
+        if Arc::ptr_eq(&self.left_child_node, &self.right_child_node) {
+            self.left_child_node.emitted_power()
+        } else {
+            self.left_child_node.emitted_power() + self.right_child_node.emitted_power()
+        }
+    }
+
+    fn emitters(&self) -> Option<Vec<Arc<dyn Hit>>> {
+        let mut emitters = Vec::new();
+        collect_emitters(&self.left_child_node, &mut emitters);
+        if !Arc::ptr_eq(&self.left_child_node, &self.right_child_node) {
+            collect_emitters(&self.right_child_node, &mut emitters);
+        }
+
+        Some(emitters)
+    }
 }
@@ -0,0 +1,103 @@
+use std::f64::consts::PI;
+
+use crate::linalg::{Vec3, ONB};
+
+// Bounds on the directions that an object emits light in, as in Conty and Kulla, "Importance
+// Sampling of Many Lights with Adaptive Tree Splitting". The normals of the object lie within
+// `theta_o` of the axis, and light leaves the surface at most `theta_e` away from its normal.
+#[derive(Copy, Clone, Debug)]
+pub struct EmissionCone {
+    axis: Vec3,
+    theta_o: f64,
+    theta_e: f64,
+}
+
+impl EmissionCone {
+    pub fn new(axis: Vec3, theta_o: f64, theta_e: f64) -> Self {
+        Self {
+            axis: axis.into_unit_vec(),
+            theta_o: theta_o.min(PI),
+            theta_e,
+        }
+    }
+
+    // Emission from one side of a flat surface.
+    pub fn one_sided(normal: Vec3) -> Self {
+        Self::new(normal, 0.0, PI / 2.0)
+    }
+
+    pub fn omnidirectional() -> Self {
+        Self::new(Vec3::new(0.0, 0.0, 1.0), PI, PI / 2.0)
+    }
+
+    pub fn axis(&self) -> Vec3 {
+        self.axis
+    }
+
+    pub fn theta_o(&self) -> f64 {
+        self.theta_o
+    }
+
+    pub fn theta_e(&self) -> f64 {
+        self.theta_e
+    }
+
+    pub fn flip(&self) -> Self {
+        Self::new(self.axis.mul_scalar(-1.0), self.theta_o, self.theta_e)
+    }
+
+    // Smallest cone containing both cones.
+    pub fn combine(&self, other: &EmissionCone) -> Self {
+        let (wide, narrow) = if self.theta_o >= other.theta_o {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let theta_e = wide.theta_e.max(narrow.theta_e);
+
+        let theta_d = wide.axis.dot(narrow.axis).clamp(-1.0, 1.0).acos();
+        if (theta_d + narrow.theta_o).min(PI) <= wide.theta_o {
+            return Self::new(wide.axis, wide.theta_o, theta_e);
+        }
+
+        let theta_o = (wide.theta_o + theta_d + narrow.theta_o) / 2.0;
+        if theta_o >= PI {
+            return Self::new(wide.axis, PI, theta_e);
+        }
+
+        // Rotate the axis of the wide cone towards the narrow one, until it is in the middle of the
+        // combined cone.
+        let theta_r = theta_o - wide.theta_o;
+        let mut perpendicular = narrow.axis - wide.axis.mul_scalar(wide.axis.dot(narrow.axis));
+        if perpendicular.length_sq() < 1e-12 {
+            // Opposite axes can be rotated towards each other in any direction.
+            perpendicular = ONB::new(wide.axis).u();
+        }
+        let axis = wide.axis.mul_scalar(theta_r.cos())
+            + perpendicular.into_unit_vec().mul_scalar(theta_r.sin());
+
+        Self::new(axis, theta_o, theta_e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn combine_cones() {
+        let up = EmissionCone::one_sided(Vec3::new(0.0, 1.0, 0.0));
+        let side = EmissionCone::one_sided(Vec3::new(1.0, 0.0, 0.0));
+
+        let combined = up.combine(&side);
+        assert!((combined.theta_o() - PI / 4.0).abs() < 1e-9);
+        let diagonal = Vec3::new(1.0, 1.0, 0.0).into_unit_vec();
+        assert!((combined.axis().dot(diagonal) - 1.0).abs() < 1e-9);
+
+        let down = EmissionCone::one_sided(Vec3::new(0.0, -1.0, 0.0));
+        assert!((up.combine(&down).theta_o() - PI / 2.0).abs() < 1e-9);
+
+        let all = EmissionCone::omnidirectional();
+        assert_eq!(all.combine(&up).theta_o(), PI);
+    }
+}
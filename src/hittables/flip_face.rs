@@ -1,8 +1,8 @@
 use std::sync::Arc;
 
-use crate::linalg::Ray;
+use crate::linalg::{Point3, Ray, Vec3};
 
-use super::{BoundingBox, Hit, HitRecord};
+use super::{BoundingBox, EmissionCone, Hit, HitRecord, Visibility};
 
 pub struct FlipFace {
     hittable: Arc<dyn Hit>,
@@ -23,7 +23,34 @@ impl Hit for FlipFace {
             None
         }
     }
+
     fn bounding_box(&self, t_min: f64, t_max: f64) -> Option<BoundingBox> {
         self.hittable.bounding_box(t_min, t_max)
     }
+
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        self.hittable.pdf_value(hit_point, direction)
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        self.hittable.random(origin)
+    }
+
+    fn emitted_power(&self) -> f64 {
+        self.hittable.emitted_power()
+    }
+
+    fn emission_cone(&self) -> EmissionCone {
+        self.hittable.emission_cone().flip()
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.hittable.visibility()
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        let mut hit_record = self.hittable.sample_surface()?;
+        hit_record.normal = hit_record.normal.mul_scalar(-1.0);
+        Some(hit_record)
+    }
 }
@@ -1,15 +1,22 @@
 pub use axis_aligned_rects::{XYRect, XZRect, YZRect};
 pub use bounding_box::BoundingBox;
+pub(crate) use bvh_node::b_box_compare;
 pub use bvh_node::BvhNode;
+pub use emission_cone::EmissionCone;
 pub use flip_face::FlipFace;
+pub use object_flags::{ObjectFlags, Visibility};
 pub use sphere::Sphere;
+pub use subsurface::Subsurface;
 pub use triangle::Triangle;
 pub use world::World;
 
 mod bounding_box;
 pub mod bvh_node;
+mod emission_cone;
 pub mod flip_face;
+pub mod object_flags;
 pub mod sphere;
+pub mod subsurface;
 pub mod triangle;
 pub mod world;
 
@@ -18,8 +25,9 @@ pub mod axis_aligned_rects;
 
 use std::sync::Arc;
 
-use crate::linalg::{Point3, Ray, Vec3};
-use crate::materials::{DefaultMaterial, Material};
+use crate::lights::LightGroups;
+use crate::linalg::{Point3, Ray, Vec3, ONB};
+use crate::materials::{DefaultMaterial, DiffuseLight, Material};
 
 #[derive(Clone)]
 pub struct HitRecord {
@@ -28,6 +36,13 @@ pub struct HitRecord {
     pub material: Arc<dyn Material>,
     pub t: f64,
     pub front_face: bool,
+    pub tangent: Vec3,
+    // Texture coordinates of the hit point on the surface.
+    pub u: f64,
+    pub v: f64,
+    // Light-linking groups that the object belongs to, and that it illuminates if it emits light.
+    pub light_groups: LightGroups,
+    pub light_links: LightGroups,
 }
 
 pub trait Hit: Send + Sync {
@@ -39,6 +54,76 @@ pub trait Hit: Send + Sync {
     fn random(&self, _origin: Vec3) -> Vec3 {
         Vec3::new(1.0, 0.0, 0.0)
     }
+    // Estimate of the total power emitted by the object, used to decide how often to sample it when
+    // it is one of several lights.
+    fn emitted_power(&self) -> f64 {
+        0.0
+    }
+    // Bounds on the directions that the object emits light in.
+    fn emission_cone(&self) -> EmissionCone {
+        EmissionCone::omnidirectional()
+    }
+    // Emissive objects inside an aggregate of objects (e.g. the triangles of a mesh), or None if the
+    // object is not an aggregate.
+    fn emitters(&self) -> Option<Vec<Arc<dyn Hit>>> {
+        None
+    }
+    // Kinds of rays that the object can be hit by, which aggregates check before intersecting it.
+    fn visibility(&self) -> Visibility {
+        Visibility::default()
+    }
+    // Point picked uniformly over the area of the object, as a hit record on the side that it emits
+    // light from (so front_face is true and the normal points out of that side), or None if the
+    // object can't be sampled by area.
+    fn sample_surface(&self) -> Option<HitRecord> {
+        None
+    }
+}
+
+// Shapes whose surface area is known.
+pub trait Area {
+    fn area(&self) -> f64;
+}
+
+// Shape made by `shape` with the emitter that `light` makes for its area, e.g. one of a given power
+// that doesn't get brighter as the shape shrinks.
+pub fn area_light<S: Area>(
+    shape: impl Fn(Arc<dyn Material>) -> S,
+    light: impl FnOnce(f64) -> DiffuseLight,
+) -> S {
+    let area = shape(Arc::new(DefaultMaterial::default())).area();
+    shape(Arc::new(light(area)))
+}
+
+// Adds the object to the list of emitters if it emits light, or its emissive objects if it is an
+// aggregate of other objects.
+pub fn collect_emitters(object: &Arc<dyn Hit>, emitters: &mut Vec<Arc<dyn Hit>>) {
+    if let Some(inner_emitters) = object.emitters() {
+        emitters.extend(inner_emitters);
+    } else if object.emitted_power() > 0.0 {
+        emitters.push(object.clone());
+    }
+}
+
+// Hit record of a point on the surface of an object, as seen from just outside the side that the
+// normal points out of.
+pub(crate) fn surface_record(object: &dyn Hit, point: Point3, normal: Vec3) -> Option<HitRecord> {
+    object.hit(Ray::new(point + normal, normal.mul_scalar(-1.0)), 0.5, 1.5)
+}
+
+// Power emitted by a surface of the given area that emits the radiance of its material uniformly
+// from one of its sides.
+pub(crate) fn surface_emitted_power(material: &Arc<dyn Material>, area: f64) -> f64 {
+    material.emitted_radiance().luminance() * area * std::f64::consts::PI
+}
+
+// Directions that a flat surface with the given front-facing normal emits light in.
+pub(crate) fn surface_emission_cone(material: &Arc<dyn Material>, normal: Vec3) -> EmissionCone {
+    if material.emits_both_sides() {
+        EmissionCone::omnidirectional()
+    } else {
+        EmissionCone::one_sided(normal)
+    }
 }
 
 impl HitRecord {
@@ -55,6 +140,11 @@ impl HitRecord {
             material,
             t,
             front_face,
+            tangent: Vec3::default(),
+            u: 0.0,
+            v: 0.0,
+            light_groups: LightGroups::default(),
+            light_links: LightGroups::all(),
         }
     }
 
@@ -66,6 +156,21 @@ impl HitRecord {
             self.normal = outward_normal.mul_scalar(-1.0);
         }
     }
+
+    // Must be called after the normal has been set, since the tangent is made orthogonal to it.
+    pub fn set_tangent(&mut self, tangent: Vec3) {
+        let tangent = tangent - self.normal.mul_scalar(tangent.dot(self.normal));
+        if tangent.length_sq() > 1e-12 {
+            self.tangent = tangent.into_unit_vec();
+        } else {
+            self.tangent = ONB::new(self.normal).u();
+        }
+    }
+
+    pub fn set_texture_coords(&mut self, u: f64, v: f64) {
+        self.u = u;
+        self.v = v;
+    }
 }
 
 impl Default for HitRecord {
@@ -76,6 +181,11 @@ impl Default for HitRecord {
             material: Arc::new(DefaultMaterial::default()),
             t: 0.0,
             front_face: false,
+            tangent: Vec3::default(),
+            u: 0.0,
+            v: 0.0,
+            light_groups: LightGroups::default(),
+            light_links: LightGroups::all(),
         }
     }
 }
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use crate::lights::LightGroups;
+use crate::linalg::{Point3, Ray, RayType, Vec3};
+
+use super::{BoundingBox, EmissionCone, Hit, HitRecord};
+
+// Kinds of rays that an object can be hit by.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Visibility {
+    pub camera: bool,
+    pub shadow: bool,
+    pub diffuse: bool,
+    pub specular: bool,
+}
+
+impl Visibility {
+    pub fn is_visible_to(&self, ray_type: RayType) -> bool {
+        match ray_type {
+            RayType::Camera => self.camera,
+            RayType::Shadow => self.shadow,
+            RayType::Diffuse => self.diffuse,
+            RayType::Specular => self.specular,
+        }
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Self {
+            camera: true,
+            shadow: true,
+            diffuse: true,
+            specular: true,
+        }
+    }
+}
+
+// Wraps an object to control which rays can hit it and which lights illuminate it.
+pub struct ObjectFlags {
+    hittable: Arc<dyn Hit>,
+    visibility: Visibility,
+    light_groups: LightGroups,
+    light_links: LightGroups,
+    emits_light: bool,
+}
+
+impl ObjectFlags {
+    pub fn new(hittable: Arc<dyn Hit>) -> Self {
+        Self {
+            emits_light: hittable.emitted_power() > 0.0,
+            hittable,
+            visibility: Visibility::default(),
+            light_groups: LightGroups::default(),
+            light_links: LightGroups::all(),
+        }
+    }
+
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    // Light-linking groups that the object belongs to.
+    pub fn with_light_groups(mut self, light_groups: LightGroups) -> Self {
+        self.light_groups = light_groups;
+        self
+    }
+
+    // Light-linking groups that the object illuminates, if it emits light.
+    pub fn with_light_links(mut self, light_links: LightGroups) -> Self {
+        self.light_links = light_links;
+        self
+    }
+}
+
+impl Hit for ObjectFlags {
+    // Visibility is checked by the aggregates the object is in, see visibility().
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut hit_record = self.hittable.hit(ray, t_min, t_max)?;
+        hit_record.light_groups = self.light_groups;
+        hit_record.light_links = self.light_links;
+
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self, t_min: f64, t_max: f64) -> Option<BoundingBox> {
+        self.hittable.bounding_box(t_min, t_max)
+    }
+
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        self.hittable.pdf_value(hit_point, direction)
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        self.hittable.random(origin)
+    }
+
+    fn emitted_power(&self) -> f64 {
+        self.hittable.emitted_power()
+    }
+
+    fn emission_cone(&self) -> EmissionCone {
+        self.hittable.emission_cone()
+    }
+
+    fn emitters(&self) -> Option<Vec<Arc<dyn Hit>>> {
+        self.hittable.emitters()
+    }
+
+    // Shadow rays are also the rays that look for the lights, so hiding an emitter from them only
+    // keeps it from blocking the other lights.
+    fn visibility(&self) -> Visibility {
+        Visibility {
+            shadow: self.visibility.shadow || self.emits_light,
+            ..self.visibility
+        }
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        let mut hit_record = self.hittable.sample_surface()?;
+        hit_record.light_groups = self.light_groups;
+        hit_record.light_links = self.light_links;
+
+        Some(hit_record)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittables::{BvhNode, Sphere, World};
+    use crate::linalg::Color;
+    use crate::materials::Lambertian;
+
+    #[test]
+    fn hidden_from_camera() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Arc::new(Sphere::new(Point3::new(0.0, 0.0, -2.0), 0.5, material));
+        let hidden = ObjectFlags::new(sphere).with_visibility(Visibility {
+            camera: false,
+            ..Visibility::default()
+        });
+
+        let mut world = World::default();
+        world.add(Arc::new(hidden));
+        let ray = Ray::new(Point3::default(), Vec3::new(0.0, 0.0, -1.0));
+        assert!(world.hit(ray, 0.001, f64::INFINITY).is_none());
+        assert!(world
+            .hit(ray.with_type(RayType::Specular), 0.001, f64::INFINITY)
+            .is_some());
+
+        let bvh = BvhNode::from_world(&mut world, 0.0, 1.0);
+        assert!(bvh.hit(ray, 0.001, f64::INFINITY).is_none());
+        assert!(bvh
+            .hit(ray.with_type(RayType::Shadow), 0.001, f64::INFINITY)
+            .is_some());
+    }
+}
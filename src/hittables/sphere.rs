@@ -1,9 +1,9 @@
 use std::sync::Arc;
 
-use crate::linalg::{Point3, Ray, Vec3};
+use crate::linalg::{Point3, Ray, Vec3, ONB};
 use crate::materials::Material;
 
-use super::{BoundingBox, Hit, HitRecord};
+use super::{surface_emitted_power, surface_record, Area, BoundingBox, Hit, HitRecord};
 
 pub struct Sphere {
     center: Point3,
@@ -21,6 +21,24 @@ impl Sphere {
     }
 }
 
+// Texture coordinates of a point on the unit sphere, with u going around the y axis and v going from
+// the bottom to the top.
+fn sphere_texture_coords(point: Point3) -> (f64, f64) {
+    let theta = (-point.y()).clamp(-1.0, 1.0).acos();
+    let phi = (-point.z()).atan2(point.x()) + std::f64::consts::PI;
+
+    (
+        phi / (2.0 * std::f64::consts::PI),
+        theta / std::f64::consts::PI,
+    )
+}
+
+impl Area for Sphere {
+    fn area(&self) -> f64 {
+        4.0 * std::f64::consts::PI * self.radius * self.radius
+    }
+}
+
 impl Hit for Sphere {
     fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let mut hit_record = HitRecord::new(
@@ -45,6 +63,9 @@ impl Hit for Sphere {
                 hit_record.hit_point = ray.at(hit_record.t);
                 let outward_normal = (hit_record.hit_point - self.center).div_scalar(self.radius);
                 hit_record.set_face_normal(ray, outward_normal);
+                hit_record.set_tangent(Vec3::new(-outward_normal.z(), 0.0, outward_normal.x()));
+                let (u, v) = sphere_texture_coords(outward_normal);
+                hit_record.set_texture_coords(u, v);
                 return Some(hit_record);
             }
             temp = (-half_b + root) / a;
@@ -53,6 +74,9 @@ impl Hit for Sphere {
                 hit_record.hit_point = ray.at(hit_record.t);
                 let outward_normal = (hit_record.hit_point - self.center).div_scalar(self.radius);
                 hit_record.set_face_normal(ray, outward_normal);
+                hit_record.set_tangent(Vec3::new(-outward_normal.z(), 0.0, outward_normal.x()));
+                let (u, v) = sphere_texture_coords(outward_normal);
+                hit_record.set_texture_coords(u, v);
                 return Some(hit_record);
             }
         }
@@ -66,4 +90,46 @@ impl Hit for Sphere {
             self.center + Vec3::from_scalar(self.radius),
         ))
     }
+
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        if self
+            .hit(Ray::new(hit_point, direction), 0.001, f64::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+
+        let distance_sq = (self.center - hit_point).length_sq();
+        let radius_sq = self.radius * self.radius;
+        if distance_sq <= radius_sq {
+            // Points inside the sphere sample it uniformly in all directions.
+            return 1.0 / (4.0 * std::f64::consts::PI);
+        }
+
+        let cos_theta_max = (1.0 - radius_sq / distance_sq).sqrt();
+        let solid_angle = 2.0 * std::f64::consts::PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_sq = direction.length_sq();
+        if distance_sq <= self.radius * self.radius {
+            return Vec3::random_unit_vector();
+        }
+
+        // Sample the cone of directions that the sphere subtends as seen from the origin.
+        let onb = ONB::new(direction);
+        onb.local(Vec3::random_to_sphere(self.radius, distance_sq))
+    }
+
+    fn emitted_power(&self) -> f64 {
+        surface_emitted_power(&self.material, self.area())
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        let normal = Vec3::random_unit_vector();
+        surface_record(self, self.center + normal.mul_scalar(self.radius), normal)
+    }
 }
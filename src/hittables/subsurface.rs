@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use crate::linalg::{Color, Ray};
+use crate::materials::{Dielectric, Isotropic, Material};
+use crate::sampler;
+
+use super::{BoundingBox, Hit, HitRecord};
+
+// Closed boundary (e.g. a triangle mesh produced by `load_closed_mesh`, which the rays inside can
+// hit from behind) filled with a scattering medium.
+// Light enters and leaves through a dielectric interface, and random walks inside the boundary with
+// exponentially distributed steps of the given mean free path.
+pub struct Subsurface {
+    boundary: Arc<dyn Hit>,
+    interface: Arc<dyn Material>,
+    phase_function: Arc<dyn Material>,
+    mean_free_path: f64,
+}
+
+impl Subsurface {
+    pub fn new(
+        boundary: Arc<dyn Hit>,
+        refractive_index: f64,
+        albedo: Color,
+        mean_free_path: f64,
+    ) -> Self {
+        Self {
+            boundary,
+            interface: Arc::new(Dielectric::new(refractive_index)),
+            phase_function: Arc::new(Isotropic::new(albedo)),
+            mean_free_path,
+        }
+    }
+}
+
+impl Hit for Subsurface {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut boundary_hit = self.boundary.hit(ray, t_min, f64::INFINITY)?;
+
+        // Reaching the boundary through a back face means that the ray started inside the medium, so
+        // it might scatter before getting there.
+        if !boundary_hit.front_face {
+            let scatter_distance = -self.mean_free_path * sampler::random().ln();
+            let t = t_min + scatter_distance / ray.direction().length();
+            if t < boundary_hit.t.min(t_max) {
+                return Some(HitRecord::new(
+                    ray.at(t),
+                    ray.direction().into_unit_vec().mul_scalar(-1.0),
+                    self.phase_function.clone(),
+                    t,
+                    true,
+                ));
+            }
+        }
+
+        if boundary_hit.t > t_max {
+            return None;
+        }
+
+        boundary_hit.material = self.interface.clone();
+        Some(boundary_hit)
+    }
+
+    fn bounding_box(&self, t_min: f64, t_max: f64) -> Option<BoundingBox> {
+        self.boundary.bounding_box(t_min, t_max)
+    }
+}
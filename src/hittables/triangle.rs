@@ -1,14 +1,22 @@
 use std::sync::Arc;
 
-use crate::linalg::{Ray, Vec3};
+use crate::linalg::{Point3, Ray, Vec3};
 use crate::materials::Material;
+use crate::sampler;
 
-use super::{BoundingBox, Hit, HitRecord};
+use super::{
+    surface_emission_cone, surface_emitted_power, surface_record, Area, BoundingBox, EmissionCone,
+    Hit, HitRecord,
+};
 
 pub struct Triangle {
     vertices: [Vec3; 3],
     normal: Vec3,
+    texture_coords: [(f64, f64); 3],
     material: Arc<dyn Material>,
+    // Whether rays hit the triangle from behind as well, which closed meshes that rays travel inside
+    // of need. Back faces are culled otherwise.
+    double_sided: bool,
 }
 
 impl Triangle {
@@ -20,24 +28,45 @@ impl Triangle {
         Self {
             vertices,
             normal,
+            texture_coords: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
             material,
+            double_sided: false,
         }
     }
+
+    pub fn with_texture_coords(mut self, texture_coords: [(f64, f64); 3]) -> Self {
+        self.texture_coords = texture_coords;
+        self
+    }
+
+    pub fn with_double_sided(mut self, double_sided: bool) -> Self {
+        self.double_sided = double_sided;
+        self
+    }
+}
+
+impl Area for Triangle {
+    fn area(&self) -> f64 {
+        0.5 * (self.vertices[1] - self.vertices[0])
+            .cross(self.vertices[2] - self.vertices[0])
+            .length()
+    }
 }
 
 impl Hit for Triangle {
-    fn hit(&self, ray: Ray, _t_min: f64, _t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let edge0 = self.vertices[1] - self.vertices[0];
         let edge1 = self.vertices[2] - self.vertices[0];
         let pvec = ray.direction().cross(edge1);
         let det = edge0.dot(pvec);
-        if det < 0.0001 {
+        let facing = if self.double_sided { det.abs() } else { det };
+        if facing < 0.0001 {
             return None;
         }
         let inv_det = 1.0 / det;
         let tvec = *ray.origin() - self.vertices[0];
         let u = tvec.dot(pvec) * inv_det;
-        if u < 0.0 || u > 1.0 {
+        if !(0.0..=1.0).contains(&u) {
             return None;
         }
         let gvec = tvec.cross(edge0);
@@ -46,7 +75,7 @@ impl Hit for Triangle {
             return None;
         }
         let t = edge1.dot(gvec) * inv_det;
-        if t < 0.0001 {
+        if t < t_min.max(0.0001) || t > t_max {
             return None;
         }
 
@@ -54,6 +83,12 @@ impl Hit for Triangle {
         let mut hit_record =
             HitRecord::new(hit_point, Vec3::default(), self.material.clone(), t, false);
         hit_record.set_face_normal(ray, self.normal.into_unit_vec());
+        hit_record.set_tangent(edge0);
+        let [uv0, uv1, uv2] = self.texture_coords;
+        hit_record.set_texture_coords(
+            (1.0 - u - v) * uv0.0 + u * uv1.0 + v * uv2.0,
+            (1.0 - u - v) * uv0.1 + u * uv1.1 + v * uv2.1,
+        );
 
         Some(hit_record)
     }
@@ -81,4 +116,67 @@ impl Hit for Triangle {
 
         Some(BoundingBox::new(min, max))
     }
+
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        if let Some(hit_record) = self.hit(Ray::new(hit_point, direction), 0.001, f64::INFINITY) {
+            let distance_sq = hit_record.t * hit_record.t * direction.length_sq();
+            let cos_theta = direction.dot(hit_record.normal).abs() / direction.length();
+
+            distance_sq / (cos_theta * self.area())
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        // Uniformly distributed barycentric coordinates.
+        let sqrt_r1 = sampler::random().sqrt();
+        let r2: f64 = sampler::random();
+        let b0 = 1.0 - sqrt_r1;
+        let b1 = r2 * sqrt_r1;
+
+        let rand_point = self.vertices[0].mul_scalar(b0)
+            + self.vertices[1].mul_scalar(b1)
+            + self.vertices[2].mul_scalar(1.0 - b0 - b1);
+
+        rand_point - origin
+    }
+
+    fn emitted_power(&self) -> f64 {
+        surface_emitted_power(&self.material, self.area())
+    }
+
+    fn emission_cone(&self) -> EmissionCone {
+        surface_emission_cone(&self.material, self.normal)
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        surface_record(self, self.random(Point3::default()), self.normal)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::materials::DefaultMaterial;
+
+    #[test]
+    fn back_faces_are_only_hit_by_double_sided_triangles() {
+        let vertices = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+        let triangle = Triangle::new(vertices, Arc::new(DefaultMaterial::default()));
+        let front_ray = Ray::new(Point3::new(0.2, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let back_ray = Ray::new(Point3::new(0.2, 0.2, -1.0), Vec3::new(0.0, 0.0, 1.0));
+
+        assert!(triangle.hit(front_ray, 0.001, f64::INFINITY).is_some());
+        assert!(triangle.hit(back_ray, 0.001, f64::INFINITY).is_none());
+
+        let triangle = triangle.with_double_sided(true);
+        let hit_record = triangle.hit(back_ray, 0.001, f64::INFINITY).unwrap();
+        assert!(!hit_record.front_face);
+        assert!((hit_record.t - 1.0).abs() < 1e-12);
+    }
 }
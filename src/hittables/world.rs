@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
-use crate::linalg::Ray;
+use crate::linalg::{Point3, Ray, Vec3};
+use crate::sampler;
 
-use super::{BoundingBox, Hit, HitRecord};
+use super::{collect_emitters, BoundingBox, Hit, HitRecord};
 
 #[derive(Default)]
 pub struct World {
@@ -39,6 +40,9 @@ impl Hit for World {
 
         let mut closest_so_far = t_max;
         for object in &self.objects {
+            if !object.visibility().is_visible_to(ray.ray_type()) {
+                continue;
+            }
             let hit_result = object.hit(ray, t_min, closest_so_far);
             if let Some(hit_record) = hit_result {
                 closest_so_far = hit_record.t;
@@ -70,4 +74,41 @@ impl Hit for World {
             None
         }
     }
+
+    // Sampling a world as a light picks one of its objects uniformly and then samples that object.
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(hit_point, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let idx = sampler::random_index(self.objects.len());
+        self.objects[idx].random(origin)
+    }
+
+    fn emitted_power(&self) -> f64 {
+        self.objects
+            .iter()
+            .map(|object| object.emitted_power())
+            .sum()
+    }
+
+    fn emitters(&self) -> Option<Vec<Arc<dyn Hit>>> {
+        let mut emitters = Vec::new();
+        for object in &self.objects {
+            collect_emitters(object, &mut emitters);
+        }
+
+        Some(emitters)
+    }
 }
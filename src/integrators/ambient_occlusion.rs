@@ -0,0 +1,119 @@
+use crate::hittables::Hit;
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Ray, RayType};
+use crate::pdfs::{CosinePDF, PDF};
+use crate::scene::Scene;
+
+use super::Integrator;
+
+// Fraction of the hemisphere above the surfaces seen by the camera that is not blocked by nearby
+// objects, weighted by the cosine of the angle to the normal.
+pub struct AmbientOcclusion {
+    sample_num: u32,
+    // Distance beyond which objects no longer occlude, or None to use the radius of the scene.
+    distance: Option<f64>,
+}
+
+impl AmbientOcclusion {
+    pub fn new(sample_num: u32) -> Self {
+        Self {
+            sample_num,
+            distance: None,
+        }
+    }
+
+    pub fn with_distance(mut self, distance: f64) -> Self {
+        self.distance = Some(distance);
+        self
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn ray_color(&self, ray: Ray, scene: &Scene, _light_groups: LightGroups) -> Color {
+        let hit_record = match scene.world.hit(ray, 0.001, f64::INFINITY) {
+            Some(hit_record) => hit_record,
+            None => return Color::from_scalar(1.0),
+        };
+
+        let distance = self.distance.unwrap_or_else(|| scene.radius());
+        let pdf = CosinePDF::new(hit_record.normal);
+        let unoccluded_num = (0..self.sample_num)
+            .filter(|_| {
+                let direction = pdf.generate();
+                let occlusion_ray =
+                    Ray::new(hit_record.hit_point, direction).with_type(RayType::Shadow);
+                let t_max = distance / direction.length();
+                scene.world.hit(occlusion_ray, 0.001, t_max).is_none()
+            })
+            .count();
+
+        Color::from_scalar(unoccluded_num as f64 / self.sample_num.max(1) as f64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::camera::Camera;
+    use crate::hittables::{World, XZRect};
+    use crate::lights::{LightList, LightSelection};
+    use crate::linalg::{Point3, Vec3};
+    use crate::materials::Lambertian;
+
+    // Floor seen from above, under a ceiling at a height of 1 if there is one.
+    fn floor_scene(ceiling: bool) -> Scene {
+        let material = Arc::new(Lambertian::new(Color::from_scalar(0.5)));
+        let mut world = World::default();
+        world.add(Arc::new(XZRect::new(
+            -10.0,
+            10.0,
+            -10.0,
+            10.0,
+            0.0,
+            material.clone(),
+        )));
+        if ceiling {
+            world.add(Arc::new(XZRect::new(
+                -10.0, 10.0, -10.0, 10.0, 1.0, material,
+            )));
+        }
+        let lights = LightList::from_world(&world, LightSelection::Power);
+        let camera = Camera::new(
+            Point3::new(0.0, 0.5, 0.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            40.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        Scene::new(world, lights, camera, 1.0, Arc::new(Color::default()))
+    }
+
+    #[test]
+    fn unoccluded_plane_is_white() {
+        let scene = floor_scene(false);
+        let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let color = AmbientOcclusion::new(64).ray_color(ray, &scene, LightGroups::all());
+
+        assert_eq!(color, Color::from_scalar(1.0));
+    }
+
+    #[test]
+    fn only_nearby_objects_occlude() {
+        let scene = floor_scene(true);
+        let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        // The ceiling hides all but the directions that graze the floor.
+        let occluded = AmbientOcclusion::new(256).ray_color(ray, &scene, LightGroups::all());
+        assert!(occluded.luminance() < 0.1, "{:?}", occluded);
+
+        let unoccluded =
+            AmbientOcclusion::new(64)
+                .with_distance(0.5)
+                .ray_color(ray, &scene, LightGroups::all());
+        assert_eq!(unoccluded, Color::from_scalar(1.0));
+    }
+}
@@ -0,0 +1,714 @@
+use crate::film::Film;
+use crate::hittables::{Hit, HitRecord};
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Point3, Ray, RayType};
+use crate::pdfs::MisHeuristic;
+use crate::scene::Scene;
+
+use super::emitters::{
+    emission_direction_pdf, emitted_radiance, refaced, sample_emission_direction, EmitterSampler,
+};
+use super::{
+    delta_light_color, linked_emission, russian_roulette, splats_onto_film, Integrator, PathTracer,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+// Vertex of a camera or light subpath.
+#[derive(Clone)]
+struct Vertex {
+    kind: VertexKind,
+    point: Point3,
+    // Surface that the vertex is on, which for light vertices is the point sampled on the emitter.
+    hit_record: Option<HitRecord>,
+    // Throughput of the subpath up to the vertex.
+    beta: Color,
+    // Densities per unit area of sampling the vertex from the previous vertex of its subpath, and
+    // from the next one if the path was traced in the opposite direction.
+    pdf_fwd: f64,
+    pdf_rev: f64,
+    // Whether the vertex scatters light in a single direction, so that it can't be connected to.
+    delta: bool,
+}
+
+impl Vertex {
+    fn camera(point: Point3) -> Self {
+        Self {
+            kind: VertexKind::Camera,
+            point,
+            hit_record: None,
+            beta: Color::from_scalar(1.0),
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    fn light(hit_record: HitRecord, pdf: f64) -> Self {
+        Self {
+            kind: VertexKind::Light,
+            point: hit_record.hit_point,
+            hit_record: Some(hit_record),
+            beta: Color::from_scalar(1.0 / pdf),
+            pdf_fwd: pdf,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    fn surface(hit_record: HitRecord, beta: Color) -> Self {
+        Self {
+            kind: VertexKind::Surface,
+            point: hit_record.hit_point,
+            hit_record: Some(hit_record),
+            beta,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    fn hit_record(&self) -> &HitRecord {
+        self.hit_record.as_ref().unwrap()
+    }
+
+    // Converts a density per unit solid angle of the direction from the vertex towards another one
+    // into a density per unit area at the other vertex.
+    fn to_area_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let direction = next.point - self.point;
+        let distance_sq = direction.length_sq();
+        if distance_sq == 0.0 {
+            return 0.0;
+        }
+
+        let cos_theta = match &next.hit_record {
+            Some(hit_record) => hit_record.normal.dot(direction).abs() / distance_sq.sqrt(),
+            None => 1.0,
+        };
+        pdf * cos_theta / distance_sq
+    }
+
+    // Light that the vertex sends towards the next vertex, for each unit of light arriving from the
+    // previous one, including the cosine with the normal on the side of the light. Light vertices
+    // send the radiance they emit.
+    fn f(&self, prev: Option<&Vertex>, next: &Vertex) -> Color {
+        let direction = next.point - self.point;
+        match self.kind {
+            VertexKind::Camera => Color::default(),
+            VertexKind::Light => {
+                let hit_record = self.hit_record();
+                let cos_theta = hit_record.normal.dot(direction).abs() / direction.length();
+                emitted_radiance(hit_record, direction).mul_scalar(cos_theta)
+            }
+            VertexKind::Surface => {
+                let ray_in = Ray::new(prev.unwrap().point, self.point - prev.unwrap().point);
+                let hit_record = refaced(self.hit_record(), ray_in);
+                match hit_record.material.scatter(ray_in, &hit_record) {
+                    Some(scatter_record) if scatter_record.specular_ray.is_none() => {
+                        hit_record.material.scattering_color(
+                            ray_in,
+                            Ray::new(self.point, direction),
+                            &hit_record,
+                            scatter_record.attenuation,
+                        )
+                    }
+                    _ => Color::default(),
+                }
+            }
+        }
+    }
+
+    // Density per unit area of the next vertex being sampled from this one.
+    fn pdf(&self, scene: &Scene, film: &Film, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        let direction = next.point - self.point;
+        let pdf = match self.kind {
+            VertexKind::Camera => scene.camera.direction_pdf(direction) / film.area(),
+            VertexKind::Light => emission_direction_pdf(self.hit_record(), direction),
+            VertexKind::Surface => return self.scattering_pdf(prev.unwrap(), next),
+        };
+
+        self.to_area_density(pdf, next)
+    }
+
+    // Density per unit area of the next vertex being sampled by scattering the light that arrives
+    // from the previous one, for vertices on surfaces.
+    fn scattering_pdf(&self, prev: &Vertex, next: &Vertex) -> f64 {
+        let ray_in = Ray::new(prev.point, self.point - prev.point);
+        let hit_record = refaced(self.hit_record(), ray_in);
+        let pdf = match hit_record.material.scatter(ray_in, &hit_record) {
+            Some(scatter_record) if scatter_record.specular_ray.is_none() => {
+                scatter_record.pdf.unwrap().value(next.point - self.point)
+            }
+            _ => 0.0,
+        };
+
+        self.to_area_density(pdf, next)
+    }
+
+    // Density per unit area of the next vertex being sampled from this one if it were a point
+    // sampled on an emitter.
+    fn emission_pdf(&self, next: &Vertex) -> f64 {
+        let pdf = emission_direction_pdf(self.hit_record(), next.point - self.point);
+        self.to_area_density(pdf, next)
+    }
+}
+
+// Densities of a vertex, copied so that they can be changed for a particular way of connecting the
+// subpaths.
+#[derive(Copy, Clone)]
+struct VertexPdfs {
+    pdf_fwd: f64,
+    pdf_rev: f64,
+    delta: bool,
+}
+
+impl From<&Vertex> for VertexPdfs {
+    fn from(vertex: &Vertex) -> Self {
+        Self {
+            pdf_fwd: vertex.pdf_fwd,
+            pdf_rev: vertex.pdf_rev,
+            delta: vertex.delta,
+        }
+    }
+}
+
+pub(super) fn visible(scene: &Scene, from: Point3, to: Point3) -> bool {
+    let direction = to - from;
+    let distance = direction.length();
+    let shadow_ray = Ray::new(from, direction.div_scalar(distance)).with_type(RayType::Shadow);
+    scene
+        .world
+        .hit(shadow_ray, 0.001, distance - 0.001)
+        .is_none()
+}
+
+// Bidirectional path tracer, which traces a subpath from the camera and another one from a point on
+// an emitter for each camera sample, and connects every vertex of one to every vertex of the other.
+// The different ways of building the same path are combined with multiple importance sampling, and
+// light subpaths that connect to the camera directly are splatted onto the film.
+//
+// Light from the background and from delta lights is only gathered from the camera subpaths.
+pub struct BidirectionalPathTracer {
+    mis_heuristic: MisHeuristic,
+    // Maximum number of rays in the paths built by connecting the subpaths.
+    max_depth: i32,
+    // Number of rays traced in each subpath before it starts being terminated with Russian roulette.
+    rr_depth: i32,
+    emitters: EmitterSampler,
+    // Traces the rays that don't start at the camera (e.g. the ones leaving shadow catchers), which
+    // have no film to splat onto.
+    path_tracer: PathTracer,
+}
+
+impl BidirectionalPathTracer {
+    pub fn new(scene: &Scene, mis_heuristic: MisHeuristic, max_depth: i32) -> Self {
+        Self {
+            mis_heuristic,
+            max_depth,
+            rr_depth: max_depth,
+            emitters: EmitterSampler::new(scene),
+            path_tracer: PathTracer::new(mis_heuristic, max_depth),
+        }
+    }
+
+    pub fn with_rr_depth(mut self, rr_depth: i32) -> Self {
+        self.rr_depth = rr_depth;
+        self.path_tracer = self.path_tracer.with_rr_depth(rr_depth);
+        self
+    }
+
+    // Extends a subpath that starts with a ray sampled from its last vertex with the given density
+    // per unit solid angle, until it has the given number of vertices, leaves the scene, or is
+    // terminated. Returns the ray that left the scene and the throughput it carries, if any.
+    fn random_walk(
+        &self,
+        scene: &Scene,
+        mut ray: Ray,
+        mut beta: Color,
+        mut pdf: f64,
+        max_vertex_num: usize,
+        path: &mut Vec<Vertex>,
+    ) -> Option<(Ray, Color)> {
+        while path.len() < max_vertex_num {
+            let hit_record = match scene.world.hit(ray, 0.001, f64::INFINITY) {
+                Some(hit_record) => hit_record,
+                None => return Some((ray, beta)),
+            };
+
+            let mut vertex = Vertex::surface(hit_record, beta);
+            vertex.pdf_fwd = path.last().unwrap().to_area_density(pdf, &vertex);
+            let hit_record = vertex.hit_record().clone();
+            path.push(vertex);
+            let vertex_num = path.len();
+
+            let scatter_record = match hit_record.material.scatter(ray, &hit_record) {
+                Some(scatter_record) => scatter_record,
+                None => break,
+            };
+            if let Some(specular_ray) = scatter_record.specular_ray {
+                path[vertex_num - 1].delta = true;
+                beta *= scatter_record.attenuation;
+                pdf = 0.0;
+                ray = specular_ray.with_type(RayType::Specular);
+            } else {
+                let material_pdf = scatter_record.pdf.as_ref().unwrap();
+                let scatter_ray = Ray::new(hit_record.hit_point, material_pdf.generate())
+                    .with_type(RayType::Diffuse);
+                pdf = material_pdf.value(*scatter_ray.direction());
+                if pdf <= 0.0 {
+                    break;
+                }
+                beta *= hit_record
+                    .material
+                    .scattering_color(ray, scatter_ray, &hit_record, scatter_record.attenuation)
+                    .div_scalar(pdf);
+
+                // Density of sampling the previous vertex if the path came the other way.
+                let next = Vertex::camera(hit_record.hit_point + *scatter_ray.direction());
+                path[vertex_num - 2].pdf_rev =
+                    path[vertex_num - 1].scattering_pdf(&next, &path[vertex_num - 2]);
+                ray = scatter_ray;
+            }
+
+            if vertex_num as i32 > self.rr_depth && !russian_roulette(&mut beta) {
+                break;
+            }
+        }
+
+        None
+    }
+
+    // Subpath starting at a point sampled on one of the emitters.
+    fn light_subpath(&self, scene: &Scene) -> Vec<Vertex> {
+        let mut path = Vec::new();
+        let (hit_record, pdf) = match self.emitters.sample() {
+            Some(sample) => sample,
+            None => return path,
+        };
+        let (direction, direction_pdf) = sample_emission_direction(&hit_record);
+        if pdf <= 0.0 || direction_pdf <= 0.0 {
+            return path;
+        }
+
+        let light_vertex = Vertex::light(hit_record, pdf);
+        let ray = Ray::new(light_vertex.point, direction).with_type(RayType::Diffuse);
+        let beta = light_vertex.beta
+            * light_vertex
+                .f(None, &Vertex::camera(light_vertex.point + direction))
+                .div_scalar(direction_pdf);
+        path.push(light_vertex);
+        self.random_walk(
+            scene,
+            ray,
+            beta,
+            direction_pdf,
+            self.max_depth as usize,
+            &mut path,
+        );
+
+        // The light only goes further if the emitter is linked to the first surface it reaches.
+        if path.len() > 1
+            && !path[0]
+                .hit_record()
+                .light_links
+                .intersects(path[1].hit_record().light_groups)
+        {
+            path.truncate(1);
+        }
+
+        path
+    }
+
+    // Weight of the path made by connecting `qs` (or nothing, to use the camera subpath on its own)
+    // to `pt`, against the other ways of sampling the same path. The vertices before them in their
+    // subpaths are `light_prefix` and `camera_prefix`.
+    fn mis_weight(
+        &self,
+        scene: &Scene,
+        film: &Film,
+        light_prefix: &[Vertex],
+        qs: Option<&Vertex>,
+        camera_prefix: &[Vertex],
+        pt: &Vertex,
+    ) -> f64 {
+        let mut light_pdfs: Vec<VertexPdfs> = light_prefix
+            .iter()
+            .chain(qs)
+            .map(VertexPdfs::from)
+            .collect();
+        let mut camera_pdfs: Vec<VertexPdfs> = camera_prefix
+            .iter()
+            .chain(Some(pt))
+            .map(VertexPdfs::from)
+            .collect();
+        let (s, t) = (light_pdfs.len(), camera_pdfs.len());
+        if s + t == 2 {
+            return 1.0;
+        }
+        let qs_minus = light_prefix.last();
+        let pt_minus = camera_prefix.last();
+
+        // The densities of the vertices next to the connection change, since they are now sampled
+        // from the other subpath.
+        camera_pdfs[t - 1].pdf_rev = match qs {
+            Some(qs) => qs.pdf(scene, film, qs_minus, pt),
+            None => self.emitters.pdf(pt.hit_record()),
+        };
+        camera_pdfs[t - 1].delta = false;
+        if let Some(pt_minus) = pt_minus {
+            camera_pdfs[t - 2].pdf_rev = match qs {
+                Some(qs) => pt.pdf(scene, film, Some(qs), pt_minus),
+                None => pt.emission_pdf(pt_minus),
+            };
+        }
+        if let Some(qs) = qs {
+            light_pdfs[s - 1].pdf_rev = pt.pdf(scene, film, pt_minus, qs);
+            light_pdfs[s - 1].delta = false;
+            if let Some(qs_minus) = qs_minus {
+                light_pdfs[s - 2].pdf_rev = qs.pdf(scene, film, Some(pt), qs_minus);
+            }
+        }
+
+        // Deltas have no density, and are left out of the ratios since they are the same for every
+        // strategy that can sample them.
+        let remap = |pdf: f64| if pdf != 0.0 { pdf } else { 1.0 };
+        let ratio = |pdfs: &VertexPdfs| {
+            let ratio = remap(pdfs.pdf_rev) / remap(pdfs.pdf_fwd);
+            match self.mis_heuristic {
+                MisHeuristic::Balance => ratio,
+                MisHeuristic::Power => ratio * ratio,
+            }
+        };
+
+        let mut ratio_sum = 0.0;
+        let mut path_ratio = 1.0;
+        for i in (1..t).rev() {
+            path_ratio *= ratio(&camera_pdfs[i]);
+            if !camera_pdfs[i].delta && !camera_pdfs[i - 1].delta {
+                ratio_sum += path_ratio;
+            }
+        }
+        path_ratio = 1.0;
+        for i in (0..s).rev() {
+            path_ratio *= ratio(&light_pdfs[i]);
+            let prev_delta = i > 0 && light_pdfs[i - 1].delta;
+            if !light_pdfs[i].delta && !prev_delta {
+                ratio_sum += path_ratio;
+            }
+        }
+
+        1.0 / (1.0 + ratio_sum)
+    }
+
+    // Light carried by the path that ends at the t-th vertex of the camera subpath (t >= 2), with the
+    // first `s` vertices of the light subpath.
+    fn connect(
+        &self,
+        scene: &Scene,
+        film: &Film,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+    ) -> Color {
+        let pt = &camera_path[t - 1];
+        let pt_minus = &camera_path[t - 2];
+        if s == 0 {
+            let hit_record = pt.hit_record();
+            let light_groups = match &pt_minus.hit_record {
+                Some(hit_record) => hit_record.light_groups,
+                None => LightGroups::all(),
+            };
+            let radiance = linked_emission(
+                Ray::new(pt_minus.point, pt.point - pt_minus.point),
+                hit_record,
+                light_groups,
+            );
+            if radiance == Color::default() {
+                return Color::default();
+            }
+            // Emitters that the light subpaths can't start from are only found this way.
+            let weight = if self.emitters.pdf(hit_record) > 0.0 {
+                self.mis_weight(scene, film, &[], None, &camera_path[..t - 1], pt)
+            } else {
+                1.0
+            };
+            return (pt.beta * radiance).mul_scalar(weight);
+        }
+
+        if pt.delta {
+            return Color::default();
+        }
+
+        let sampled_light = if s == 1 {
+            match self.emitters.sample() {
+                Some((hit_record, pdf)) if pdf > 0.0 => Some(Vertex::light(hit_record, pdf)),
+                _ => return Color::default(),
+            }
+        } else {
+            None
+        };
+        let qs = sampled_light.as_ref().unwrap_or(&light_path[s - 1]);
+        if qs.delta {
+            return Color::default();
+        }
+        if s == 1
+            && !qs
+                .hit_record()
+                .light_links
+                .intersects(pt.hit_record().light_groups)
+        {
+            return Color::default();
+        }
+        let qs_minus = if s > 1 {
+            Some(&light_path[s - 2])
+        } else {
+            None
+        };
+
+        let color = qs.beta * qs.f(qs_minus, pt) * pt.f(Some(pt_minus), qs) * pt.beta;
+        if color == Color::default() || !visible(scene, pt.point, qs.point) {
+            return Color::default();
+        }
+
+        let weight = self.mis_weight(
+            scene,
+            film,
+            &light_path[..s - 1],
+            Some(qs),
+            &camera_path[..t - 1],
+            pt,
+        );
+        color.div_scalar((qs.point - pt.point).length_sq()) * Color::from_scalar(weight)
+    }
+
+    // Connects the s-th vertex of the light subpath to a point on the lens, splatting the light it
+    // carries onto the film.
+    fn splat(&self, scene: &Scene, film: &Film, light_path: &[Vertex], s: usize) {
+        let qs = &light_path[s - 1];
+        if qs.delta || !splats_onto_film(film, qs.hit_record()) {
+            return;
+        }
+        let camera_sample = match scene.camera.project(qs.point) {
+            Some(camera_sample) => camera_sample,
+            None => return,
+        };
+
+        let camera_vertex = Vertex::camera(camera_sample.lens_point);
+        let qs_minus = if s > 1 {
+            Some(&light_path[s - 2])
+        } else {
+            None
+        };
+        let color = qs.beta * qs.f(qs_minus, &camera_vertex);
+        if color == Color::default() || !visible(scene, qs.point, camera_vertex.point) {
+            return;
+        }
+
+        let weight = self.mis_weight(
+            scene,
+            film,
+            &light_path[..s - 1],
+            Some(qs),
+            &[],
+            &camera_vertex,
+        );
+        let distance_sq = (camera_vertex.point - qs.point).length_sq();
+        film.add_splat(
+            camera_sample.s,
+            camera_sample.t,
+            color.mul_scalar(camera_sample.pdf * weight / distance_sq),
+        );
+    }
+}
+
+impl Integrator for BidirectionalPathTracer {
+    fn ray_color(&self, ray: Ray, scene: &Scene, light_groups: LightGroups) -> Color {
+        self.path_tracer.ray_color(ray, scene, light_groups)
+    }
+
+    fn camera_ray_color(&self, ray: Ray, scene: &Scene, film: &Film) -> Color {
+        let mut camera_path = vec![Vertex::camera(*ray.origin())];
+        let pdf = scene.camera.direction_pdf(*ray.direction()) / film.area();
+        let escaped = self.random_walk(
+            scene,
+            ray,
+            Color::from_scalar(1.0),
+            pdf,
+            self.max_depth as usize + 1,
+            &mut camera_path,
+        );
+        let light_path = self.light_subpath(scene);
+
+        let mut color = Color::default();
+        if let Some((escaped_ray, beta)) = escaped {
+            color += beta * scene.background.radiance(*escaped_ray.direction());
+        }
+
+        for t in 1..=camera_path.len() {
+            if t > 1 && !camera_path[t - 1].delta {
+                let vertex = &camera_path[t - 1];
+                let hit_record = vertex.hit_record();
+                let ray_in = Ray::new(
+                    camera_path[t - 2].point,
+                    vertex.point - camera_path[t - 2].point,
+                );
+                if let Some(scatter_record) = hit_record.material.scatter(ray_in, hit_record) {
+                    color += vertex.beta
+                        * delta_light_color(ray_in, hit_record, scatter_record.attenuation, scene);
+                }
+            }
+
+            for s in 0..=light_path.len() {
+                if s + t < 2 || s + t - 1 > self.max_depth as usize {
+                    continue;
+                }
+                if t == 1 {
+                    // Emitters seen straight from the camera are left to the camera subpaths,
+                    // which find them with a weight of 1.
+                    if s > 1 {
+                        self.splat(scene, film, &light_path, s);
+                    }
+                } else {
+                    color += self.connect(scene, film, &light_path, &camera_path, s, t);
+                }
+            }
+        }
+
+        color
+    }
+
+    // The light subpath that the sample would have been connected to is still splatted, or the
+    // splats would only get the light paths of the samples that see opaque surfaces.
+    fn skipped_camera_sample(&self, scene: &Scene, film: &Film) {
+        let light_path = self.light_subpath(scene);
+        for s in 2..=light_path.len().min(self.max_depth as usize) {
+            self.splat(scene, film, &light_path, s);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::camera::Camera;
+    use crate::hittables::{FlipFace, ObjectFlags, World, XZRect};
+    use crate::lights::{LightList, LightSelection};
+    use crate::linalg::Vec3;
+    use crate::materials::{DiffuseLight, Lambertian};
+
+    // Diffuse floor in the given light-linking groups under a square light that faces down and only
+    // lights group 0, seen by a camera that looks at the given point.
+    fn floor_under_light(floor_groups: LightGroups, look_at: Point3) -> Scene {
+        let floor = Arc::new(XZRect::new(
+            -2.0,
+            2.0,
+            -2.0,
+            2.0,
+            0.0,
+            Arc::new(Lambertian::new(Color::from_scalar(0.5))),
+        ));
+        let mut world = World::default();
+        world.add(Arc::new(
+            ObjectFlags::new(floor).with_light_groups(floor_groups),
+        ));
+        let light = Arc::new(FlipFace::new(Arc::new(XZRect::new(
+            -0.5,
+            0.5,
+            -0.5,
+            0.5,
+            1.0,
+            Arc::new(DiffuseLight::new(Color::from_scalar(4.0))),
+        ))));
+        world.add(Arc::new(
+            ObjectFlags::new(light).with_light_links(LightGroups::default()),
+        ));
+        let lights = LightList::from_world(&world, LightSelection::Power);
+        let camera = Camera::new(
+            Point3::new(0.0, 0.5, 3.0),
+            look_at,
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            10.0,
+        );
+        Scene::new(world, lights, camera, 1.0, Arc::new(Color::default()))
+    }
+
+    // Average luminance of the pixels rendered by the path tracer and by the bidirectional path
+    // tracer, once the light that the bidirectional one splats is added to its samples.
+    fn average_luminances(scene: &Scene, samples_per_pixel: u32) -> (f64, f64) {
+        let film = Film::new(16, 16);
+        let path_tracer = PathTracer::new(MisHeuristic::Power, 5);
+        let bidirectional = BidirectionalPathTracer::new(scene, MisHeuristic::Power, 5);
+        let (mut path_sum, mut bidirectional_sum) = (0.0, 0.0);
+        for j in 0..film.height() {
+            for i in 0..film.width() {
+                for _ in 0..samples_per_pixel {
+                    let (s, t) = film.sample_pixel(i, j);
+                    let ray = scene.camera.get_ray(s, t);
+                    path_sum += path_tracer.camera_ray_color(ray, scene, &film).luminance();
+
+                    let (s, t) = film.sample_pixel(i, j);
+                    let ray = scene.camera.get_ray(s, t);
+                    bidirectional_sum += bidirectional
+                        .camera_ray_color(ray, scene, &film)
+                        .luminance();
+                }
+            }
+        }
+
+        let pixel_num = film.pixel_num() as f64;
+        let path_average = path_sum / (pixel_num * samples_per_pixel as f64);
+        let mut bidirectional_average = bidirectional_sum / (pixel_num * samples_per_pixel as f64);
+        for j in 0..film.height() {
+            for i in 0..film.width() {
+                bidirectional_average +=
+                    film.splat_color(i, j, samples_per_pixel).luminance() / pixel_num;
+            }
+        }
+
+        (path_average, bidirectional_average)
+    }
+
+    // Both integrators estimate the same image, so their averages must agree, including where the
+    // camera sees the light directly.
+    #[test]
+    fn converges_to_path_tracing() {
+        let scene = floor_under_light(LightGroups::default(), Point3::default());
+        let (path_average, bidirectional_average) = average_luminances(&scene, 300);
+
+        assert!(
+            (bidirectional_average - path_average).abs() < 0.05 * path_average,
+            "bidirectional average {}, path average {}",
+            bidirectional_average,
+            path_average
+        );
+    }
+
+    // The floor is lit once it is in one of the groups of the light, and only then.
+    #[test]
+    fn light_links_match_path_tracing() {
+        // The camera sees the floor below the light.
+        let look_at = Point3::new(0.0, 0.0, 1.5);
+        let unlinked = floor_under_light(LightGroups::from_groups(&[1]), look_at);
+        assert_eq!(average_luminances(&unlinked, 10), (0.0, 0.0));
+
+        let linked = floor_under_light(LightGroups::from_groups(&[0, 1]), look_at);
+        let (path_average, bidirectional_average) = average_luminances(&linked, 50);
+        assert!(
+            (bidirectional_average - path_average).abs() < 0.05 * path_average,
+            "bidirectional average {}, path average {}",
+            bidirectional_average,
+            path_average
+        );
+    }
+}
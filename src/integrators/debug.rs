@@ -0,0 +1,191 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::film::Film;
+use crate::hittables::{Hit, HitRecord};
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Ray};
+use crate::scene::Scene;
+
+use super::Integrator;
+
+// Property of the surfaces seen by the camera that is shown as a color.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DebugOutput {
+    // Normals facing the camera, mapped from [-1, 1] to [0, 1].
+    Normals,
+    // Texture coordinates in the red and green channels.
+    TextureCoords,
+    // Distance to the camera, from white at the front of the sphere bounding the scene to black at
+    // its back.
+    Depth,
+    // A different random color for each material, which stays the same between renders of the same
+    // scene.
+    MaterialIds,
+}
+
+// Shows a property of the first surface hit by each camera ray, for diagnosing scenes quickly.
+pub struct DebugIntegrator {
+    output: DebugOutput,
+    // IDs of the materials seen through the centers of the pixels, numbered in the order they are
+    // first seen row by row and keyed by their address, which changes between runs.
+    material_ids: HashMap<usize, u32>,
+}
+
+impl DebugIntegrator {
+    pub fn new(output: DebugOutput) -> Self {
+        Self {
+            output,
+            material_ids: HashMap::new(),
+        }
+    }
+
+    // ID of the material of a hit point. Materials that can't be seen through any pixel center
+    // share one.
+    fn material_id(&self, hit_record: &HitRecord) -> u32 {
+        let address = Arc::as_ptr(&hit_record.material) as *const () as usize;
+        self.material_ids.get(&address).copied().unwrap_or(u32::MAX)
+    }
+}
+
+impl Integrator for DebugIntegrator {
+    fn ray_color(&self, ray: Ray, scene: &Scene, _light_groups: LightGroups) -> Color {
+        let hit_record = match scene.world.hit(ray, 0.001, f64::INFINITY) {
+            Some(hit_record) => hit_record,
+            None => return Color::default(),
+        };
+
+        match self.output {
+            DebugOutput::Normals => {
+                let normal = hit_record.normal;
+                Color::new(
+                    0.5 * (normal.x() + 1.0),
+                    0.5 * (normal.y() + 1.0),
+                    0.5 * (normal.z() + 1.0),
+                )
+            }
+            DebugOutput::TextureCoords => Color::new(hit_record.u, hit_record.v, 0.0),
+            DebugOutput::Depth => {
+                let distance = hit_record.t * ray.direction().length();
+                let radius = scene.radius();
+                let center_distance = match scene.world.bounding_box(0.0, 1.0) {
+                    Some(bounding_box) => {
+                        let center =
+                            (bounding_box.min_corner() + bounding_box.max_corner()).mul_scalar(0.5);
+                        (center - *ray.origin()).length()
+                    }
+                    None => radius,
+                };
+                let near = (center_distance - radius).max(0.0);
+                let far = center_distance + radius;
+                Color::from_scalar(1.0 - ((distance - near) / (far - near)).clamp(0.0, 1.0))
+            }
+            DebugOutput::MaterialIds => {
+                let mut hasher = DefaultHasher::new();
+                self.material_id(&hit_record).hash(&mut hasher);
+                let [r, g, b, ..] = hasher.finish().to_le_bytes();
+                Color::new(r as f64, g as f64, b as f64).div_scalar(255.0)
+            }
+        }
+    }
+
+    fn begin_pass(&mut self, scene: &Scene, film: &Film, pass: u32, _samples_per_pixel: u32) {
+        if self.output != DebugOutput::MaterialIds || pass > 0 {
+            return;
+        }
+
+        for j in 0..film.height() {
+            for i in 0..film.width() {
+                let s = (i as f64 + 0.5) / (film.width() - 1) as f64;
+                let t = (film.height() - j) as f64 + 0.5;
+                let ray = scene.camera.get_ray(s, t / (film.height() - 1) as f64);
+                if let Some(hit_record) = scene.world.hit(ray, 0.001, f64::INFINITY) {
+                    let address = Arc::as_ptr(&hit_record.material) as *const () as usize;
+                    let id = self.material_ids.len() as u32;
+                    self.material_ids.entry(address).or_insert(id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::hittables::{Sphere, World};
+    use crate::lights::{LightList, LightSelection};
+    use crate::linalg::{Point3, Vec3};
+    use crate::materials::Lambertian;
+
+    // Sphere of radius 1 at the origin on the left of another one, each with its own material.
+    fn two_spheres() -> Scene {
+        let mut world = World::default();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::from_scalar(0.5))),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(3.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::from_scalar(0.5))),
+        )));
+        let lights = LightList::from_world(&world, LightSelection::Power);
+        let camera = Camera::new(
+            Point3::new(1.5, 0.0, 10.0),
+            Point3::new(1.5, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            10.0,
+        );
+        Scene::new(world, lights, camera, 1.0, Arc::new(Color::default()))
+    }
+
+    #[test]
+    fn normals_are_mapped_to_colors() {
+        let scene = two_spheres();
+        let integrator = DebugIntegrator::new(DebugOutput::Normals);
+
+        let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let color = integrator.ray_color(ray, &scene, LightGroups::all());
+        assert_eq!(color, Color::new(0.5, 0.5, 1.0));
+
+        let ray = Ray::new(Point3::new(0.0, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let color = integrator.ray_color(ray, &scene, LightGroups::all());
+        assert_eq!(color, Color::new(0.5, 0.0, 0.5));
+    }
+
+    #[test]
+    fn material_ids_follow_the_order_materials_are_seen_in() {
+        let scene = two_spheres();
+        let film = Film::new(16, 16);
+        let mut integrator = DebugIntegrator::new(DebugOutput::MaterialIds);
+        integrator.begin_pass(&scene, &film, 0, 1);
+
+        let left = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let right = Ray::new(Point3::new(3.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let left_hit = scene.world.hit(left, 0.001, f64::INFINITY).unwrap();
+        let right_hit = scene.world.hit(right, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(integrator.material_id(&left_hit), 0);
+        assert_eq!(integrator.material_id(&right_hit), 1);
+
+        // The colors only depend on the IDs, so they are the same for another copy of the scene.
+        let other_scene = two_spheres();
+        let mut other_integrator = DebugIntegrator::new(DebugOutput::MaterialIds);
+        other_integrator.begin_pass(&other_scene, &film, 0, 1);
+        let color = integrator.ray_color(left, &scene, LightGroups::all());
+        assert_eq!(
+            color,
+            other_integrator.ray_color(left, &other_scene, LightGroups::all())
+        );
+        assert_ne!(
+            color,
+            integrator.ray_color(right, &scene, LightGroups::all())
+        );
+    }
+}
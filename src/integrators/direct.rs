@@ -0,0 +1,83 @@
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Ray};
+use crate::pdfs::MisHeuristic;
+use crate::scene::Scene;
+
+use super::{Integrator, PathTracer};
+
+// Light that reaches the surfaces seen by the camera straight from the lights, without any diffuse
+// interreflections. Specular surfaces are still followed so that mirrors and glass show something.
+pub struct DirectLighting {
+    path_tracer: PathTracer,
+}
+
+impl DirectLighting {
+    pub fn new(mis_heuristic: MisHeuristic, max_specular_depth: i32) -> Self {
+        Self {
+            path_tracer: PathTracer::direct_only(mis_heuristic, max_specular_depth),
+        }
+    }
+}
+
+impl Integrator for DirectLighting {
+    fn ray_color(&self, ray: Ray, scene: &Scene, light_groups: LightGroups) -> Color {
+        self.path_tracer.ray_color(ray, scene, light_groups)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::camera::Camera;
+    use crate::hittables::{World, XZRect, YZRect};
+    use crate::lights::{LightList, LightSelection, PointLight};
+    use crate::linalg::{Point3, Vec3};
+    use crate::materials::Lambertian;
+
+    #[test]
+    fn ignores_light_reflected_by_other_surfaces() {
+        // Floor under a point light, next to a white wall that would reflect some of its light onto
+        // the floor.
+        let mut world = World::default();
+        world.add(Arc::new(XZRect::new(
+            -2.0,
+            2.0,
+            -2.0,
+            2.0,
+            0.0,
+            Arc::new(Lambertian::new(Color::from_scalar(0.5))),
+        )));
+        world.add(Arc::new(YZRect::new(
+            0.0,
+            2.0,
+            -2.0,
+            2.0,
+            -0.5,
+            Arc::new(Lambertian::new(Color::from_scalar(1.0))),
+        )));
+        let lights = LightList::from_world(&world, LightSelection::Power);
+        let camera = Camera::new(
+            Point3::new(0.0, 0.5, 0.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            40.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let mut scene = Scene::new(world, lights, camera, 1.0, Arc::new(Color::default()));
+        scene.add_delta_light(Arc::new(PointLight::new(
+            Point3::new(0.0, 1.0, 0.0),
+            Color::from_scalar(2.0),
+        )));
+
+        // Only the light from the point light, whose irradiance at a distance of 1 is its intensity.
+        let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let color =
+            DirectLighting::new(MisHeuristic::Power, 5).ray_color(ray, &scene, LightGroups::all());
+        let expected = 0.5 * 2.0 / std::f64::consts::PI;
+        assert!((color.luminance() - expected).abs() < 1e-9, "{:?}", color);
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use crate::hittables::{collect_emitters, Hit, HitRecord};
+use crate::linalg::{Color, Ray, Vec3, ONB};
+use crate::pdfs::Distribution1D;
+use crate::sampler;
+use crate::scene::Scene;
+
+// Picks points on the emissive surfaces of a scene, for integrators that trace paths starting from
+// the lights. Each emitter is picked in proportion to its power and then sampled uniformly by area.
+pub struct EmitterSampler {
+    emitters: Vec<Arc<dyn Hit>>,
+    distribution: Distribution1D,
+    total_power: f64,
+}
+
+impl EmitterSampler {
+    // Only the lights of the scene that can be sampled by area are used, so the background and the
+    // delta lights have to be handled separately.
+    pub fn new(scene: &Scene) -> Self {
+        let mut emitters = Vec::new();
+        for light in scene.lights.lights() {
+            collect_emitters(light, &mut emitters);
+        }
+        emitters
+            .retain(|emitter| emitter.emitted_power() > 0.0 && emitter.sample_surface().is_some());
+
+        let powers: Vec<f64> = emitters
+            .iter()
+            .map(|emitter| emitter.emitted_power())
+            .collect();
+        let total_power = powers.iter().sum();
+
+        Self {
+            emitters,
+            distribution: Distribution1D::new(&powers),
+            total_power,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.emitters.is_empty()
+    }
+
+    // Point on one of the emitters, together with its density per unit area.
+    pub fn sample(&self) -> Option<(HitRecord, f64)> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let (idx, _) = self.distribution.sample_discrete(sampler::random());
+        let hit_record = self.emitters[idx].sample_surface()?;
+        let pdf = self.pdf(&hit_record);
+
+        Some((hit_record, pdf))
+    }
+
+    // Density per unit area of sampling a point on an emitter. Since the power of each emitter is its
+    // area times pi times its radiance, this only depends on the radiance of its material. It assumes
+    // that every emitter of the scene is one of its lights.
+    pub fn pdf(&self, hit_record: &HitRecord) -> f64 {
+        if self.total_power <= 0.0 {
+            return 0.0;
+        }
+
+        std::f64::consts::PI * hit_record.material.emitted_radiance().luminance() / self.total_power
+    }
+}
+
+// Hit record of the same point for a ray that arrives at it from another direction.
+pub(crate) fn refaced(hit_record: &HitRecord, ray: Ray) -> HitRecord {
+    let outward_normal = if hit_record.front_face {
+        hit_record.normal
+    } else {
+        hit_record.normal.mul_scalar(-1.0)
+    };
+    let mut refaced = hit_record.clone();
+    refaced.set_face_normal(ray, outward_normal);
+    refaced
+}
+
+// Normal of the side of a surface that emits light, for a hit record on an emitter.
+fn emitting_normal(hit_record: &HitRecord) -> Vec3 {
+    if hit_record.front_face {
+        hit_record.normal
+    } else {
+        hit_record.normal.mul_scalar(-1.0)
+    }
+}
+
+// Direction that light leaves a point on an emitter in, with a density proportional to the cosine
+// with the normal (on either side for two-sided emitters), together with its density per unit
+// solid angle.
+pub(crate) fn sample_emission_direction(hit_record: &HitRecord) -> (Vec3, f64) {
+    let mut normal = emitting_normal(hit_record);
+    if hit_record.material.emits_both_sides() && sampler::random() < 0.5 {
+        normal = normal.mul_scalar(-1.0);
+    }
+
+    let direction = ONB::new(normal).local(Vec3::random_cosine_dir());
+    (direction, emission_direction_pdf(hit_record, direction))
+}
+
+pub(crate) fn emission_direction_pdf(hit_record: &HitRecord, direction: Vec3) -> f64 {
+    let cos_theta = direction.into_unit_vec().dot(emitting_normal(hit_record));
+    if hit_record.material.emits_both_sides() {
+        0.5 * cos_theta.abs() / std::f64::consts::PI
+    } else {
+        cos_theta.max(0.0) / std::f64::consts::PI
+    }
+}
+
+// Radiance that leaves a point on an emitter along a direction.
+pub(crate) fn emitted_radiance(hit_record: &HitRecord, direction: Vec3) -> Color {
+    let ray_in = Ray::new(hit_record.hit_point + direction, direction.mul_scalar(-1.0));
+    hit_record
+        .material
+        .emit(ray_in, &refaced(hit_record, ray_in))
+}
@@ -0,0 +1,62 @@
+use crate::film::Film;
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Ray};
+use crate::pdfs::MisHeuristic;
+use crate::scene::Scene;
+
+use super::sd_tree::SdTree;
+use super::{Integrator, PathTracer};
+
+// Number of samples that a spatial leaf of the SD-tree can get in a pass before it is split, relative
+// to the square root of the samples of the pass.
+const SPATIAL_THRESHOLD: f64 = 12.5;
+// Largest fraction of the energy of a directional quadtree that its leaves can hold.
+const DIRECTIONAL_THRESHOLD: f64 = 0.01;
+
+// Path tracer that learns where the light arrives from in each region of the scene, and samples the
+// directions of the paths from it as well as from the materials (Müller et al., "Practical path
+// guiding for efficient light-transport simulation"). The first pass only samples the materials,
+// and each of the following ones is guided by what was learned in the previous one.
+pub struct GuidedPathTracer {
+    path_tracer: PathTracer,
+    sd_tree: SdTree,
+    pass_num: u32,
+}
+
+impl GuidedPathTracer {
+    pub fn new(scene: &Scene, mis_heuristic: MisHeuristic, max_depth: i32) -> Self {
+        Self {
+            path_tracer: PathTracer::new(mis_heuristic, max_depth),
+            sd_tree: SdTree::new(scene),
+            pass_num: 8,
+        }
+    }
+
+    pub fn with_rr_depth(mut self, rr_depth: i32) -> Self {
+        self.path_tracer = self.path_tracer.with_rr_depth(rr_depth);
+        self
+    }
+
+    pub fn with_passes(mut self, pass_num: u32) -> Self {
+        self.pass_num = pass_num;
+        self
+    }
+}
+
+impl Integrator for GuidedPathTracer {
+    fn ray_color(&self, ray: Ray, scene: &Scene, light_groups: LightGroups) -> Color {
+        self.path_tracer
+            .trace(ray, scene, light_groups, None, Some(&self.sd_tree), None)
+    }
+
+    fn pass_count(&self) -> u32 {
+        self.pass_num
+    }
+
+    fn begin_pass(&mut self, _scene: &Scene, _film: &Film, pass: u32, _samples_per_pixel: u32) {
+        if pass > 0 {
+            self.sd_tree
+                .refine(SPATIAL_THRESHOLD, DIRECTIONAL_THRESHOLD);
+        }
+    }
+}
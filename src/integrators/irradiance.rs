@@ -0,0 +1,71 @@
+use rayon::prelude::*;
+
+use crate::film::Film;
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Ray};
+use crate::pdfs::MisHeuristic;
+use crate::scene::Scene;
+
+use super::irradiance_cache::IrradianceCache;
+use super::{Integrator, PathTracer};
+
+// Spacing in pixels of the grids that the cache is filled over before rendering, from coarse to
+// fine.
+const PREPASS_STRIDES: [u32; 3] = [16, 4, 1];
+
+// Fast preview of diffuse scenes, which samples the light coming straight from the lights at each
+// diffuse surface seen by the camera, and interpolates the light bounced off the other surfaces
+// from an irradiance cache. Specular surfaces are still followed, as in direct lighting.
+pub struct IrradianceCaching {
+    path_tracer: PathTracer,
+    cache: IrradianceCache,
+}
+
+impl IrradianceCaching {
+    pub fn new(scene: &Scene, mis_heuristic: MisHeuristic, max_depth: i32) -> Self {
+        Self {
+            path_tracer: PathTracer::direct_only(mis_heuristic, max_depth),
+            cache: IrradianceCache::new(scene, PathTracer::new(mis_heuristic, max_depth)),
+        }
+    }
+
+    pub fn with_rr_depth(mut self, rr_depth: i32) -> Self {
+        self.cache = self.cache.with_rr_depth(rr_depth);
+        self
+    }
+
+    pub fn with_samples(mut self, sample_num: u32) -> Self {
+        self.cache = self.cache.with_samples(sample_num);
+        self
+    }
+
+    pub fn with_error(mut self, error: f64) -> Self {
+        self.cache = self.cache.with_error(error);
+        self
+    }
+}
+
+impl Integrator for IrradianceCaching {
+    fn ray_color(&self, ray: Ray, scene: &Scene, light_groups: LightGroups) -> Color {
+        self.path_tracer
+            .trace(ray, scene, light_groups, None, None, Some(&self.cache))
+    }
+
+    // Fills the cache over finer and finer grids of pixels, so that the records are spread evenly
+    // before the samples of the pixels interpolate them instead of in the order they are rendered.
+    fn begin_pass(&mut self, scene: &Scene, film: &Film, pass: u32, _samples_per_pixel: u32) {
+        if pass > 0 {
+            return;
+        }
+
+        let this: &Self = self;
+        for &stride in PREPASS_STRIDES.iter() {
+            let columns = film.width().div_ceil(stride);
+            let rows = film.height().div_ceil(stride);
+            (0..columns * rows).into_par_iter().for_each(|idx| {
+                let (s, t) = film.sample_pixel(idx % columns * stride, idx / columns * stride);
+                this.ray_color(scene.camera.get_ray(s, t), scene, LightGroups::all());
+            });
+        }
+    }
+}
@@ -0,0 +1,535 @@
+use std::f64::consts::PI;
+use std::sync::RwLock;
+
+use crate::hittables::{Hit, HitRecord};
+use crate::linalg::{Color, Point3, Ray, RayType, Vec3, ONB};
+use crate::sampler;
+use crate::scene::Scene;
+
+use super::{linked_emission, Integrator, PathTracer};
+
+// Smallest and largest distance over which a record is valid, relative to the radius of the scene,
+// so that corners don't fill up with records and open areas still get a few.
+const MIN_RADIUS: f64 = 0.005;
+const MAX_RADIUS: f64 = 0.5;
+// Smallest cosine with the normal that the rotational gradient is estimated with, which keeps the
+// directions that graze the surface from blowing it up.
+const MIN_GRADIENT_COS: f64 = 0.1;
+
+// Indirect irradiance computed at a point of a surface, together with how it changes as the surface
+// turns (rotational gradient) and as the point moves (translational gradient), one vector for each
+// color channel.
+struct IrradianceRecord {
+    point: Point3,
+    normal: Vec3,
+    irradiance: Color,
+    // Distance at which the record stops being valid for an error of one, which shrinks with how
+    // close the surrounding surfaces are and with how quickly the irradiance changes.
+    radius: f64,
+    rotational: [Vec3; 3],
+    translational: [Vec3; 3],
+}
+
+impl IrradianceRecord {
+    // Error of reusing the record at a point with the given normal (Ward et al., "A ray tracing
+    // solution for diffuse interreflection").
+    fn error(&self, point: Point3, normal: Vec3) -> f64 {
+        (point - self.point).length() / self.radius
+            + (1.0 - normal.dot(self.normal)).max(0.0).sqrt()
+    }
+
+    // Irradiance at a point with the given normal, extrapolated from the record with its gradients.
+    fn extrapolate(&self, point: Point3, normal: Vec3) -> Color {
+        let rotation = self.normal.cross(normal);
+        let offset = point - self.point;
+        let mut irradiance = self.irradiance;
+        for channel in 0..3 {
+            irradiance[channel] +=
+                self.rotational[channel].dot(rotation) + self.translational[channel].dot(offset);
+            irradiance[channel] = irradiance[channel].max(0.0);
+        }
+
+        irradiance
+    }
+}
+
+// Node of the octree that the records are looked up in. Each record is stored in the smallest node
+// that contains its point and is at least as large as the region it is valid in.
+struct OctreeNode {
+    center: Point3,
+    half_size: f64,
+    records: Vec<usize>,
+    children: Option<[usize; 8]>,
+}
+
+impl OctreeNode {
+    fn new(center: Point3, half_size: f64) -> Self {
+        Self {
+            center,
+            half_size,
+            records: Vec::new(),
+            children: None,
+        }
+    }
+
+    fn octant(&self, point: Point3) -> usize {
+        (0..3)
+            .filter(|&axis| point[axis] > self.center[axis])
+            .map(|axis| 1 << axis)
+            .sum()
+    }
+
+    // Whether the node, grown by its half size on every side, contains a point. The records of
+    // the node are only valid within it.
+    fn reaches(&self, point: Point3) -> bool {
+        (0..3).all(|axis| (point[axis] - self.center[axis]).abs() <= 2.0 * self.half_size)
+    }
+}
+
+struct Records {
+    records: Vec<IrradianceRecord>,
+    nodes: Vec<OctreeNode>,
+}
+
+impl Records {
+    // Adds a record, which is valid up to the given distance from its point.
+    fn insert(&mut self, record: IrradianceRecord, reach: f64) {
+        let mut node = 0;
+        while self.nodes[node].half_size / 2.0 >= reach
+            && (0..3).all(|axis| {
+                (record.point[axis] - self.nodes[node].center[axis]).abs()
+                    <= self.nodes[node].half_size
+            })
+        {
+            if self.nodes[node].children.is_none() {
+                let half_size = self.nodes[node].half_size / 2.0;
+                let mut children = [0; 8];
+                for (octant, child) in children.iter_mut().enumerate() {
+                    let mut center = self.nodes[node].center;
+                    for axis in 0..3 {
+                        center[axis] += if octant & (1 << axis) != 0 {
+                            half_size
+                        } else {
+                            -half_size
+                        };
+                    }
+                    *child = self.nodes.len();
+                    self.nodes.push(OctreeNode::new(center, half_size));
+                }
+                self.nodes[node].children = Some(children);
+            }
+            node = self.nodes[node].children.unwrap()[self.nodes[node].octant(record.point)];
+        }
+
+        self.nodes[node].records.push(self.records.len());
+        self.records.push(record);
+    }
+
+    // Records stored in the nodes whose records can be valid at a point.
+    fn nearby(&self, point: Point3) -> Vec<&IrradianceRecord> {
+        let mut records = Vec::new();
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            records.extend(node.records.iter().map(|&idx| &self.records[idx]));
+            if let Some(children) = node.children {
+                stack.extend(
+                    children
+                        .iter()
+                        .filter(|&&child| self.nodes[child].reaches(point)),
+                );
+            }
+        }
+
+        records
+    }
+}
+
+// Cache of the indirect irradiance arriving at the diffuse surfaces of a scene. The irradiance is
+// only computed at sparse points, from many samples of the hemisphere above them, and interpolated
+// in between using its gradients (Ward and Heckbert, "Irradiance gradients"). Points are added
+// lazily wherever none of the existing ones can be reused within the allowed error.
+pub struct IrradianceCache {
+    // Traces the hemisphere samples of the records.
+    path_tracer: PathTracer,
+    sample_num: u32,
+    // Largest error that records can be reused with. Lower values place records more densely.
+    error: f64,
+    min_radius: f64,
+    max_radius: f64,
+    records: RwLock<Records>,
+}
+
+impl IrradianceCache {
+    pub fn new(scene: &Scene, path_tracer: PathTracer) -> Self {
+        let (min_corner, max_corner) = match scene.world.bounding_box(0.0, 1.0) {
+            Some(bounding_box) => (bounding_box.min_corner(), bounding_box.max_corner()),
+            None => (Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0)),
+        };
+        let extent = max_corner - min_corner;
+        let half_size = extent.x().max(extent.y()).max(extent.z()).max(1e-3) / 2.0;
+        let root = OctreeNode::new((min_corner + max_corner).div_scalar(2.0), half_size);
+
+        Self {
+            path_tracer,
+            sample_num: 256,
+            error: 0.2,
+            min_radius: MIN_RADIUS * scene.radius(),
+            max_radius: MAX_RADIUS * scene.radius(),
+            records: RwLock::new(Records {
+                records: Vec::new(),
+                nodes: vec![root],
+            }),
+        }
+    }
+
+    pub fn with_rr_depth(mut self, rr_depth: i32) -> Self {
+        self.path_tracer = self.path_tracer.with_rr_depth(rr_depth);
+        self
+    }
+
+    pub fn with_samples(mut self, sample_num: u32) -> Self {
+        self.sample_num = sample_num;
+        self
+    }
+
+    pub fn with_error(mut self, error: f64) -> Self {
+        self.error = error;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.read().unwrap().records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Indirect light that a diffuse surface reflects back along a ray, as if it were lambertian
+    // with the given albedo.
+    pub fn radiance(&self, scene: &Scene, hit_record: &HitRecord, attenuation: Color) -> Color {
+        attenuation * self.irradiance(scene, hit_record).div_scalar(PI)
+    }
+
+    // Indirect irradiance at a hit point, interpolated from the records around it or computed from
+    // a new one if there are none.
+    pub fn irradiance(&self, scene: &Scene, hit_record: &HitRecord) -> Color {
+        if let Some(irradiance) = self.interpolate(hit_record.hit_point, hit_record.normal) {
+            return irradiance;
+        }
+
+        let record = self.compute_record(scene, hit_record);
+        let irradiance = record.irradiance;
+        let reach = self.error * record.radius;
+        self.records.write().unwrap().insert(record, reach);
+        irradiance
+    }
+
+    // Average of the irradiance extrapolated from the records that are valid at a point, weighted
+    // so that it fades out smoothly towards the edge of each record.
+    fn interpolate(&self, point: Point3, normal: Vec3) -> Option<Color> {
+        let records = self.records.read().unwrap();
+        let mut irradiance = Color::default();
+        let mut weight_sum = 0.0;
+        for record in records.nearby(point) {
+            let error = record.error(point, normal);
+            // Records in front of the point may see light that is hidden from it.
+            let depth = (point - record.point).dot((normal + record.normal).mul_scalar(0.5));
+            if error >= self.error || depth < -0.01 * record.radius {
+                continue;
+            }
+
+            let weight = 1.0 - error / self.error;
+            irradiance += record.extrapolate(point, normal).mul_scalar(weight);
+            weight_sum += weight;
+        }
+
+        if weight_sum > 0.0 {
+            Some(irradiance.div_scalar(weight_sum))
+        } else {
+            None
+        }
+    }
+
+    // Samples the indirect light arriving at a hit point over the hemisphere, in proportion to the
+    // cosine with the normal. The light that comes straight from the emitters and the background is
+    // left out, since it is sampled directly.
+    //
+    // The hemisphere is split into rows of equal cosine-weighted solid angle by polar angle, and
+    // columns by azimuth, with one sample in each cell. The gradients are estimated from how the
+    // light changes between neighbouring cells (Ward and Heckbert, "Irradiance gradients").
+    fn compute_record(&self, scene: &Scene, hit_record: &HitRecord) -> IrradianceRecord {
+        let point = hit_record.hit_point;
+        let normal = hit_record.normal;
+        let onb = ONB::new(normal);
+
+        // There are about pi times as many columns as rows, which keeps the cells roughly square.
+        let rows = ((self.sample_num as f64 / PI).sqrt().round() as usize).max(1);
+        let columns = (self.sample_num as usize / rows).max(1);
+
+        // Radiance found by the sample of each cell, and the distance to the surface it hit.
+        let mut cells = vec![(Color::default(), f64::INFINITY); rows * columns];
+        let mut irradiance = Color::default();
+        let mut rotational = [Vec3::default(); 3];
+        let mut inverse_distance_sum = 0.0;
+        for row in 0..rows {
+            for column in 0..columns {
+                let sin_theta_sq = (row as f64 + sampler::random()) / rows as f64;
+                let phi = 2.0 * PI * (column as f64 + sampler::random()) / columns as f64;
+                let (sin_theta, cos_theta) = (sin_theta_sq.sqrt(), (1.0 - sin_theta_sq).sqrt());
+                let direction = onb.local(Vec3::new(
+                    sin_theta * phi.cos(),
+                    sin_theta * phi.sin(),
+                    cos_theta,
+                ));
+
+                let ray = Ray::new(point, direction).with_type(RayType::Diffuse);
+                let sample_hit = match scene.world.hit(ray, 0.001, f64::INFINITY) {
+                    Some(sample_hit) => sample_hit,
+                    None => continue,
+                };
+                let radiance = self
+                    .path_tracer
+                    .ray_color(ray, scene, hit_record.light_groups)
+                    - linked_emission(ray, &sample_hit, hit_record.light_groups);
+                if !radiance.luminance().is_finite() {
+                    continue;
+                }
+                cells[row * columns + column] = (radiance, sample_hit.t);
+                inverse_distance_sum += 1.0 / sample_hit.t;
+
+                // Turning the surface changes the cosine of each direction.
+                let rotation = normal
+                    .cross(direction)
+                    .div_scalar(cos_theta.max(MIN_GRADIENT_COS));
+                irradiance += radiance;
+                for channel in 0..3 {
+                    rotational[channel] += rotation.mul_scalar(radiance[channel]);
+                }
+            }
+        }
+
+        // Each sample estimates the irradiance as pi times the radiance it found.
+        let scale = PI / (rows * columns) as f64;
+        irradiance = irradiance.mul_scalar(scale);
+        for gradient in rotational.iter_mut() {
+            *gradient = gradient.mul_scalar(scale);
+        }
+
+        // Moving the point slides the walls between the cells over the surfaces they see, by more
+        // for the nearer of the surfaces on either side, trading the light of the two cells. Walls
+        // between rows slide along the azimuth of their column, and walls between columns across it.
+        let mut translational = [Vec3::default(); 3];
+        for column in 0..columns {
+            let phi = 2.0 * PI * (column as f64 + 0.5) / columns as f64;
+            let phi_wall = 2.0 * PI * column as f64 / columns as f64;
+            let along = onb.u().mul_scalar(phi.cos()) + onb.v().mul_scalar(phi.sin());
+            let across = onb.v().mul_scalar(phi_wall.cos()) - onb.u().mul_scalar(phi_wall.sin());
+            let previous_column = (column + columns - 1) % columns;
+
+            for row in 0..rows {
+                let (radiance, distance) = cells[row * columns + column];
+                let sin_theta_low = (row as f64 / rows as f64).sqrt();
+                let sin_theta_high = ((row + 1) as f64 / rows as f64).sqrt();
+
+                if row > 0 {
+                    let (below, below_distance) = cells[(row - 1) * columns + column];
+                    let cos_theta_sq = 1.0 - sin_theta_low * sin_theta_low;
+                    let weight = 2.0 * PI / columns as f64 * sin_theta_low * cos_theta_sq
+                        / distance.min(below_distance).max(self.min_radius);
+                    for channel in 0..3 {
+                        translational[channel] +=
+                            along.mul_scalar(weight * (radiance[channel] - below[channel]));
+                    }
+                }
+
+                let (before, before_distance) = cells[row * columns + previous_column];
+                let weight = (sin_theta_high - sin_theta_low)
+                    / distance.min(before_distance).max(self.min_radius);
+                for channel in 0..3 {
+                    translational[channel] +=
+                        across.mul_scalar(weight * (radiance[channel] - before[channel]));
+                }
+            }
+        }
+
+        // The record is valid up to the harmonic mean of the distances to the surfaces around it,
+        // and no further than where its gradient would change the irradiance by as much as its
+        // value.
+        let mut radius = if inverse_distance_sum > 0.0 {
+            (rows * columns) as f64 / inverse_distance_sum
+        } else {
+            self.max_radius
+        };
+        let gradient = Vec3::new(
+            Color::new(
+                translational[0].x(),
+                translational[1].x(),
+                translational[2].x(),
+            )
+            .luminance(),
+            Color::new(
+                translational[0].y(),
+                translational[1].y(),
+                translational[2].y(),
+            )
+            .luminance(),
+            Color::new(
+                translational[0].z(),
+                translational[1].z(),
+                translational[2].z(),
+            )
+            .luminance(),
+        )
+        .length();
+        if gradient > 0.0 {
+            radius = radius.min(irradiance.luminance() / gradient);
+        }
+
+        IrradianceRecord {
+            point,
+            normal,
+            irradiance,
+            radius: radius.clamp(self.min_radius, self.max_radius),
+            rotational,
+            translational,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::camera::Camera;
+    use crate::hittables::{World, XZRect, YZRect};
+    use crate::lights::LightList;
+    use crate::materials::{Lambertian, Metal};
+    use crate::pdfs::MisHeuristic;
+
+    // Irradiance that grows linearly along the x axis, and the record of it at a point.
+    fn linear_record(x: f64) -> IrradianceRecord {
+        let slope = Vec3::new(0.5, 0.0, 0.0);
+        IrradianceRecord {
+            point: Point3::new(x, 0.0, 0.0),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            irradiance: Color::from_scalar(1.0 + 0.5 * x),
+            radius: 1.0,
+            rotational: [Vec3::default(); 3],
+            translational: [slope; 3],
+        }
+    }
+
+    #[test]
+    fn gradients_interpolate_linear_irradiance() {
+        let camera = Camera::new(
+            Point3::new(0.0, 0.0, 5.0),
+            Point3::default(),
+            Vec3::new(0.0, 1.0, 0.0),
+            20.0,
+            1.0,
+            0.0,
+            5.0,
+        );
+        let scene = Scene::new(
+            World::default(),
+            LightList::default(),
+            camera,
+            1.0,
+            Arc::new(Color::default()),
+        );
+        let cache =
+            IrradianceCache::new(&scene, PathTracer::new(MisHeuristic::Power, 5)).with_error(0.5);
+        for &x in &[-0.2, 0.0, 0.3] {
+            cache
+                .records
+                .write()
+                .unwrap()
+                .insert(linear_record(x), cache.error);
+        }
+
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        for &x in &[-0.4, -0.1, 0.1, 0.35, 0.6] {
+            let irradiance = cache.interpolate(Point3::new(x, 0.0, 0.1), normal).unwrap();
+            assert!(
+                (irradiance.g() - (1.0 + 0.5 * x)).abs() < 1e-9,
+                "{:?}",
+                irradiance
+            );
+        }
+
+        // Points too far away, or facing another way, need records of their own.
+        assert!(cache
+            .interpolate(Point3::new(2.0, 0.0, 0.0), normal)
+            .is_none());
+        assert!(cache
+            .interpolate(Point3::default(), Vec3::new(0.0, -1.0, 0.0))
+            .is_none());
+    }
+
+    // A floor next to a mirror wall of height one under a white sky, where the only indirect light
+    // is the sky seen in the mirror. The irradiance from a wall at distance d is
+    // pi / 2 * (1 - d / sqrt(d^2 + 1)), and it grows towards the wall. The gradient is a little
+    // steeper than the true one, by less as the cells get smaller.
+    #[test]
+    fn gradients_follow_the_light_of_a_wall() {
+        let mut world = World::default();
+        world.add(Arc::new(XZRect::new(
+            0.0,
+            100.0,
+            -100.0,
+            100.0,
+            0.0,
+            Arc::new(Lambertian::new(Color::from_scalar(0.5))),
+        )));
+        world.add(Arc::new(YZRect::new(
+            0.0,
+            1.0,
+            -100.0,
+            100.0,
+            0.0,
+            Arc::new(Metal::new(Color::from_scalar(1.0), 0.0)),
+        )));
+        let camera = Camera::new(
+            Point3::new(5.0, 5.0, 5.0),
+            Point3::default(),
+            Vec3::new(0.0, 1.0, 0.0),
+            20.0,
+            1.0,
+            0.0,
+            5.0,
+        );
+        let scene = Scene::new(
+            world,
+            LightList::default(),
+            camera,
+            1.0,
+            Arc::new(Color::from_scalar(1.0)),
+        );
+        let cache = IrradianceCache::new(&scene, PathTracer::new(MisHeuristic::Power, 5))
+            .with_samples(4096);
+
+        let d: f64 = 0.5;
+        let ray = Ray::new(Point3::new(d, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit_record = scene.world.hit(ray, 0.001, f64::INFINITY).unwrap();
+        let record = cache.compute_record(&scene, &hit_record);
+
+        let irradiance = PI / 2.0 * (1.0 - d / (d * d + 1.0).sqrt());
+        let slope = -PI / 2.0 / (d * d + 1.0).powf(1.5);
+        assert!(
+            (record.irradiance.g() - irradiance).abs() < 0.03 * irradiance,
+            "{:?}",
+            record.irradiance
+        );
+        let gradient = record.translational[1];
+        assert!(
+            (gradient.x() - slope).abs() < 0.1 * slope.abs(),
+            "{:?}",
+            gradient
+        );
+        assert!(gradient.y().abs() < 1e-9, "{:?}", gradient);
+        assert!(gradient.z().abs() < 0.1 * slope.abs(), "{:?}", gradient);
+    }
+}
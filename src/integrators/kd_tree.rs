@@ -0,0 +1,122 @@
+use std::cmp::Ordering;
+
+use crate::linalg::Point3;
+
+// Balanced kd-tree over points with some data attached to each of them. The items are stored in an
+// array where the median of each range is the node that splits the rest of the range in two.
+pub struct KdTree<T> {
+    items: Vec<(Point3, T)>,
+    // Axis that each node splits its range along.
+    split_axes: Vec<usize>,
+}
+
+fn build<T>(items: &mut [(Point3, T)], split_axes: &mut [usize]) {
+    if items.len() <= 1 {
+        return;
+    }
+
+    // Split along the axis that the points are most spread out on.
+    let mut min = items[0].0;
+    let mut max = items[0].0;
+    for (point, _) in items.iter() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(point[axis]);
+            max[axis] = max[axis].max(point[axis]);
+        }
+    }
+    let extent = max - min;
+    let axis = (0..3)
+        .max_by(|a, b| {
+            extent[*a]
+                .partial_cmp(&extent[*b])
+                .unwrap_or(Ordering::Equal)
+        })
+        .unwrap();
+
+    let mid = items.len() / 2;
+    items.select_nth_unstable_by(mid, |(a, _), (b, _)| {
+        a[axis].partial_cmp(&b[axis]).unwrap_or(Ordering::Equal)
+    });
+    split_axes[mid] = axis;
+
+    let (left_items, right_items) = items.split_at_mut(mid);
+    let (left_axes, right_axes) = split_axes.split_at_mut(mid);
+    build(left_items, left_axes);
+    build(&mut right_items[1..], &mut right_axes[1..]);
+}
+
+impl<T> KdTree<T> {
+    pub fn new(mut items: Vec<(Point3, T)>) -> Self {
+        let mut split_axes = vec![0; items.len()];
+        build(&mut items, &mut split_axes);
+
+        Self { items, split_axes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // Calls `f` with every item whose point is within `radius` of `point`.
+    pub fn for_each_within<F: FnMut(&Point3, &T)>(&self, point: Point3, radius: f64, mut f: F) {
+        self.visit(0, self.items.len(), point, radius, &mut f);
+    }
+
+    fn visit<F: FnMut(&Point3, &T)>(
+        &self,
+        start: usize,
+        end: usize,
+        point: Point3,
+        radius: f64,
+        f: &mut F,
+    ) {
+        if start >= end {
+            return;
+        }
+
+        let mid = start + (end - start) / 2;
+        let (node_point, data) = &self.items[mid];
+        if (*node_point - point).length_sq() <= radius * radius {
+            f(node_point, data);
+        }
+
+        let axis = self.split_axes[mid];
+        let offset = point[axis] - node_point[axis];
+        if offset <= radius {
+            self.visit(start, mid, point, radius, f);
+        }
+        if offset >= -radius {
+            self.visit(mid + 1, end, point, radius, f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::linalg::Vec3;
+
+    #[test]
+    fn finds_the_same_points_as_a_linear_search() {
+        let points: Vec<Point3> = (0..500)
+            .map(|_| Vec3::random_in_unit_disk() + Vec3::random_unit_vector().mul_scalar(0.5))
+            .collect();
+        let tree = KdTree::new(points.iter().cloned().zip(0..).collect());
+
+        for _ in 0..50 {
+            let center = Vec3::random_unit_vector().mul_scalar(0.7);
+            let mut found = Vec::new();
+            tree.for_each_within(center, 0.3, |_, idx| found.push(*idx));
+            found.sort_unstable();
+
+            let expected: Vec<usize> = (0..points.len())
+                .filter(|idx| (points[*idx] - center).length() <= 0.3)
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use rayon::prelude::*;
+
+use crate::film::Film;
+use crate::hittables::{Hit, HitRecord};
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Ray, RayType};
+use crate::pdfs::MisHeuristic;
+use crate::scene::Scene;
+
+use super::bidirectional::visible;
+use super::emitters::{emitted_radiance, sample_emission_direction, EmitterSampler};
+use super::{russian_roulette, splats_onto_film, Integrator, PathTracer};
+
+// Light tracer, which follows paths from points sampled on the emitters and connects each of their
+// vertices to a point on the lens, splatting the light they carry onto the film. It renders the
+// caustics seen directly by the camera with little noise, and is a reference for the integrators
+// that trace paths from the camera, since it samples the paths from the other end.
+//
+// The background and the delta lights are left out, since they can't be sampled by area. Paths can
+// only reach the camera from diffuse surfaces, so nothing shows up in the mirrors and through the
+// glass that the camera sees. With alpha, nothing is splatted from the surfaces that the render
+// loop composites itself, like shadow catchers.
+pub struct LightTracer {
+    // Maximum number of rays in the paths, counting the one that reaches the camera.
+    max_depth: i32,
+    // Number of rays traced before paths start being terminated randomly with Russian roulette.
+    rr_depth: i32,
+    emitters: EmitterSampler,
+    // Traces the rays that don't start at the camera (e.g. the ones leaving shadow catchers), which
+    // have no film to splat onto.
+    path_tracer: PathTracer,
+}
+
+impl LightTracer {
+    pub fn new(scene: &Scene, mis_heuristic: MisHeuristic, max_depth: i32) -> Self {
+        Self {
+            max_depth,
+            rr_depth: max_depth,
+            emitters: EmitterSampler::new(scene),
+            path_tracer: PathTracer::new(mis_heuristic, max_depth),
+        }
+    }
+
+    pub fn with_rr_depth(mut self, rr_depth: i32) -> Self {
+        self.rr_depth = rr_depth;
+        self.path_tracer = self.path_tracer.with_rr_depth(rr_depth);
+        self
+    }
+
+    // Splats the light that leaves a surface towards a random point on the lens, where `color` gives
+    // the light leaving the surface along a direction, if the surface is in view and not blocked.
+    fn splat(
+        &self,
+        scene: &Scene,
+        film: &Film,
+        hit_record: &HitRecord,
+        color: impl Fn(Ray) -> Color,
+    ) {
+        if !splats_onto_film(film, hit_record) {
+            return;
+        }
+        let point = hit_record.hit_point;
+        let camera_sample = match scene.camera.project(point) {
+            Some(camera_sample) => camera_sample,
+            None => return,
+        };
+
+        let to_lens = camera_sample.lens_point - point;
+        let color = color(Ray::new(point, to_lens).with_type(RayType::Shadow));
+        if color == Color::default() || !visible(scene, point, camera_sample.lens_point) {
+            return;
+        }
+
+        film.add_splat(
+            camera_sample.s,
+            camera_sample.t,
+            color.mul_scalar(camera_sample.pdf / to_lens.length_sq()),
+        );
+    }
+
+    // Traces a path from one of the emitters, splatting the light it carries to the camera from each
+    // of its vertices.
+    fn trace_light_path(&self, scene: &Scene, film: &Film) {
+        let (hit_record, pdf) = match self.emitters.sample() {
+            Some(sample) => sample,
+            None => return,
+        };
+        if pdf <= 0.0 {
+            return;
+        }
+
+        // Light leaving the emitter along a direction, including the cosine with its normal.
+        let emission = |direction| {
+            let cos_theta = hit_record.normal.dot(direction).abs() / direction.length();
+            emitted_radiance(&hit_record, direction).mul_scalar(cos_theta / pdf)
+        };
+        self.splat(scene, film, &hit_record, |ray| emission(*ray.direction()));
+
+        let (direction, direction_pdf) = sample_emission_direction(&hit_record);
+        if direction_pdf <= 0.0 {
+            return;
+        }
+        let mut beta = emission(direction).div_scalar(direction_pdf);
+        let mut ray = Ray::new(hit_record.hit_point, direction).with_type(RayType::Diffuse);
+
+        let light_links = hit_record.light_links;
+        for depth in 1..self.max_depth {
+            let hit_record = match scene.world.hit(ray, 0.001, f64::INFINITY) {
+                Some(hit_record) => hit_record,
+                None => break,
+            };
+            // The emitter only lights the surfaces in the groups that it is linked to.
+            if depth == 1 && !light_links.intersects(hit_record.light_groups) {
+                break;
+            }
+            let scatter_record = match hit_record.material.scatter(ray, &hit_record) {
+                Some(scatter_record) => scatter_record,
+                None => break,
+            };
+
+            if let Some(specular_ray) = scatter_record.specular_ray {
+                beta *= scatter_record.attenuation;
+                ray = specular_ray.with_type(RayType::Specular);
+            } else {
+                let attenuation = scatter_record.attenuation;
+                self.splat(scene, film, &hit_record, |to_lens| {
+                    beta * hit_record.material.scattering_color(
+                        ray,
+                        to_lens,
+                        &hit_record,
+                        attenuation,
+                    )
+                });
+
+                let material_pdf = scatter_record.pdf.as_ref().unwrap();
+                let scatter_ray = Ray::new(hit_record.hit_point, material_pdf.generate())
+                    .with_type(RayType::Diffuse);
+                let pdf_val = material_pdf.value(*scatter_ray.direction());
+                if pdf_val <= 0.0 {
+                    break;
+                }
+                beta *= hit_record
+                    .material
+                    .scattering_color(ray, scatter_ray, &hit_record, attenuation)
+                    .div_scalar(pdf_val);
+                ray = scatter_ray;
+            }
+
+            if depth >= self.rr_depth && !russian_roulette(&mut beta) {
+                break;
+            }
+        }
+    }
+}
+
+impl Integrator for LightTracer {
+    fn ray_color(&self, ray: Ray, scene: &Scene, light_groups: LightGroups) -> Color {
+        self.path_tracer.ray_color(ray, scene, light_groups)
+    }
+
+    // The light reaching the camera is all splatted by the light paths.
+    fn camera_ray_color(&self, _ray: Ray, _scene: &Scene, _film: &Film) -> Color {
+        Color::default()
+    }
+
+    // Traces as many light paths as there are samples in the pass, so that each of them splats one
+    // estimate as the film expects.
+    fn begin_pass(&mut self, scene: &Scene, film: &Film, _pass: u32, samples_per_pixel: u32) {
+        let this: &Self = self;
+        let path_num = film.pixel_num() * samples_per_pixel as u64;
+        (0..path_num)
+            .into_par_iter()
+            .for_each(|_| this.trace_light_path(scene, film));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::camera::Camera;
+    use crate::hittables::{FlipFace, ObjectFlags, World, XYRect, XZRect};
+    use crate::lights::{LightList, LightSelection};
+    use crate::linalg::{Point3, Vec3};
+    use crate::materials::{DiffuseLight, Lambertian};
+
+    // Diffuse floor in the given light-linking groups and a diffuse back wall, under a square light
+    // that faces down and only lights group 0, which the camera doesn't see.
+    fn floor_and_wall_under_light(floor_groups: LightGroups) -> Scene {
+        let material = Arc::new(Lambertian::new(Color::from_scalar(0.5)));
+        let floor = Arc::new(XZRect::new(-2.0, 2.0, -2.0, 2.0, 0.0, material.clone()));
+        let mut world = World::default();
+        world.add(Arc::new(
+            ObjectFlags::new(floor).with_light_groups(floor_groups),
+        ));
+        world.add(Arc::new(XYRect::new(-2.0, 2.0, 0.0, 2.0, -2.0, material)));
+        let light = Arc::new(FlipFace::new(Arc::new(XZRect::new(
+            -0.5,
+            0.5,
+            -0.5,
+            0.5,
+            1.0,
+            Arc::new(DiffuseLight::new(Color::from_scalar(4.0))),
+        ))));
+        world.add(Arc::new(
+            ObjectFlags::new(light).with_light_links(LightGroups::default()),
+        ));
+        let lights = LightList::from_world(&world, LightSelection::Power);
+        let camera = Camera::new(
+            Point3::new(0.0, 0.5, 3.0),
+            Point3::new(0.0, 0.0, 1.5),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            10.0,
+        );
+        Scene::new(world, lights, camera, 1.0, Arc::new(Color::default()))
+    }
+
+    // Average luminance of the pixels rendered by the path tracer and of the ones that the light
+    // tracer splats, with paths terminated by Russian roulette after two rays.
+    fn average_luminances(scene: &Scene, samples_per_pixel: u32) -> (f64, f64) {
+        let film = Film::new(16, 16);
+        let path_tracer = PathTracer::new(MisHeuristic::Power, 5);
+        let mut light_tracer = LightTracer::new(scene, MisHeuristic::Power, 5).with_rr_depth(2);
+        light_tracer.begin_pass(scene, &film, 0, samples_per_pixel);
+
+        let (mut path_sum, mut light_sum) = (0.0, 0.0);
+        for j in 0..film.height() {
+            for i in 0..film.width() {
+                for _ in 0..samples_per_pixel {
+                    let (s, t) = film.sample_pixel(i, j);
+                    let ray = scene.camera.get_ray(s, t);
+                    path_sum += path_tracer.camera_ray_color(ray, scene, &film).luminance();
+                }
+                light_sum += film.splat_color(i, j, samples_per_pixel).luminance();
+            }
+        }
+
+        let pixel_num = film.pixel_num() as f64;
+        (
+            path_sum / (pixel_num * samples_per_pixel as f64),
+            light_sum / pixel_num,
+        )
+    }
+
+    // Both integrators estimate the same image, so their averages must agree.
+    #[test]
+    fn converges_to_path_tracing() {
+        let scene = floor_and_wall_under_light(LightGroups::default());
+        let (path_average, light_average) = average_luminances(&scene, 200);
+
+        assert!(
+            (light_average - path_average).abs() < 0.05 * path_average,
+            "light tracing average {}, path average {}",
+            light_average,
+            path_average
+        );
+    }
+
+    // A floor that the light isn't linked to only gets the light reflected by the wall.
+    #[test]
+    fn light_links_match_path_tracing() {
+        let scene = floor_and_wall_under_light(LightGroups::from_groups(&[1]));
+        let (path_average, light_average) = average_luminances(&scene, 200);
+
+        assert!(
+            (light_average - path_average).abs() < 0.05 * path_average,
+            "light tracing average {}, path average {}",
+            light_average,
+            path_average
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::film::Film;
+use crate::hittables::Hit;
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Ray};
+use crate::pdfs::{Distribution1D, MisHeuristic};
+use crate::sampler::{self, Sampler};
+use crate::scene::Scene;
+
+use super::{splats_onto_film, Integrator, PathTracer};
+
+// One of the random numbers that a path is built from, together with its value before the last
+// mutation in case the mutation is rejected.
+#[derive(Copy, Clone, Default)]
+struct PrimarySample {
+    value: f64,
+    // Iteration of the chain in which the value was last mutated.
+    last_modified: u64,
+    backup_value: f64,
+    backup_modified: u64,
+}
+
+// Point of the primary sample space, i.e. the sequence of random numbers that drives the camera,
+// light and material sampling of a path. It is mutated lazily: each number catches up with the
+// mutations it missed when a path asks for it, since paths use a varying amount of them.
+struct PrimarySampleSpace {
+    samples: Vec<PrimarySample>,
+    // Next number to hand out to the path being traced.
+    index: usize,
+    iteration: u64,
+    large_step: bool,
+    // Last iteration in which a large step was accepted.
+    last_large_step: u64,
+    // Standard deviation of the small steps.
+    sigma: f64,
+    rng: StdRng,
+}
+
+impl PrimarySampleSpace {
+    fn new(seed: u64, sigma: f64) -> Self {
+        Self {
+            samples: Vec::new(),
+            index: 0,
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            sigma,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // Starts mutating the point into the next one of the chain, either replacing all of its
+    // numbers (large step) or perturbing them a little (small step).
+    fn start_iteration(&mut self, large_step: bool) {
+        self.iteration += 1;
+        self.large_step = large_step;
+        self.index = 0;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    fn reject(&mut self) {
+        for sample in self.samples.iter_mut() {
+            if sample.last_modified == self.iteration {
+                sample.value = sample.backup_value;
+                sample.last_modified = sample.backup_modified;
+            }
+        }
+        self.iteration -= 1;
+    }
+}
+
+impl Sampler for PrimarySampleSpace {
+    fn next_1d(&mut self) -> f64 {
+        if self.index >= self.samples.len() {
+            self.samples.push(PrimarySample::default());
+        }
+        let sample = &mut self.samples[self.index];
+        self.index += 1;
+
+        // Numbers that haven't been used since the last accepted large step take a value from it.
+        if sample.last_modified < self.last_large_step {
+            sample.value = self.rng.gen_range(0.0, 1.0);
+            sample.last_modified = self.last_large_step;
+        }
+        sample.backup_value = sample.value;
+        sample.backup_modified = sample.last_modified;
+
+        if self.large_step {
+            sample.value = self.rng.gen_range(0.0, 1.0);
+        } else {
+            // The small steps that the number missed add up to a single normal perturbation whose
+            // variance is their sum, wrapped around [0, 1).
+            let step_num = (self.iteration - sample.last_modified) as f64;
+            let u1: f64 = 1.0 - self.rng.gen_range(0.0, 1.0);
+            let u2: f64 = self.rng.gen_range(0.0, 1.0);
+            let normal = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+            let value = sample.value + normal * self.sigma * step_num.sqrt();
+            let value = value - value.floor();
+            sample.value = if value < 1.0 { value } else { 0.0 };
+        }
+        sample.last_modified = self.iteration;
+
+        sample.value
+    }
+}
+
+// Light carried to the camera by the path built from a point of the primary sample space.
+#[derive(Copy, Clone)]
+struct PathSample {
+    s: f64,
+    t: f64,
+    color: Color,
+}
+
+impl PathSample {
+    // Scalar contribution that the chains sample paths in proportion to.
+    fn importance(&self) -> f64 {
+        let luminance = self.color.luminance();
+        if luminance.is_finite() {
+            luminance.max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+// Primary sample space Metropolis light transport (Kelemen et al., "A simple and robust mutation
+// strategy for the Metropolis light transport algorithm"). Markov chains wander through the random
+// numbers that the path tracer builds its paths from, so that paths are sampled in proportion to
+// the light they carry, and the paths close to the ones that are hard to find get explored.
+// Everything is splatted onto the film, so the samples of the pixels only set the number of
+// mutations.
+pub struct MetropolisLightTransport {
+    path_tracer: PathTracer,
+    // Number of paths sampled independently to estimate the total light reaching the film and to
+    // pick the starting points of the chains from.
+    bootstrap_num: usize,
+    chain_num: usize,
+    // Probability of mutating a point into an independent one instead of perturbing it.
+    large_step_probability: f64,
+    sigma: f64,
+}
+
+impl MetropolisLightTransport {
+    pub fn new(mis_heuristic: MisHeuristic, max_depth: i32) -> Self {
+        Self {
+            path_tracer: PathTracer::new(mis_heuristic, max_depth),
+            bootstrap_num: 100_000,
+            chain_num: 1000,
+            large_step_probability: 0.3,
+            sigma: 0.01,
+        }
+    }
+
+    pub fn with_rr_depth(mut self, rr_depth: i32) -> Self {
+        self.path_tracer = self.path_tracer.with_rr_depth(rr_depth);
+        self
+    }
+
+    pub fn with_bootstrap_paths(mut self, bootstrap_num: usize) -> Self {
+        self.bootstrap_num = bootstrap_num;
+        self
+    }
+
+    pub fn with_chains(mut self, chain_num: usize) -> Self {
+        self.chain_num = chain_num;
+        self
+    }
+
+    pub fn with_large_step_probability(mut self, large_step_probability: f64) -> Self {
+        self.large_step_probability = large_step_probability;
+        self
+    }
+
+    // Path built from the random numbers drawn from the installed sampler, the first two of which
+    // pick the point of the film. With alpha, the paths through the points of the film that the
+    // render loop composites itself carry no light.
+    fn sample_path(&self, scene: &Scene, film: &Film) -> PathSample {
+        let (s, t) = film.sample(sampler::random(), sampler::random());
+        let ray = scene.camera.get_ray(s, t);
+        let splatted = !film.alpha()
+            || scene
+                .world
+                .hit(ray, 0.001, f64::INFINITY)
+                .is_some_and(|hit_record| splats_onto_film(film, &hit_record));
+        let color = if splatted {
+            self.path_tracer.ray_color(ray, scene, LightGroups::all())
+        } else {
+            Color::default()
+        };
+
+        PathSample { s, t, color }
+    }
+
+    // Path built from a fresh point of the primary sample space seeded with the given value.
+    fn seeded_path(
+        &self,
+        seed: u64,
+        scene: &Scene,
+        film: &Film,
+    ) -> (PrimarySampleSpace, PathSample) {
+        let mut space = PrimarySampleSpace::new(seed, self.sigma);
+        space.start_iteration(true);
+        let (mut space, path) = sampler::with_sampler(space, || self.sample_path(scene, film));
+        space.accept();
+
+        (space, path)
+    }
+
+    // Runs a chain from the given point, splatting the expected value of each mutation: both the
+    // proposed and the current path, weighted by the probability of accepting the proposal.
+    // `scale` is the light that a path carries to the film per unit of importance.
+    fn run_chain(
+        &self,
+        mut space: PrimarySampleSpace,
+        mut current: PathSample,
+        mutation_num: u64,
+        scale: f64,
+        scene: &Scene,
+        film: &Film,
+    ) {
+        let mut rng = rand::thread_rng();
+        for _ in 0..mutation_num {
+            space.start_iteration(rng.gen_range(0.0, 1.0) < self.large_step_probability);
+            let (mutated, proposed) =
+                sampler::with_sampler(space, || self.sample_path(scene, film));
+            space = mutated;
+
+            let current_importance = current.importance();
+            let proposed_importance = proposed.importance();
+            let accept_probability = if current_importance > 0.0 {
+                (proposed_importance / current_importance).min(1.0)
+            } else {
+                1.0
+            };
+
+            if proposed_importance > 0.0 {
+                let weight = accept_probability * scale / proposed_importance;
+                film.add_splat(proposed.s, proposed.t, proposed.color.mul_scalar(weight));
+            }
+            if current_importance > 0.0 {
+                let weight = (1.0 - accept_probability) * scale / current_importance;
+                film.add_splat(current.s, current.t, current.color.mul_scalar(weight));
+            }
+
+            if rng.gen_range(0.0, 1.0) < accept_probability {
+                current = proposed;
+                space.accept();
+            } else {
+                space.reject();
+            }
+        }
+    }
+}
+
+impl Integrator for MetropolisLightTransport {
+    fn ray_color(&self, ray: Ray, scene: &Scene, light_groups: LightGroups) -> Color {
+        self.path_tracer.ray_color(ray, scene, light_groups)
+    }
+
+    // The light reaching the camera is all splatted by the chains.
+    fn camera_ray_color(&self, _ray: Ray, _scene: &Scene, _film: &Film) -> Color {
+        Color::default()
+    }
+
+    fn begin_pass(&mut self, scene: &Scene, film: &Film, _pass: u32, samples_per_pixel: u32) {
+        let this: &Self = self;
+        let seed_base: u64 = rand::thread_rng().gen();
+        let importances: Vec<f64> = (0..this.bootstrap_num.max(1))
+            .into_par_iter()
+            .map(|idx| {
+                let (_, path) = this.seeded_path(seed_base.wrapping_add(idx as u64), scene, film);
+                path.importance()
+            })
+            .collect();
+        let seeds = Distribution1D::new(&importances);
+
+        // Average importance of the paths, i.e. the integral of the importance over the primary
+        // sample space. The film is larger than the unit square the splats are measured over.
+        let importance_integral = importances.iter().sum::<f64>() / importances.len() as f64;
+        if importance_integral <= 0.0 {
+            return;
+        }
+        let scale = importance_integral * film.area();
+
+        let mutation_num = film.pixel_num() * samples_per_pixel as u64;
+        let chain_num = this.chain_num.max(1) as u64;
+        (0..chain_num).into_par_iter().for_each(|chain| {
+            let chain_mutations =
+                mutation_num / chain_num + u64::from(chain < mutation_num % chain_num);
+            if chain_mutations == 0 {
+                return;
+            }
+
+            // Start each chain from a bootstrap path picked in proportion to its importance, so that
+            // the chains already follow the distribution they sample.
+            let (idx, _) = seeds.sample_discrete(rand::thread_rng().gen_range(0.0, 1.0));
+            let (space, path) = this.seeded_path(seed_base.wrapping_add(idx as u64), scene, film);
+            this.run_chain(space, path, chain_mutations, scale, scene, film);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rejected_mutations_restore_the_samples() {
+        let mut space = PrimarySampleSpace::new(7, 0.01);
+        space.start_iteration(true);
+        let (mut space, first) = sampler::with_sampler(space, || {
+            (0..5).map(|_| sampler::random()).collect::<Vec<_>>()
+        });
+        space.accept();
+
+        for large_step in [false, true] {
+            space.start_iteration(large_step);
+            let (mutated, second) = sampler::with_sampler(space, || {
+                (0..8).map(|_| sampler::random()).collect::<Vec<_>>()
+            });
+            space = mutated;
+            assert_ne!(first[..], second[..5]);
+            space.reject();
+
+            space.start_iteration(false);
+            space.sigma = 0.0;
+            let (mutated, replayed) = sampler::with_sampler(space, || {
+                (0..5).map(|_| sampler::random()).collect::<Vec<_>>()
+            });
+            space = mutated;
+            space.sigma = 0.01;
+            assert_eq!(first, replayed);
+            space.reject();
+        }
+    }
+}
@@ -0,0 +1,211 @@
+pub use ambient_occlusion::AmbientOcclusion;
+pub use bidirectional::BidirectionalPathTracer;
+pub use debug::{DebugIntegrator, DebugOutput};
+pub use direct::DirectLighting;
+pub use emitters::EmitterSampler;
+pub use guided::GuidedPathTracer;
+pub use irradiance::IrradianceCaching;
+pub use irradiance_cache::IrradianceCache;
+pub use light::LightTracer;
+pub use mlt::MetropolisLightTransport;
+pub use path::PathTracer;
+pub use photon::PhotonMapper;
+pub use photon_map::CausticMap;
+pub use sd_tree::{DTree, GuidingPDF, SdTree};
+pub use whitted::Whitted;
+
+pub mod ambient_occlusion;
+pub mod bidirectional;
+pub mod debug;
+pub mod direct;
+pub mod emitters;
+pub mod guided;
+pub mod irradiance;
+pub mod irradiance_cache;
+pub mod kd_tree;
+pub mod light;
+pub mod mlt;
+pub mod path;
+pub mod photon;
+pub mod photon_map;
+pub mod sd_tree;
+pub mod whitted;
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::film::Film;
+use crate::hittables::{Hit, HitRecord};
+use crate::lights::{DeltaLight, LightGroups};
+use crate::linalg::{Color, Ray, RayType};
+use crate::materials::Compositing;
+use crate::sampler;
+use crate::scene::Scene;
+
+// Algorithm that computes the light arriving along the rays traced from the camera.
+pub trait Integrator: Send + Sync {
+    // Radiance arriving along a ray. Emitters only contribute if they are linked to one of the
+    // `light_groups`, which are those of the object that the ray leaves from (all of them for
+    // camera rays).
+    fn ray_color(&self, ray: Ray, scene: &Scene, light_groups: LightGroups) -> Color;
+
+    // Radiance arriving along a ray traced from the camera. Integrators that also trace paths from
+    // the lights can splat the light they carry to the camera onto the film.
+    fn camera_ray_color(&self, ray: Ray, scene: &Scene, _film: &Film) -> Color {
+        self.ray_color(ray, scene, LightGroups::all())
+    }
+
+    // Called instead of `camera_ray_color` for the camera samples that the render loop composites
+    // itself when rendering with alpha, i.e. the ones that miss or see holdouts and shadow catchers.
+    // Integrators that trace a light path for every camera sample still have to splat it here.
+    fn skipped_camera_sample(&self, _scene: &Scene, _film: &Film) {}
+
+    // Number of passes that the samples of each pixel are split into, for integrators that need to
+    // prepare something before each of them (e.g. shooting photons).
+    fn pass_count(&self) -> u32 {
+        1
+    }
+
+    // Called before each pass takes the given number of samples per pixel. Integrators that don't
+    // work pixel by pixel can splat their estimates for the whole pass onto the film here.
+    fn begin_pass(&mut self, _scene: &Scene, _film: &Film, _pass: u32, _samples_per_pixel: u32) {}
+}
+
+// Integrators that can be picked from the CLI.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IntegratorKind {
+    Path,
+    Bidirectional,
+    Photon,
+    Metropolis,
+    LightTracing,
+    IrradianceCaching,
+    AmbientOcclusion,
+    Direct,
+    Whitted,
+    Debug(DebugOutput),
+}
+
+impl FromStr for IntegratorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(IntegratorKind::Path),
+            "bdpt" => Ok(IntegratorKind::Bidirectional),
+            "photon" => Ok(IntegratorKind::Photon),
+            "mlt" => Ok(IntegratorKind::Metropolis),
+            "light" => Ok(IntegratorKind::LightTracing),
+            "irradiance" => Ok(IntegratorKind::IrradianceCaching),
+            "ao" => Ok(IntegratorKind::AmbientOcclusion),
+            "direct" => Ok(IntegratorKind::Direct),
+            "whitted" => Ok(IntegratorKind::Whitted),
+            "normals" => Ok(IntegratorKind::Debug(DebugOutput::Normals)),
+            "uvs" => Ok(IntegratorKind::Debug(DebugOutput::TextureCoords)),
+            "depth" => Ok(IntegratorKind::Debug(DebugOutput::Depth)),
+            "materials" => Ok(IntegratorKind::Debug(DebugOutput::MaterialIds)),
+            _ => Err(format!(
+                "Invalid integrator '{}'. Use one of [path, bdpt, photon, mlt, light, irradiance, ao, direct, whitted, normals, uvs, depth, \
+                 materials]",
+                s
+            )),
+        }
+    }
+}
+
+// Lights of the scene in the form that they are importance sampled in, if there are any.
+pub(crate) fn sampled_lights(scene: &Scene) -> Option<Arc<dyn Hit>> {
+    if scene.lights.is_empty() {
+        None
+    } else {
+        Some(scene.lights.clone() as Arc<dyn Hit>)
+    }
+}
+
+// Fraction of the directions scattered from a hit point that are sampled from the lights.
+pub(crate) fn light_sampling_weight(scene: &Scene, hit_record: &HitRecord) -> f64 {
+    scene
+        .light_sampling_weight
+        .unwrap_or_else(|| hit_record.material.light_sampling_weight())
+        .clamp(0.0, 1.0)
+}
+
+// Shadow rays towards each of the delta lights linked to a scattering point, together with
+// the distance to the light and the light that the ray would carry if nothing blocks it.
+pub(crate) fn delta_light_samples(
+    ray: Ray,
+    hit_record: &HitRecord,
+    attenuation: Color,
+    delta_lights: &[Arc<dyn DeltaLight>],
+) -> Vec<(Ray, f64, Color)> {
+    let mut samples = Vec::new();
+    for light in delta_lights {
+        if !light.light_links().intersects(hit_record.light_groups) {
+            continue;
+        }
+        if let Some(light_sample) = light.sample(hit_record.hit_point) {
+            let shadow_ray =
+                Ray::new(hit_record.hit_point, light_sample.direction).with_type(RayType::Shadow);
+            let color =
+                hit_record
+                    .material
+                    .scattering_color(ray, shadow_ray, hit_record, attenuation)
+                    * light_sample.radiance;
+            if color != Color::default() {
+                samples.push((shadow_ray, light_sample.distance - 0.001, color));
+            }
+        }
+    }
+
+    samples
+}
+
+// Light from the delta lights that reaches a scattering point without being blocked.
+pub(crate) fn delta_light_color(
+    ray: Ray,
+    hit_record: &HitRecord,
+    attenuation: Color,
+    scene: &Scene,
+) -> Color {
+    let mut color = Color::default();
+    for (shadow_ray, distance, light_color) in
+        delta_light_samples(ray, hit_record, attenuation, &scene.delta_lights)
+    {
+        if scene.world.hit(shadow_ray, 0.001, distance).is_none() {
+            color += light_color;
+        }
+    }
+
+    color
+}
+
+// Light emitted towards the origin of a ray by the surface it hits, if the surface is linked to one
+// of the light-linking groups of the object the ray leaves from.
+pub(crate) fn linked_emission(
+    ray: Ray,
+    hit_record: &HitRecord,
+    light_groups: LightGroups,
+) -> Color {
+    if hit_record.light_links.intersects(light_groups) {
+        hit_record.material.emit(ray, hit_record)
+    } else {
+        Color::default()
+    }
+}
+
+// Randomly terminates paths that carry little light, returning false for the ones that end, while
+// the ones that survive carry more of it to make up for the others.
+pub(crate) fn russian_roulette(beta: &mut Color) -> bool {
+    let survival_probability = beta.max_component().min(0.95);
+    if sampler::random() >= survival_probability {
+        return false;
+    }
+    *beta = beta.div_scalar(survival_probability);
+    true
+}
+
+// Whether the light leaving a surface towards the camera can be splatted onto the pixels that see
+// it. With alpha, the surfaces that aren't opaque are composited by the render loop instead.
+pub(crate) fn splats_onto_film(film: &Film, hit_record: &HitRecord) -> bool {
+    !film.alpha() || hit_record.material.compositing() == Compositing::Opaque
+}
@@ -0,0 +1,498 @@
+use std::sync::Arc;
+
+use crate::hittables::{Hit, HitRecord};
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Point3, Ray, RayType, Vec3};
+use crate::materials::ScatterRecord;
+use crate::pdfs::{HittablePDF, MisHeuristic, MixturePDF, PDF};
+use crate::scene::Scene;
+
+use super::irradiance_cache::IrradianceCache;
+use super::photon_map::CausticMap;
+use super::sd_tree::{GuidingPDF, SdTree};
+use super::{
+    delta_light_color, light_sampling_weight, linked_emission, russian_roulette, sampled_lights,
+    Integrator,
+};
+
+// Path tracer that samples each diffuse vertex twice: once towards the lights and once from the
+// material, combining both samples with multiple importance sampling. The light sampling weight of
+// the vertex scales the PDFs of the two strategies, trusting one sample more than the other.
+pub struct PathTracer {
+    mis_heuristic: MisHeuristic,
+    // Maximum number of rays traced for each path.
+    max_depth: i32,
+    // Number of rays traced before paths start being terminated randomly with Russian roulette.
+    rr_depth: i32,
+    // Whether paths continue after diffuse bounces, or only look for the light they hit directly.
+    indirect: bool,
+}
+
+// Fraction of the scattered directions sampled from the learned distribution of incident light,
+// where there is one, instead of from the material.
+const GUIDING_WEIGHT: f64 = 0.3;
+
+// Scattering point of a path, kept to learn the light arriving along the direction the path left
+// it in once the rest of the path is traced.
+struct GuidingVertex {
+    point: Point3,
+    direction: Vec3,
+    pdf: f64,
+    // Throughput of the path after the vertex, and the light it had picked up until then.
+    throughput: Color,
+    color: Color,
+}
+
+impl PathTracer {
+    pub fn new(mis_heuristic: MisHeuristic, max_depth: i32) -> Self {
+        Self {
+            mis_heuristic,
+            max_depth,
+            rr_depth: max_depth,
+            indirect: true,
+        }
+    }
+
+    pub fn with_rr_depth(mut self, rr_depth: i32) -> Self {
+        self.rr_depth = rr_depth;
+        self
+    }
+
+    pub(super) fn direct_only(mis_heuristic: MisHeuristic, max_depth: i32) -> Self {
+        Self {
+            indirect: false,
+            ..Self::new(mis_heuristic, max_depth)
+        }
+    }
+
+    // Follows a path from the given ray, adding up the light it picks up at each vertex weighted by
+    // the throughput of the path so far. With a caustics photon map, the light that reaches diffuse
+    // surfaces through specular bounces is estimated from the photons instead. With an SD-tree,
+    // diffuse vertices also sample directions from the light it learned, and the light found along
+    // them is recorded in it. With an irradiance cache, the indirect light reaching diffuse surfaces
+    // is interpolated from it, for path tracers that only look for the light coming straight from
+    // the lights.
+    pub(super) fn trace(
+        &self,
+        mut ray: Ray,
+        scene: &Scene,
+        mut light_groups: LightGroups,
+        caustics: Option<&CausticMap>,
+        guide: Option<&SdTree>,
+        irradiance: Option<&IrradianceCache>,
+    ) -> Color {
+        let mut color = Color::default();
+        let mut throughput = Color::from_scalar(1.0);
+        // MIS weight of the material sample that produced the current ray, which scales the light
+        // emitted by the surface (or background) it hits. It is 1 if light sampling can't find that
+        // light.
+        let mut emission_weight = 1.0;
+        // Whether the last diffuse vertex gathered caustics from the photons, and whether the current
+        // ray has bounced off specular surfaces since then, in which case the photons already carry
+        // the light it finds.
+        let mut gathered_caustics = false;
+        let mut caustic_path = false;
+        let mut guiding_vertices = Vec::new();
+
+        for depth in 0..self.max_depth {
+            let hit_record = match scene.world.hit(ray, 0.001, f64::INFINITY) {
+                Some(hit_record) => hit_record,
+                None => {
+                    color += throughput
+                        * scene
+                            .background
+                            .radiance(*ray.direction())
+                            .mul_scalar(emission_weight);
+                    break;
+                }
+            };
+
+            let photon_emitter = caustic_path
+                && caustics.is_some_and(|caustics| caustics.shoots_photons(&hit_record));
+            if !photon_emitter {
+                color += throughput
+                    * linked_emission(ray, &hit_record, light_groups).mul_scalar(emission_weight);
+            }
+            let scatter_record = match hit_record.material.scatter(ray, &hit_record) {
+                Some(scatter_record) => scatter_record,
+                None => break,
+            };
+            light_groups = hit_record.light_groups;
+
+            if let Some(specular_ray) = scatter_record.specular_ray {
+                throughput *= scatter_record.attenuation;
+                ray = specular_ray.with_type(RayType::Specular);
+                emission_weight = 1.0;
+                caustic_path = gathered_caustics;
+            } else {
+                let attenuation = scatter_record.attenuation;
+                color += throughput * delta_light_color(ray, &hit_record, attenuation, scene);
+                caustic_path = false;
+                gathered_caustics = false;
+                if let Some(caustics) = caustics {
+                    if !hit_record.material.is_volumetric() {
+                        color += throughput * caustics.radiance(ray, &hit_record, attenuation);
+                        gathered_caustics = true;
+                    }
+                }
+                if let Some(irradiance) = irradiance {
+                    if !hit_record.material.is_volumetric() {
+                        color += throughput * irradiance.radiance(scene, &hit_record, attenuation);
+                    }
+                }
+
+                // Guided vertices sample from a mixture of the material and the learned light, which
+                // also takes the place of the material PDF for multiple importance sampling.
+                let guiding_pdf = guide
+                    .filter(|_| !hit_record.material.is_volumetric())
+                    .and_then(|guide| guide.guide(hit_record.hit_point));
+                let scatter_record = match guiding_pdf {
+                    Some(guiding_pdf) => {
+                        let pdfs: Vec<Arc<dyn PDF>> = vec![
+                            scatter_record.pdf.clone().unwrap(),
+                            Arc::new(GuidingPDF::new(guiding_pdf)),
+                        ];
+                        let mixture =
+                            MixturePDF::with_weights(pdfs, &[1.0 - GUIDING_WEIGHT, GUIDING_WEIGHT]);
+                        ScatterRecord::new(None, Some(Arc::new(mixture)), attenuation)
+                    }
+                    None => scatter_record,
+                };
+                let material_pdf = scatter_record.pdf.as_ref().unwrap();
+                let light_weight = light_sampling_weight(scene, &hit_record);
+                let light_pdf = sampled_lights(scene)
+                    .filter(|_| light_weight > 0.0)
+                    .map(|lights| HittablePDF::new(lights, hit_record.hit_point));
+                if let Some(light_pdf) = &light_pdf {
+                    color += throughput
+                        * self.light_sample_color(
+                            ray,
+                            scene,
+                            &hit_record,
+                            &scatter_record,
+                            light_pdf,
+                            light_weight,
+                        );
+                }
+
+                let scatter_ray = Ray::new(hit_record.hit_point, material_pdf.generate())
+                    .with_type(RayType::Diffuse);
+                let pdf_val = material_pdf.value(*scatter_ray.direction());
+                if pdf_val <= 0.0 {
+                    break;
+                }
+                // Light that the lights can't sample along the ray, like the background behind them,
+                // is only found by the material sample, even when it is never picked.
+                let light_pdf_val = light_pdf
+                    .as_ref()
+                    .map_or(0.0, |light_pdf| light_pdf.value(*scatter_ray.direction()));
+                emission_weight = if light_pdf_val > 0.0 {
+                    self.mis_heuristic
+                        .weight((1.0 - light_weight) * pdf_val, light_weight * light_pdf_val)
+                } else {
+                    1.0
+                };
+                throughput *= hit_record
+                    .material
+                    .scattering_color(ray, scatter_ray, &hit_record, attenuation)
+                    .div_scalar(pdf_val);
+                ray = scatter_ray;
+                if guide.is_some() && !hit_record.material.is_volumetric() {
+                    guiding_vertices.push(GuidingVertex {
+                        point: hit_record.hit_point,
+                        direction: *ray.direction(),
+                        pdf: pdf_val,
+                        throughput,
+                        color,
+                    });
+                }
+
+                if !self.indirect {
+                    let radiance = match scene.world.hit(ray, 0.001, f64::INFINITY) {
+                        Some(light_hit) => linked_emission(ray, &light_hit, light_groups),
+                        None => scene.background.radiance(*ray.direction()),
+                    };
+                    color += throughput * radiance.mul_scalar(emission_weight);
+                    break;
+                }
+            }
+
+            if depth + 1 >= self.rr_depth && !russian_roulette(&mut throughput) {
+                break;
+            }
+        }
+
+        // The light that the path picked up after each vertex, divided by the throughput up to it, is
+        // an estimate of the light arriving at it along the scattered direction.
+        if let Some(guide) = guide {
+            for vertex in guiding_vertices {
+                let mut radiance = color - vertex.color;
+                for channel in 0..3 {
+                    radiance[channel] = if vertex.throughput[channel] > 0.0 {
+                        radiance[channel] / vertex.throughput[channel]
+                    } else {
+                        0.0
+                    };
+                }
+                guide.record(
+                    vertex.point,
+                    vertex.direction,
+                    radiance.luminance() / vertex.pdf,
+                );
+            }
+        }
+
+        color
+    }
+
+    // Light arriving at a scattering point along a direction sampled from the lights, weighted
+    // against the chance of the material sampling the same direction.
+    fn light_sample_color(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        hit_record: &HitRecord,
+        scatter_record: &ScatterRecord,
+        light_pdf: &HittablePDF,
+        light_weight: f64,
+    ) -> Color {
+        let shadow_ray =
+            Ray::new(hit_record.hit_point, light_pdf.generate()).with_type(RayType::Shadow);
+        let light_pdf_val = light_pdf.value(*shadow_ray.direction());
+        if light_pdf_val <= 0.0 {
+            return Color::default();
+        }
+
+        let scattering_color = hit_record.material.scattering_color(
+            ray,
+            shadow_ray,
+            hit_record,
+            scatter_record.attenuation,
+        );
+        if scattering_color == Color::default() {
+            return Color::default();
+        }
+
+        let radiance = match scene.world.hit(shadow_ray, 0.001, f64::INFINITY) {
+            Some(light_hit) => linked_emission(shadow_ray, &light_hit, hit_record.light_groups),
+            None => scene.background.radiance(*shadow_ray.direction()),
+        };
+        let weight = self.mis_heuristic.weight(
+            light_weight * light_pdf_val,
+            (1.0 - light_weight)
+                * scatter_record
+                    .pdf
+                    .as_ref()
+                    .unwrap()
+                    .value(*shadow_ray.direction()),
+        );
+
+        (scattering_color * radiance).mul_scalar(weight / light_pdf_val)
+    }
+}
+
+impl Integrator for PathTracer {
+    fn ray_color(&self, ray: Ray, scene: &Scene, light_groups: LightGroups) -> Color {
+        self.trace(ray, scene, light_groups, None, None, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::camera::Camera;
+    use crate::hittables::{ObjectFlags, Sphere, Visibility, World, XZRect};
+    use crate::lights::{LightList, LinkedLight, PointLight};
+    use crate::linalg::{Point3, Vec3};
+    use crate::materials::{DiffuseLight, Lambertian};
+
+    // Diffuse sphere lit by a uniform white background, which reflects exactly its albedo however
+    // many times the light bounces off it.
+    fn background_lit_sphere(lights: LightList) -> Scene {
+        let mut world = World::default();
+        world.add(Arc::new(Sphere::new(
+            Point3::default(),
+            1.0,
+            Arc::new(Lambertian::new(Color::from_scalar(0.5))),
+        )));
+        let camera = Camera::new(
+            Point3::new(0.0, 0.0, 5.0),
+            Point3::default(),
+            Vec3::new(0.0, 1.0, 0.0),
+            20.0,
+            1.0,
+            0.0,
+            5.0,
+        );
+        Scene::new(
+            world,
+            lights,
+            camera,
+            1.0,
+            Arc::new(Color::from_scalar(1.0)),
+        )
+    }
+
+    fn average_radiance(path_tracer: &PathTracer, scene: &Scene) -> f64 {
+        let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let sample_num = 20000;
+        let mut average = 0.0;
+        for _ in 0..sample_num {
+            average += path_tracer.ray_color(ray, scene, LightGroups::all()).g();
+        }
+        average / sample_num as f64
+    }
+
+    // Russian roulette must not change the average.
+    #[test]
+    fn russian_roulette_is_unbiased() {
+        let scene = background_lit_sphere(LightList::default());
+        let path_tracer = PathTracer::new(MisHeuristic::Power, 50).with_rr_depth(0);
+        let average = average_radiance(&path_tracer, &scene);
+
+        assert!((average - 0.5).abs() < 0.02, "average radiance {}", average);
+    }
+
+    // When every direction is sampled from the lights, the background that they can't sample is
+    // still found by the material samples.
+    #[test]
+    fn light_sampling_only_keeps_the_unsampled_light() {
+        let mut lights = LightList::default();
+        lights.add(Arc::new(XZRect::new(
+            -0.05,
+            0.05,
+            -0.05,
+            0.05,
+            10.0,
+            Arc::new(DiffuseLight::new(Color::from_scalar(1.0))),
+        )));
+        let mut scene = background_lit_sphere(lights);
+        scene.light_sampling_weight = Some(1.0);
+
+        let path_tracer = PathTracer::new(MisHeuristic::Power, 50);
+        let average = average_radiance(&path_tracer, &scene);
+
+        assert!((average - 0.5).abs() < 0.02, "average radiance {}", average);
+    }
+
+    // A floor under a small light, which the light samples find whether or not it casts shadows.
+    #[test]
+    fn lights_hidden_from_shadows_still_light() {
+        let floor_radiance = |visibility| {
+            let light = Arc::new(XZRect::new(
+                -0.1,
+                0.1,
+                -0.1,
+                0.1,
+                1.0,
+                Arc::new(DiffuseLight::new(Color::from_scalar(100.0)).two_sided()),
+            ));
+            let light = Arc::new(ObjectFlags::new(light).with_visibility(visibility));
+            let mut world = World::default();
+            world.add(Arc::new(XZRect::new(
+                -10.0,
+                10.0,
+                -10.0,
+                10.0,
+                0.0,
+                Arc::new(Lambertian::new(Color::from_scalar(0.5))),
+            )));
+            world.add(light.clone());
+            let mut lights = LightList::default();
+            lights.add(light);
+            let camera = Camera::new(
+                Point3::new(0.0, 0.5, 0.0),
+                Point3::default(),
+                Vec3::new(0.0, 0.0, 1.0),
+                20.0,
+                1.0,
+                0.0,
+                0.5,
+            );
+            let scene = Scene::new(world, lights, camera, 1.0, Arc::new(Color::default()));
+
+            let path_tracer = PathTracer::new(MisHeuristic::Power, 2);
+            let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, -1.0, 0.0));
+            let sample_num = 20000;
+            let mut average = 0.0;
+            for _ in 0..sample_num {
+                average += path_tracer.ray_color(ray, &scene, LightGroups::all()).g();
+            }
+            average / sample_num as f64
+        };
+
+        let lit = floor_radiance(Visibility::default());
+        let hidden = floor_radiance(Visibility {
+            shadow: false,
+            ..Visibility::default()
+        });
+        assert!(
+            (hidden - lit).abs() < 0.05 * lit,
+            "{} instead of {}",
+            hidden,
+            lit
+        );
+    }
+
+    // A floor in light-linking group 1 under an area light and a point light, which only light it
+    // when they are linked to its group.
+    #[test]
+    fn unlinked_lights_do_not_illuminate() {
+        let floor_radiance = |light_links| {
+            let light = Arc::new(XZRect::new(
+                -0.1,
+                0.1,
+                -0.1,
+                0.1,
+                1.0,
+                Arc::new(DiffuseLight::new(Color::from_scalar(100.0)).two_sided()),
+            ));
+            let light = Arc::new(ObjectFlags::new(light).with_light_links(light_links));
+            let floor = Arc::new(XZRect::new(
+                -10.0,
+                10.0,
+                -10.0,
+                10.0,
+                0.0,
+                Arc::new(Lambertian::new(Color::from_scalar(0.5))),
+            ));
+            let mut world = World::default();
+            world.add(Arc::new(
+                ObjectFlags::new(floor).with_light_groups(LightGroups::from_groups(&[1])),
+            ));
+            world.add(light.clone());
+            let mut lights = LightList::default();
+            lights.add(light);
+            let camera = Camera::new(
+                Point3::new(0.0, 0.5, 0.0),
+                Point3::default(),
+                Vec3::new(0.0, 0.0, 1.0),
+                20.0,
+                1.0,
+                0.0,
+                0.5,
+            );
+            let mut scene = Scene::new(world, lights, camera, 1.0, Arc::new(Color::default()));
+            scene.delta_lights.push(Arc::new(LinkedLight::new(
+                Arc::new(PointLight::new(
+                    Point3::new(0.5, 1.0, 0.0),
+                    Color::from_scalar(1.0),
+                )),
+                light_links,
+            )));
+
+            let path_tracer = PathTracer::new(MisHeuristic::Power, 2);
+            let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, -1.0, 0.0));
+            let mut total = 0.0;
+            for _ in 0..1000 {
+                total += path_tracer.ray_color(ray, &scene, LightGroups::all()).g();
+            }
+            total
+        };
+
+        assert_eq!(floor_radiance(LightGroups::from_groups(&[0, 2])), 0.0);
+        assert!(floor_radiance(LightGroups::from_groups(&[1])) > 0.0);
+    }
+}
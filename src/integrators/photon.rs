@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use crate::film::Film;
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Ray};
+use crate::pdfs::MisHeuristic;
+use crate::scene::Scene;
+
+use super::emitters::EmitterSampler;
+use super::photon_map::CausticMap;
+use super::{Integrator, PathTracer};
+
+// How quickly the radius of the photon lookups shrinks from one pass to the next, between 0 and 1.
+// Lower values shrink it faster, trading noise for bias.
+const RADIUS_REDUCTION: f64 = 2.0 / 3.0;
+
+// Path tracer that estimates caustics from a photon map instead of waiting for paths to find the
+// lights through specular surfaces. The photons are shot anew for each pass with a smaller lookup
+// radius, as in progressive photon mapping, so the bias of the density estimation goes away as the
+// number of passes grows.
+pub struct PhotonMapper {
+    path_tracer: PathTracer,
+    emitters: Arc<EmitterSampler>,
+    max_depth: i32,
+    // Number of photons shot from the emitters in each pass.
+    photon_num: usize,
+    pass_num: u32,
+    // Lookup radius of the first pass.
+    radius: f64,
+    caustics: Option<CausticMap>,
+}
+
+impl PhotonMapper {
+    pub fn new(scene: &Scene, mis_heuristic: MisHeuristic, max_depth: i32) -> Self {
+        Self {
+            path_tracer: PathTracer::new(mis_heuristic, max_depth),
+            emitters: Arc::new(EmitterSampler::new(scene)),
+            max_depth,
+            photon_num: 100_000,
+            pass_num: 16,
+            radius: scene.radius() / 100.0,
+            caustics: None,
+        }
+    }
+
+    pub fn with_rr_depth(mut self, rr_depth: i32) -> Self {
+        self.path_tracer = self.path_tracer.with_rr_depth(rr_depth);
+        self
+    }
+
+    pub fn with_photons(mut self, photon_num: usize) -> Self {
+        self.photon_num = photon_num;
+        self
+    }
+
+    pub fn with_passes(mut self, pass_num: u32) -> Self {
+        self.pass_num = pass_num;
+        self
+    }
+
+    pub fn with_radius(mut self, radius: f64) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    // Lookup radius of a pass, shrinking the area of the lookups by (i + alpha) / (i + 1) after the
+    // i-th pass (Knaus and Zwicker, "Progressive photon mapping: a probabilistic approach").
+    fn pass_radius(&self, pass: u32) -> f64 {
+        let mut radius_sq = self.radius * self.radius;
+        for i in 1..=pass {
+            radius_sq *= (i as f64 + RADIUS_REDUCTION) / (i as f64 + 1.0);
+        }
+
+        radius_sq.sqrt()
+    }
+}
+
+impl Integrator for PhotonMapper {
+    fn ray_color(&self, ray: Ray, scene: &Scene, light_groups: LightGroups) -> Color {
+        self.path_tracer
+            .trace(ray, scene, light_groups, self.caustics.as_ref(), None, None)
+    }
+
+    fn pass_count(&self) -> u32 {
+        self.pass_num
+    }
+
+    fn begin_pass(&mut self, scene: &Scene, _film: &Film, pass: u32, _samples_per_pixel: u32) {
+        self.caustics = Some(CausticMap::new(
+            scene,
+            self.emitters.clone(),
+            self.photon_num,
+            self.pass_radius(pass),
+            self.max_depth,
+        ));
+    }
+}
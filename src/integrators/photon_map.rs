@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::hittables::{Hit, HitRecord};
+use crate::linalg::{Color, Point3, Ray, RayType, Vec3};
+use crate::scene::Scene;
+
+use super::emitters::{emitted_radiance, sample_emission_direction, EmitterSampler};
+use super::kd_tree::KdTree;
+
+// Light that reached a diffuse surface after leaving an emitter and bouncing off specular surfaces.
+struct Photon {
+    // Direction the photon was travelling in when it reached the surface.
+    direction: Vec3,
+    power: Color,
+}
+
+// Caustics photon map, made of the photons shot from the emitters that bounce off at least one
+// specular surface before reaching a diffuse one. The radiance they carry is estimated by density
+// estimation within a fixed radius of the shading points.
+pub struct CausticMap {
+    photons: KdTree<Photon>,
+    radius: f64,
+    emitters: Arc<EmitterSampler>,
+}
+
+// Follows a photon from an emitter through specular bounces, returning where it lands if it
+// reaches a diffuse surface after at least one of them.
+fn trace_photon(
+    scene: &Scene,
+    emitters: &EmitterSampler,
+    max_depth: i32,
+) -> Option<(Point3, Photon)> {
+    let (hit_record, pdf) = emitters.sample()?;
+    let (direction, direction_pdf) = sample_emission_direction(&hit_record);
+    if pdf <= 0.0 || direction_pdf <= 0.0 {
+        return None;
+    }
+
+    let cos_theta = hit_record.normal.dot(direction).abs() / direction.length();
+    let mut power =
+        emitted_radiance(&hit_record, direction).mul_scalar(cos_theta / (pdf * direction_pdf));
+    let mut ray = Ray::new(hit_record.hit_point, direction).with_type(RayType::Diffuse);
+
+    let light_links = hit_record.light_links;
+    for depth in 0..max_depth {
+        let hit_record = scene.world.hit(ray, 0.001, f64::INFINITY)?;
+        // As when the paths are traced from the camera, the emitter must be linked to the first
+        // surface its light reaches, which is the last specular surface seen from the other end.
+        if depth == 0 && !light_links.intersects(hit_record.light_groups) {
+            return None;
+        }
+        let scatter_record = hit_record.material.scatter(ray, &hit_record)?;
+        match scatter_record.specular_ray {
+            Some(specular_ray) => {
+                power *= scatter_record.attenuation;
+                ray = specular_ray.with_type(RayType::Specular);
+            }
+            None if depth > 0 && !hit_record.material.is_volumetric() => {
+                let photon = Photon {
+                    direction: ray.direction().into_unit_vec(),
+                    power,
+                };
+                return Some((hit_record.hit_point, photon));
+            }
+            None => return None,
+        }
+    }
+
+    None
+}
+
+impl CausticMap {
+    // Shoots the given number of photons from the emitters of the scene.
+    pub fn new(
+        scene: &Scene,
+        emitters: Arc<EmitterSampler>,
+        photon_num: usize,
+        radius: f64,
+        max_depth: i32,
+    ) -> Self {
+        let photons: Vec<(Point3, Photon)> = (0..photon_num)
+            .into_par_iter()
+            .filter_map(|_| {
+                let (point, mut photon) = trace_photon(scene, &emitters, max_depth)?;
+                photon.power = photon.power.div_scalar(photon_num as f64);
+                Some((point, photon))
+            })
+            .collect();
+
+        Self {
+            photons: KdTree::new(photons),
+            radius,
+            emitters,
+        }
+    }
+
+    // Whether the light emitted by a surface is carried by the photons when it reaches diffuse
+    // surfaces through specular bounces.
+    pub fn shoots_photons(&self, hit_record: &HitRecord) -> bool {
+        self.emitters.pdf(hit_record) > 0.0
+    }
+
+    // Caustic radiance scattered from a diffuse surface back along a ray, estimated from the
+    // photons around the hit point. `attenuation` is the one returned by scattering the ray.
+    pub fn radiance(&self, ray: Ray, hit_record: &HitRecord, attenuation: Color) -> Color {
+        let mut power = Color::default();
+        self.photons
+            .for_each_within(hit_record.hit_point, self.radius, |_, photon| {
+                let towards_light = photon.direction.mul_scalar(-1.0);
+                let cos_theta = hit_record.normal.dot(towards_light).abs();
+                if cos_theta < 1e-6 {
+                    return;
+                }
+
+                // The photons already carry the cosine at the surface, so it is divided out of the
+                // light scattered by the material.
+                let scattering_color = hit_record.material.scattering_color(
+                    ray,
+                    Ray::new(hit_record.hit_point, towards_light),
+                    hit_record,
+                    attenuation,
+                );
+                power += scattering_color.div_scalar(cos_theta) * photon.power;
+            });
+
+        power.div_scalar(std::f64::consts::PI * self.radius * self.radius)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::hittables::{FlipFace, ObjectFlags, World, XZRect};
+    use crate::lights::{LightGroups, LightList, LightSelection};
+    use crate::materials::{DiffuseLight, Lambertian, Metal};
+
+    // Photons shot from a light that only lights group 0, down onto a mirror in the given groups and
+    // up onto a diffuse ceiling.
+    fn caustic_photon_num(mirror_groups: LightGroups) -> usize {
+        let mut world = World::default();
+        let mirror = Arc::new(XZRect::new(
+            -10.0,
+            10.0,
+            -10.0,
+            10.0,
+            0.0,
+            Arc::new(Metal::new(Color::from_scalar(1.0), 0.0)),
+        ));
+        world.add(Arc::new(
+            ObjectFlags::new(mirror).with_light_groups(mirror_groups),
+        ));
+        world.add(Arc::new(XZRect::new(
+            -10.0,
+            10.0,
+            -10.0,
+            10.0,
+            3.0,
+            Arc::new(Lambertian::new(Color::from_scalar(0.5))),
+        )));
+        let light = Arc::new(FlipFace::new(Arc::new(XZRect::new(
+            -0.5,
+            0.5,
+            -0.5,
+            0.5,
+            2.0,
+            Arc::new(DiffuseLight::new(Color::from_scalar(4.0))),
+        ))));
+        world.add(Arc::new(
+            ObjectFlags::new(light).with_light_links(LightGroups::default()),
+        ));
+        let lights = LightList::from_world(&world, LightSelection::Power);
+        let camera = Camera::new(
+            Point3::new(0.0, 1.0, 5.0),
+            Point3::default(),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            10.0,
+        );
+        let scene = Scene::new(world, lights, camera, 1.0, Arc::new(Color::default()));
+
+        let emitters = Arc::new(EmitterSampler::new(&scene));
+        CausticMap::new(&scene, emitters, 1000, 0.1, 5)
+            .photons
+            .len()
+    }
+
+    // As seen from the ceiling, the light arrives from the mirror, which it must be linked to.
+    #[test]
+    fn photons_follow_light_links() {
+        assert!(caustic_photon_num(LightGroups::default()) > 0);
+        assert_eq!(caustic_photon_num(LightGroups::from_groups(&[1])), 0);
+    }
+}
@@ -0,0 +1,450 @@
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::film::atomic_add;
+use crate::hittables::Hit;
+use crate::linalg::{Point3, Vec3};
+use crate::pdfs::PDF;
+use crate::sampler;
+use crate::scene::Scene;
+
+// Deepest level that the directional quadtrees are refined to.
+const MAX_DTREE_DEPTH: usize = 20;
+
+// Node of a directional quadtree, with the energy recorded through each of its quadrants. A child
+// index of 0 means that the quadrant is a leaf, since the root can't be anyone's child.
+struct QuadNode {
+    sums: [AtomicU64; 4],
+    children: [usize; 4],
+}
+
+impl QuadNode {
+    fn new(sums: [f64; 4]) -> Self {
+        Self {
+            sums: [
+                AtomicU64::new(sums[0].to_bits()),
+                AtomicU64::new(sums[1].to_bits()),
+                AtomicU64::new(sums[2].to_bits()),
+                AtomicU64::new(sums[3].to_bits()),
+            ],
+            children: [0; 4],
+        }
+    }
+
+    fn sums(&self) -> [f64; 4] {
+        [
+            f64::from_bits(self.sums[0].load(Ordering::Relaxed)),
+            f64::from_bits(self.sums[1].load(Ordering::Relaxed)),
+            f64::from_bits(self.sums[2].load(Ordering::Relaxed)),
+            f64::from_bits(self.sums[3].load(Ordering::Relaxed)),
+        ]
+    }
+}
+
+// Maps a direction to the unit square, keeping areas proportional to solid angles: the first
+// coordinate is the cosine of its polar angle and the second its azimuth, both rescaled.
+fn to_square(direction: Vec3) -> (f64, f64) {
+    let direction = direction.into_unit_vec();
+    let mut phi = direction.y().atan2(direction.x());
+    if phi < 0.0 {
+        phi += 2.0 * PI;
+    }
+
+    (
+        ((direction.z().clamp(-1.0, 1.0) + 1.0) / 2.0).min(1.0 - f64::EPSILON),
+        (phi / (2.0 * PI)).min(1.0 - f64::EPSILON),
+    )
+}
+
+fn from_square(x: f64, y: f64) -> Vec3 {
+    let z = 2.0 * x - 1.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * y;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// Quadrant of a node that a point of its square falls in, and the point in the square of the
+// quadrant.
+fn quadrant(x: f64, y: f64) -> (usize, f64, f64) {
+    let column = usize::from(x >= 0.5);
+    let row = usize::from(y >= 0.5);
+
+    (
+        column + 2 * row,
+        2.0 * x - column as f64,
+        2.0 * y - row as f64,
+    )
+}
+
+// Distribution of the light arriving at a region of the scene over the sphere of directions, as a
+// quadtree over `to_square` whose nodes are refined where more light arrives (Müller et al.,
+// "Practical path guiding for efficient light-transport simulation").
+pub struct DTree {
+    nodes: Vec<QuadNode>,
+}
+
+impl Default for DTree {
+    fn default() -> Self {
+        Self {
+            nodes: vec![QuadNode::new([0.0; 4])],
+        }
+    }
+}
+
+impl DTree {
+    pub fn total(&self) -> f64 {
+        self.nodes[0].sums().iter().sum()
+    }
+
+    // Adds energy arriving along a direction, from any thread.
+    pub fn record(&self, direction: Vec3, value: f64) {
+        if !(value.is_finite() && value > 0.0) {
+            return;
+        }
+
+        let (mut x, mut y) = to_square(direction);
+        let mut node = 0;
+        loop {
+            let (idx, child_x, child_y) = quadrant(x, y);
+            atomic_add(&self.nodes[node].sums[idx], value);
+            node = self.nodes[node].children[idx];
+            if node == 0 {
+                return;
+            }
+            x = child_x;
+            y = child_y;
+        }
+    }
+
+    // Density per unit solid angle of sampling a direction in proportion to the recorded energy.
+    pub fn pdf(&self, direction: Vec3) -> f64 {
+        if self.total() <= 0.0 {
+            return 0.0;
+        }
+
+        let (mut x, mut y) = to_square(direction);
+        let mut node = 0;
+        let mut density = 1.0;
+        loop {
+            let (idx, child_x, child_y) = quadrant(x, y);
+            let sums = self.nodes[node].sums();
+            let node_total: f64 = sums.iter().sum();
+            if sums[idx] <= 0.0 {
+                return 0.0;
+            }
+            density *= 4.0 * sums[idx] / node_total;
+
+            node = self.nodes[node].children[idx];
+            if node == 0 {
+                return density / (4.0 * PI);
+            }
+            x = child_x;
+            y = child_y;
+        }
+    }
+
+    pub fn sample(&self) -> Vec3 {
+        let (mut x, mut y, mut size) = (0.0, 0.0, 1.0);
+        let mut node = 0;
+        loop {
+            let sums = self.nodes[node].sums();
+            let mut u = sampler::random() * sums.iter().sum::<f64>();
+            let mut idx = 0;
+            for (quadrant, sum) in sums.iter().enumerate() {
+                if *sum > 0.0 {
+                    idx = quadrant;
+                    if u < *sum {
+                        break;
+                    }
+                    u -= sum;
+                }
+            }
+
+            size /= 2.0;
+            x += (idx % 2) as f64 * size;
+            y += (idx / 2) as f64 * size;
+            node = self.nodes[node].children[idx];
+            if node == 0 {
+                return from_square(x + sampler::random() * size, y + sampler::random() * size);
+            }
+        }
+    }
+
+    // Copy of the tree, with the energy recorded so far.
+    fn copy(&self) -> Self {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| QuadNode {
+                children: node.children,
+                ..QuadNode::new(node.sums())
+            })
+            .collect();
+
+        Self { nodes }
+    }
+
+    // Empty tree whose quadrants are subdivided wherever more than `threshold` of the energy of
+    // this tree arrived, and merged elsewhere, so that its leaves get similar amounts of it.
+    pub fn refined(&self, threshold: f64) -> Self {
+        let mut tree = Self::default();
+        let total = self.total();
+        if total > 0.0 {
+            self.subdivide(
+                Some(0),
+                self.nodes[0].sums(),
+                0,
+                1,
+                total * threshold,
+                &mut tree,
+            );
+        }
+
+        tree
+    }
+
+    fn subdivide(
+        &self,
+        node: Option<usize>,
+        sums: [f64; 4],
+        new_node: usize,
+        depth: usize,
+        min_energy: f64,
+        tree: &mut Self,
+    ) {
+        if depth >= MAX_DTREE_DEPTH {
+            return;
+        }
+
+        for (idx, sum) in sums.iter().enumerate() {
+            if *sum <= min_energy {
+                continue;
+            }
+
+            // Quadrants that were leaves of this tree spread their energy evenly over their
+            // children.
+            let child = node
+                .map(|node| self.nodes[node].children[idx])
+                .filter(|child| *child != 0);
+            let child_sums = match child {
+                Some(child) => self.nodes[child].sums(),
+                None => [sum / 4.0; 4],
+            };
+
+            let new_child = tree.nodes.len();
+            tree.nodes.push(QuadNode::new([0.0; 4]));
+            tree.nodes[new_node].children[idx] = new_child;
+            self.subdivide(child, child_sums, new_child, depth + 1, min_energy, tree);
+        }
+    }
+}
+
+// Samples directions from a trained directional quadtree.
+pub struct GuidingPDF {
+    tree: Arc<DTree>,
+}
+
+impl GuidingPDF {
+    pub fn new(tree: Arc<DTree>) -> Self {
+        Self { tree }
+    }
+}
+
+impl PDF for GuidingPDF {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.tree.pdf(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.tree.sample()
+    }
+}
+
+// Region of the scene with the distribution of incident light guiding the paths through it, and
+// the one being learned from them.
+struct SpatialLeaf {
+    depth: usize,
+    sample_num: AtomicU64,
+    sampling: Arc<DTree>,
+    building: DTree,
+}
+
+enum SpatialNode {
+    Inner { axis: usize, children: [usize; 2] },
+    Leaf(SpatialLeaf),
+}
+
+// Spatial-directional tree: a binary tree that halves the bounding box of the scene along the x, y
+// and z axes in turn, with a directional quadtree in each of its leaves. Leaves that get many
+// samples are split, so the distributions get finer where paths go the most.
+pub struct SdTree {
+    min_corner: Point3,
+    max_corner: Point3,
+    nodes: Vec<SpatialNode>,
+}
+
+impl SdTree {
+    pub fn new(scene: &Scene) -> Self {
+        let (min_corner, max_corner) = match scene.world.bounding_box(0.0, 1.0) {
+            Some(bounding_box) => (bounding_box.min_corner(), bounding_box.max_corner()),
+            None => (Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0)),
+        };
+        let root = SpatialLeaf {
+            depth: 0,
+            sample_num: AtomicU64::new(0),
+            sampling: Arc::new(DTree::default()),
+            building: DTree::default(),
+        };
+
+        Self {
+            min_corner,
+            max_corner,
+            nodes: vec![SpatialNode::Leaf(root)],
+        }
+    }
+
+    fn leaf(&self, point: Point3) -> &SpatialLeaf {
+        let (mut min_corner, mut max_corner) = (self.min_corner, self.max_corner);
+        let mut node = 0;
+        loop {
+            match &self.nodes[node] {
+                SpatialNode::Inner { axis, children } => {
+                    let middle = (min_corner[*axis] + max_corner[*axis]) / 2.0;
+                    if point[*axis] < middle {
+                        max_corner[*axis] = middle;
+                        node = children[0];
+                    } else {
+                        min_corner[*axis] = middle;
+                        node = children[1];
+                    }
+                }
+                SpatialNode::Leaf(leaf) => return leaf,
+            }
+        }
+    }
+
+    // Distribution to guide the paths leaving a point with, once something has been learned there.
+    pub fn guide(&self, point: Point3) -> Option<Arc<DTree>> {
+        let sampling = &self.leaf(point).sampling;
+        if sampling.total() > 0.0 {
+            Some(sampling.clone())
+        } else {
+            None
+        }
+    }
+
+    // Records an estimate of the light arriving at a point along a direction, divided by the
+    // density of having sampled the direction.
+    pub fn record(&self, point: Point3, direction: Vec3, value: f64) {
+        let leaf = self.leaf(point);
+        leaf.sample_num.fetch_add(1, Ordering::Relaxed);
+        leaf.building.record(direction, value);
+    }
+
+    // Starts guiding the paths with what was recorded since the last refinement, and starts learning
+    // anew from them. Leaves with more
+    // than `spatial_threshold` times the square root of all the recorded samples are split first,
+    // so that the number of leaves grows with that square root, and then the directional quadtrees
+    // are refined to put about `directional_threshold` of the energy in each of their leaves at most.
+    pub fn refine(&mut self, spatial_threshold: f64, directional_threshold: f64) {
+        let sample_num: u64 = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                SpatialNode::Leaf(leaf) => leaf.sample_num.load(Ordering::Relaxed),
+                SpatialNode::Inner { .. } => 0,
+            })
+            .sum();
+        let max_samples = (spatial_threshold * (sample_num as f64).sqrt()) as u64;
+
+        let mut node = 0;
+        while node < self.nodes.len() {
+            let children = match &self.nodes[node] {
+                SpatialNode::Leaf(leaf)
+                    if leaf.sample_num.load(Ordering::Relaxed) > max_samples =>
+                {
+                    let sample_num = leaf.sample_num.load(Ordering::Relaxed) / 2;
+                    let child = || SpatialLeaf {
+                        depth: leaf.depth + 1,
+                        sample_num: AtomicU64::new(sample_num),
+                        sampling: leaf.sampling.clone(),
+                        building: leaf.building.copy(),
+                    };
+                    [child(), child()]
+                }
+                _ => {
+                    node += 1;
+                    continue;
+                }
+            };
+
+            let axis = match &self.nodes[node] {
+                SpatialNode::Leaf(leaf) => leaf.depth % 3,
+                SpatialNode::Inner { .. } => unreachable!(),
+            };
+            let first_child = self.nodes.len();
+            let [left, right] = children;
+            self.nodes.push(SpatialNode::Leaf(left));
+            self.nodes.push(SpatialNode::Leaf(right));
+            self.nodes[node] = SpatialNode::Inner {
+                axis,
+                children: [first_child, first_child + 1],
+            };
+        }
+
+        for node in self.nodes.iter_mut() {
+            if let SpatialNode::Leaf(leaf) = node {
+                let sampling = std::mem::take(&mut leaf.building);
+                leaf.building = sampling.refined(directional_threshold);
+                leaf.sampling = Arc::new(sampling);
+                leaf.sample_num = AtomicU64::new(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sampled_directions_follow_the_recorded_energy() {
+        let towards = Vec3::new(0.3, -0.4, 0.8).into_unit_vec();
+        let mut tree = DTree::default();
+        let mut building = DTree::default();
+        for _ in 0..4 {
+            for _ in 0..20_000 {
+                let direction = Vec3::random_unit_vector();
+                let value = if direction.dot(towards) > 0.9 {
+                    1.0
+                } else {
+                    0.01
+                };
+                building.record(direction, value);
+            }
+            tree = building;
+            building = tree.refined(0.01);
+        }
+
+        // The density integrates to one over the sphere, which the square maps onto preserving
+        // areas.
+        let resolution = 1000;
+        let integral: f64 = (0..resolution * resolution)
+            .map(|idx| {
+                let x = ((idx % resolution) as f64 + 0.5) / resolution as f64;
+                let y = ((idx / resolution) as f64 + 0.5) / resolution as f64;
+                tree.pdf(from_square(x, y)) * 4.0 * PI
+            })
+            .sum::<f64>()
+            / (resolution * resolution) as f64;
+        assert!((integral - 1.0).abs() < 0.01, "integral {}", integral);
+
+        // Most samples go where most of the energy arrived from.
+        let towards_num = (0..10_000)
+            .filter(|_| tree.sample().into_unit_vec().dot(towards) > 0.9)
+            .count();
+        assert!(towards_num > 6_500, "{} samples", towards_num);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::hittables::Hit;
+use crate::lights::LightGroups;
+use crate::linalg::{Color, Ray, RayType};
+use crate::pdfs::{HittablePDF, PDF};
+use crate::scene::Scene;
+
+use super::{delta_light_color, linked_emission, sampled_lights, Integrator};
+
+// Whitted-style ray tracer: light sources are sampled directly at diffuse surfaces and only perfect
+// reflections and refractions are followed, which is fast but misses all indirect diffuse light.
+pub struct Whitted {
+    max_depth: i32,
+}
+
+impl Whitted {
+    pub fn new(max_depth: i32) -> Self {
+        Self { max_depth }
+    }
+
+    fn trace(&self, ray: Ray, scene: &Scene, light_groups: LightGroups, depth: i32) -> Color {
+        if depth <= 0 {
+            return Color::default();
+        }
+
+        let hit_record = match scene.world.hit(ray, 0.001, f64::INFINITY) {
+            Some(hit_record) => hit_record,
+            None => return scene.background.radiance(*ray.direction()),
+        };
+
+        let emitted_color = linked_emission(ray, &hit_record, light_groups);
+        let scatter_record = match hit_record.material.scatter(ray, &hit_record) {
+            Some(scatter_record) => scatter_record,
+            None => return emitted_color,
+        };
+
+        if let Some(specular_ray) = scatter_record.specular_ray {
+            return emitted_color
+                + scatter_record.attenuation
+                    * self.trace(
+                        specular_ray.with_type(RayType::Specular),
+                        scene,
+                        hit_record.light_groups,
+                        depth - 1,
+                    );
+        }
+
+        let attenuation = scatter_record.attenuation;
+        let mut color = emitted_color + delta_light_color(ray, &hit_record, attenuation, scene);
+
+        // A single shadow ray towards the area lights, without any material sampling.
+        if let Some(lights) = sampled_lights(scene) {
+            let light_pdf = HittablePDF::new(lights, hit_record.hit_point);
+            let shadow_ray =
+                Ray::new(hit_record.hit_point, light_pdf.generate()).with_type(RayType::Shadow);
+            let pdf_val = light_pdf.value(*shadow_ray.direction());
+            if pdf_val > 0.0 {
+                let radiance = match scene.world.hit(shadow_ray, 0.001, f64::INFINITY) {
+                    Some(light_hit) => {
+                        linked_emission(shadow_ray, &light_hit, hit_record.light_groups)
+                    }
+                    None => scene.background.radiance(*shadow_ray.direction()),
+                };
+                color += hit_record
+                    .material
+                    .scattering_color(ray, shadow_ray, &hit_record, attenuation)
+                    .div_scalar(pdf_val)
+                    * radiance;
+            }
+        }
+
+        color
+    }
+}
+
+impl Integrator for Whitted {
+    fn ray_color(&self, ray: Ray, scene: &Scene, light_groups: LightGroups) -> Color {
+        self.trace(ray, scene, light_groups, self.max_depth)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::camera::Camera;
+    use crate::hittables::{FlipFace, World, XZRect};
+    use crate::lights::{LightList, LightSelection};
+    use crate::linalg::{Point3, Vec3};
+    use crate::materials::{DiffuseLight, Metal};
+
+    #[test]
+    fn mirrors_reflect_the_lights() {
+        let mut world = World::default();
+        world.add(Arc::new(XZRect::new(
+            -2.0,
+            2.0,
+            -2.0,
+            2.0,
+            0.0,
+            Arc::new(Metal::new(Color::from_scalar(0.8), 0.0)),
+        )));
+        world.add(Arc::new(FlipFace::new(Arc::new(XZRect::new(
+            -1.0,
+            1.0,
+            -1.0,
+            1.0,
+            1.0,
+            Arc::new(DiffuseLight::new(Color::from_scalar(4.0))),
+        )))));
+        let lights = LightList::from_world(&world, LightSelection::Power);
+        let camera = Camera::new(
+            Point3::new(0.0, 0.5, 0.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            40.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let scene = Scene::new(world, lights, camera, 1.0, Arc::new(Color::default()));
+
+        let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let color = Whitted::new(5).ray_color(ray, &scene, LightGroups::all());
+        assert!((color.luminance() - 3.2).abs() < 1e-9, "{:?}", color);
+
+        // Past the maximum depth the reflection is black.
+        let color = Whitted::new(1).ray_color(ray, &scene, LightGroups::all());
+        assert_eq!(color, Color::default());
+    }
+}
@@ -6,10 +6,15 @@ extern crate rand;
 extern crate rayon;
 
 pub mod camera;
+pub mod film;
 pub mod hittables;
+pub mod integrators;
+pub mod lights;
 pub mod linalg;
 pub mod load_mesh;
 pub mod materials;
 pub mod pdfs;
 pub mod render;
+pub mod sampler;
 pub mod scene;
+pub mod textures;
@@ -0,0 +1,30 @@
+use crate::linalg::{Color, Point3, Vec3};
+
+use super::{DeltaLight, LightSample};
+
+// Light coming from infinitely far away along a single direction, like sunlight. The radiance is
+// the irradiance that the light delivers to a surface facing it.
+pub struct DirectionalLight {
+    // Direction that the light travels in.
+    direction: Vec3,
+    irradiance: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color) -> Self {
+        Self {
+            direction: direction.into_unit_vec(),
+            irradiance,
+        }
+    }
+}
+
+impl DeltaLight for DirectionalLight {
+    fn sample(&self, _point: Point3) -> Option<LightSample> {
+        Some(LightSample::new(
+            self.direction.mul_scalar(-1.0),
+            f64::INFINITY,
+            self.irradiance,
+        ))
+    }
+}
@@ -0,0 +1,186 @@
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::hittables::{BoundingBox, Hit, HitRecord};
+use crate::linalg::{Color, Point3, Ray, Vec3};
+use crate::pdfs::Distribution2D;
+use crate::sampler;
+
+use super::exr::read_exr;
+use super::Background;
+
+// Light arriving from all directions, given by an equirectangular image that wraps around the scene.
+// The top row of the image is straight up (+y) and the columns go around the y axis, starting at +x.
+// Directions are importance sampled by the luminance of the image, so small and bright regions like
+// the sun are found easily.
+#[derive(Clone)]
+pub struct EnvironmentMap {
+    pixels: Arc<Vec<Color>>,
+    width: usize,
+    height: usize,
+    // Rotation of the map around the y axis, in radians.
+    rotation: f64,
+    distribution: Arc<Distribution2D>,
+    // Radius of a sphere around the scene, which is only used to estimate the power of the map.
+    scene_radius: f64,
+}
+
+impl EnvironmentMap {
+    pub fn new(pixels: Vec<Color>, width: usize, height: usize, rotation_degrees: f64) -> Self {
+        // The rows near the poles cover a smaller solid angle, which the sin(theta) factor accounts
+        // for.
+        let mut func = Vec::with_capacity(width * height);
+        for row in 0..height {
+            let sin_theta = (PI * (row as f64 + 0.5) / height as f64).sin();
+            for column in 0..width {
+                func.push(pixels[row * width + column].luminance() * sin_theta);
+            }
+        }
+
+        Self {
+            pixels: Arc::new(pixels),
+            width,
+            height,
+            rotation: rotation_degrees.to_radians(),
+            distribution: Arc::new(Distribution2D::new(&func, width)),
+            scene_radius: 1.0,
+        }
+    }
+
+    // Loads a Radiance .hdr or an OpenEXR .exr image, depending on its extension.
+    pub fn load(path: &Path, rotation_degrees: f64) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let is_exr = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("exr"));
+        if is_exr {
+            let (pixels, width, height) = read_exr(reader)?;
+            return Ok(Self::new(pixels, width, height, rotation_degrees));
+        }
+
+        let decoder = image::hdr::HdrDecoder::new(reader)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?
+            .into_iter()
+            .map(|pixel| Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64))
+            .collect();
+
+        Ok(Self::new(
+            pixels,
+            metadata.width as usize,
+            metadata.height as usize,
+            rotation_degrees,
+        ))
+    }
+
+    // Maps a direction to coordinates in [0, 1)^2 on the image, as (column, row).
+    fn direction_to_uv(&self, direction: Vec3) -> (f64, f64) {
+        let direction = direction.into_unit_vec();
+        let theta = direction.y().clamp(-1.0, 1.0).acos();
+        let phi = (direction.z().atan2(direction.x()) - self.rotation).rem_euclid(2.0 * PI);
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let theta = v * PI;
+        let phi = u * 2.0 * PI + self.rotation;
+
+        Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        )
+    }
+
+    fn lookup(&self, u: f64, v: f64) -> Color {
+        let column = ((u * self.width as f64) as usize).min(self.width - 1);
+        let row = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[row * self.width + column]
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let (u, v) = self.direction_to_uv(direction);
+        self.lookup(u, v)
+    }
+
+    fn light(&self, scene_radius: f64) -> Option<Arc<dyn Hit>> {
+        let mut light = self.clone();
+        light.scene_radius = scene_radius;
+        Some(Arc::new(light))
+    }
+}
+
+impl Hit for EnvironmentMap {
+    // The map is infinitely far away, so rays never hit it.
+    fn hit(&self, _ray: Ray, _t_min: f64, _t_max: f64) -> Option<HitRecord> {
+        None
+    }
+
+    fn bounding_box(&self, _t_min: f64, _t_max: f64) -> Option<BoundingBox> {
+        None
+    }
+
+    fn pdf_value(&self, _hit_point: Point3, direction: Vec3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        // Converts the density over the image to a density over solid angle.
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn random(&self, _origin: Vec3) -> Vec3 {
+        let ((u, v), _) = self
+            .distribution
+            .sample_continuous(sampler::random(), sampler::random());
+        self.uv_to_direction(u, v)
+    }
+
+    // Power that the map delivers to the sphere around the scene, as if it was a disk facing each
+    // direction.
+    fn emitted_power(&self) -> f64 {
+        let pixel_solid_angle = 2.0 * PI * PI / (self.width * self.height) as f64;
+        let mut power = 0.0;
+        for row in 0..self.height {
+            let sin_theta = (PI * (row as f64 + 0.5) / self.height as f64).sin();
+            for column in 0..self.width {
+                power += self.pixels[row * self.width + column].luminance()
+                    * sin_theta
+                    * pixel_solid_angle;
+            }
+        }
+
+        power * PI * self.scene_radius * self.scene_radius
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn uv_direction_round_trip() {
+        let pixels = vec![Color::new(1.0, 1.0, 1.0); 8 * 4];
+        let environment_map = EnvironmentMap::new(pixels, 8, 4, 30.0);
+
+        let direction = environment_map.uv_to_direction(0.3, 0.6);
+        let (u, v) = environment_map.direction_to_uv(direction);
+        assert!((u - 0.3).abs() < 1e-9);
+        assert!((v - 0.6).abs() < 1e-9);
+
+        // A constant map is sampled uniformly over the sphere.
+        let pdf = environment_map.pdf_value(Point3::default(), direction);
+        assert!((pdf - 1.0 / (4.0 * PI)).abs() < 0.1 / (4.0 * PI));
+    }
+}
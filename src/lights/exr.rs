@@ -0,0 +1,119 @@
+use std::io::{self, BufRead, Seek};
+
+use exr::prelude::{read, ReadChannels, ReadLayers, ReadSpecificChannel};
+
+use crate::linalg::Color;
+
+// Pixels of the image as they are decoded, which only grow with the data found in the file, so that
+// a corrupt header can't claim more memory than the file could fill.
+struct Pixels {
+    colors: Vec<Color>,
+    width: usize,
+    height: usize,
+    // Whether the colors come from the R, G and B channels instead of the luminance channel Y.
+    rgb: bool,
+}
+
+// Reads the first layer of an OpenEXR image, which is what environment maps are usually stored in,
+// from either its R, G and B channels or its luminance channel Y.
+pub fn read_exr(reader: impl BufRead + Seek) -> io::Result<(Vec<Color>, usize, usize)> {
+    let image = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .specific_channels()
+        .optional("R", 0.0)
+        .optional("G", 0.0)
+        .optional("B", 0.0)
+        .optional("Y", 0.0)
+        .collect_pixels(
+            |resolution, (r, g, b, y)| {
+                let rgb = r.is_some() && g.is_some() && b.is_some();
+                Pixels {
+                    colors: Vec::new(),
+                    width: resolution.width(),
+                    height: if rgb || y.is_some() {
+                        resolution.height()
+                    } else {
+                        0
+                    },
+                    rgb,
+                }
+            },
+            |pixels, position, (r, g, b, y): (f32, f32, f32, f32)| {
+                let idx = position.y() * pixels.width + position.x();
+                if idx >= pixels.colors.len() {
+                    pixels.colors.resize(idx + 1, Color::default());
+                }
+                pixels.colors[idx] = if pixels.rgb {
+                    Color::new(r as f64, g as f64, b as f64)
+                } else {
+                    Color::from_scalar(y as f64)
+                };
+            },
+        )
+        .first_valid_layer()
+        .all_attributes()
+        .from_buffered(reader)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+
+    let mut pixels = image.layer_data.channel_data.pixels;
+    if pixels.height == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The OpenEXR image has neither R, G and B channels nor a Y channel",
+        ));
+    }
+    pixels
+        .colors
+        .resize(pixels.width * pixels.height, Color::default());
+
+    Ok((pixels.colors, pixels.width, pixels.height))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    // Image written by the OpenEXR library, which comes with the test suite of CPython.
+    const PYTHON_EXR: &[u8] = include_bytes!("../../sample_images/python.exr");
+
+    #[test]
+    fn decodes_an_image_written_by_openexr() {
+        let (pixels, width, height) = read_exr(Cursor::new(PYTHON_EXR)).unwrap();
+
+        assert_eq!((width, height), (16, 16));
+        assert_eq!(pixels.len(), 16 * 16);
+        assert_eq!(pixels[0], Color::default());
+        // A pixel of the blue snake and one of the yellow snake.
+        assert_eq!(
+            pixels[6 * 16 + 2],
+            Color::new(0.2744140625, 0.501953125, 0.6904296875)
+        );
+        assert_eq!(
+            pixels[6 * 16 + 11],
+            Color::new(0.92529296875, 0.8076171875, 0.2705078125)
+        );
+    }
+
+    #[test]
+    fn corrupt_images_are_errors() {
+        assert!(read_exr(Cursor::new(&PYTHON_EXR[..PYTHON_EXR.len() / 2])).is_err());
+        assert!(read_exr(Cursor::new(&b"not an image"[..])).is_err());
+
+        // A data window far larger than the file has pixels for, whose corners follow the size of
+        // the attribute.
+        let mut huge = PYTHON_EXR.to_vec();
+        let attribute = b"dataWindow\0box2i\0";
+        let max_corner = huge
+            .windows(attribute.len())
+            .position(|window| window == attribute)
+            .unwrap()
+            + attribute.len()
+            + 12;
+        huge[max_corner..max_corner + 4].copy_from_slice(&99_999i32.to_le_bytes());
+        huge[max_corner + 4..max_corner + 8].copy_from_slice(&99_999i32.to_le_bytes());
+        assert!(read_exr(Cursor::new(huge)).is_err());
+    }
+}
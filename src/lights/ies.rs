@@ -0,0 +1,305 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Angular distribution of the light given off by a fixture, from an IES LM-63 photometric file.
+// Vertical angles are measured from the axis of the fixture, which points down the emitter's normal,
+// and horizontal angles go around that axis. Only type C photometry is supported, which is what
+// nearly all architectural fixtures use.
+pub struct IesProfile {
+    // Angles in degrees, in increasing order.
+    vertical_angles: Vec<f64>,
+    horizontal_angles: Vec<f64>,
+    // Candela values for each horizontal angle, relative to the brightest value in the file.
+    intensities: Vec<Vec<f64>>,
+}
+
+// Smallest cosine with the axis that the radiance of an emitter is divided by, which keeps the
+// directions that graze the surface from blowing it up.
+const MIN_COS: f64 = 0.01;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Numbers that follow the TILT line, read in order.
+struct Numbers(std::vec::IntoIter<f64>);
+
+impl Numbers {
+    fn next(&mut self) -> io::Result<f64> {
+        self.0
+            .next()
+            .ok_or_else(|| invalid_data("IES file ended before all its data was read"))
+    }
+
+    // Number of items that are each made of the given number of values, which must all still be
+    // left to read.
+    fn count(&mut self, values_per_item: usize) -> io::Result<usize> {
+        let count = self.next()?;
+        if count < 0.0
+            || count.fract() != 0.0
+            || count * values_per_item as f64 > self.0.len() as f64
+        {
+            return Err(invalid_data(
+                "IES file has a count that doesn't match its data",
+            ));
+        }
+
+        Ok(count as usize)
+    }
+}
+
+// Index of the interval of the angles that contains the given angle, along with how far into the
+// interval it lies.
+fn find_interval(angles: &[f64], angle: f64) -> Option<(usize, f64)> {
+    if angles.len() == 1 {
+        return if angle == angles[0] {
+            Some((0, 0.0))
+        } else {
+            None
+        };
+    }
+    if angle < angles[0] || angle > angles[angles.len() - 1] {
+        return None;
+    }
+
+    let idx = angles
+        .windows(2)
+        .position(|pair| angle <= pair[1])
+        .unwrap_or(angles.len() - 2);
+    let width = angles[idx + 1] - angles[idx];
+    let t = if width > 0.0 {
+        (angle - angles[idx]) / width
+    } else {
+        0.0
+    };
+
+    Some((idx, t))
+}
+
+impl IesProfile {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        // Everything before the TILT line is free-form keywords describing the fixture.
+        let mut lines = text.lines();
+        let tilt = lines
+            .by_ref()
+            .find_map(|line| line.trim().strip_prefix("TILT="))
+            .ok_or_else(|| invalid_data("IES file is missing its TILT line"))?
+            .trim()
+            .to_string();
+
+        let rest: Vec<&str> = lines.collect();
+        let mut numbers = Vec::new();
+        for token in rest
+            .iter()
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+        {
+            let number: f64 = token
+                .parse()
+                .map_err(|_| invalid_data("IES file contains a value that is not a number"))?;
+            numbers.push(number);
+        }
+        let mut numbers = Numbers(numbers.into_iter());
+
+        // Tilt data only matters for lamps whose output changes with their orientation, which is
+        // ignored.
+        if tilt == "INCLUDE" {
+            numbers.next()?;
+            let pair_count = numbers.count(2)?;
+            for _ in 0..(2 * pair_count) {
+                numbers.next()?;
+            }
+        }
+
+        let _lamp_count = numbers.next()?;
+        let _lumens_per_lamp = numbers.next()?;
+        let candela_multiplier = numbers.next()?;
+        let vertical_count = numbers.count(1)?;
+        let horizontal_count = numbers.count(1)?;
+        if numbers.next()? != 1.0 {
+            return Err(invalid_data("IES file does not use type C photometry"));
+        }
+        let _units_type = numbers.next()?;
+        let _width = numbers.next()?;
+        let _length = numbers.next()?;
+        let _height = numbers.next()?;
+        let _ballast_factor = numbers.next()?;
+        let _future_use = numbers.next()?;
+        let _input_watts = numbers.next()?;
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(invalid_data("IES file has no candela values"));
+        }
+
+        let mut vertical_angles = Vec::with_capacity(vertical_count);
+        for _ in 0..vertical_count {
+            vertical_angles.push(numbers.next()?);
+        }
+        let mut horizontal_angles = Vec::with_capacity(horizontal_count);
+        for _ in 0..horizontal_count {
+            horizontal_angles.push(numbers.next()?);
+        }
+        if vertical_count.saturating_mul(horizontal_count) > numbers.0.len() {
+            return Err(invalid_data("IES file ended before all its data was read"));
+        }
+
+        let mut intensities = Vec::with_capacity(horizontal_count);
+        let mut max_intensity: f64 = 0.0;
+        for _ in 0..horizontal_count {
+            let mut row = Vec::with_capacity(vertical_count);
+            for _ in 0..vertical_count {
+                let intensity = (numbers.next()? * candela_multiplier).max(0.0);
+                max_intensity = max_intensity.max(intensity);
+                row.push(intensity);
+            }
+            intensities.push(row);
+        }
+
+        if max_intensity > 0.0 {
+            for row in &mut intensities {
+                for intensity in row.iter_mut() {
+                    *intensity /= max_intensity;
+                }
+            }
+        }
+
+        Ok(Self {
+            vertical_angles,
+            horizontal_angles,
+            intensities,
+        })
+    }
+
+    // Intensity relative to the brightest direction of the fixture, for angles in degrees.
+    pub fn intensity(&self, vertical_degrees: f64, horizontal_degrees: f64) -> f64 {
+        let (vertical_idx, vertical_t) =
+            match find_interval(&self.vertical_angles, vertical_degrees) {
+                Some(interval) => interval,
+                None => return 0.0,
+            };
+        let row_value = |row: &Vec<f64>| {
+            if vertical_t > 0.0 {
+                row[vertical_idx] * (1.0 - vertical_t) + row[vertical_idx + 1] * vertical_t
+            } else {
+                row[vertical_idx]
+            }
+        };
+
+        // The last horizontal angle tells which symmetry the fixture has, and only the part of the
+        // distribution that is not implied by it is stored.
+        let last_angle = self.horizontal_angles[self.horizontal_angles.len() - 1];
+        if self.horizontal_angles.len() == 1 || last_angle == 0.0 {
+            return row_value(&self.intensities[0]);
+        }
+        let mut horizontal = horizontal_degrees.rem_euclid(360.0);
+        if last_angle <= 180.0 && horizontal > 180.0 {
+            horizontal = 360.0 - horizontal;
+        }
+        if last_angle <= 90.0 && horizontal > 90.0 {
+            horizontal = 180.0 - horizontal;
+        }
+
+        match find_interval(&self.horizontal_angles, horizontal) {
+            Some((horizontal_idx, horizontal_t)) if horizontal_t > 0.0 => {
+                row_value(&self.intensities[horizontal_idx]) * (1.0 - horizontal_t)
+                    + row_value(&self.intensities[horizontal_idx + 1]) * horizontal_t
+            }
+            Some((horizontal_idx, _)) => row_value(&self.intensities[horizontal_idx]),
+            None => 0.0,
+        }
+    }
+
+    // Factor that the radiance of an emitter is scaled by in a direction, for angles in degrees, so
+    // that the intensity it gives off follows the profile instead of falling off with the cosine.
+    pub fn radiance_scale(&self, vertical_degrees: f64, horizontal_degrees: f64) -> f64 {
+        let cos_theta = vertical_degrees.to_radians().cos().max(MIN_COS);
+        self.intensity(vertical_degrees, horizontal_degrees) / cos_theta
+    }
+
+    // Cosine-weighted average of the radiance scale over the hemisphere around the axis, which
+    // scales the power given off by an emitter that uses the profile.
+    pub fn hemisphere_average(&self) -> f64 {
+        let vertical_steps = 90;
+        let horizontal_steps = 72;
+        let mut weighted_sum = 0.0;
+        let mut weight_sum = 0.0;
+        for vertical_step in 0..vertical_steps {
+            let theta = (vertical_step as f64 + 0.5) / vertical_steps as f64 * 90.0;
+            let weight = theta.to_radians().cos() * theta.to_radians().sin();
+            for horizontal_step in 0..horizontal_steps {
+                let phi = (horizontal_step as f64 + 0.5) / horizontal_steps as f64 * 360.0;
+                weighted_sum += weight * self.radiance_scale(theta, phi);
+                weight_sum += weight;
+            }
+        }
+
+        weighted_sum / weight_sum
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IesProfile;
+
+    const PROFILE: &str = "IESNA:LM-63-2002
+[TEST] test fixture
+TILT=NONE
+1 1000 1 3 3 1 2 0 0 0
+1 1 100
+0 45 90
+0 90 180
+100 50 0
+200 100 0
+100 50 0
+";
+
+    #[test]
+    fn parse_and_interpolate() {
+        let profile = IesProfile::parse(PROFILE).unwrap();
+
+        assert_eq!(profile.intensity(0.0, 90.0), 1.0);
+        assert_eq!(profile.intensity(0.0, 0.0), 0.5);
+        assert_eq!(profile.intensity(22.5, 0.0), 0.375);
+        assert_eq!(profile.intensity(0.0, 45.0), 0.75);
+        // Bilateral symmetry mirrors the angles past 180 degrees.
+        assert_eq!(profile.intensity(0.0, 270.0), 1.0);
+        assert_eq!(profile.intensity(120.0, 0.0), 0.0);
+
+        assert!(IesProfile::parse("IESNA:LM-63-2002\n1 2 3").is_err());
+    }
+
+    #[test]
+    fn reject_bad_counts_and_photometry() {
+        let huge_count = PROFILE.replace("1 1000 1 3 3", "1 1000 1 1e12 3");
+        assert!(IesProfile::parse(&huge_count).is_err());
+        let negative_count = PROFILE.replace("1 1000 1 3 3", "1 1000 1 3 -3");
+        assert!(IesProfile::parse(&negative_count).is_err());
+        let huge_tilt = PROFILE.replace("TILT=NONE", "TILT=INCLUDE\n1 1e15");
+        assert!(IesProfile::parse(&huge_tilt).is_err());
+        let type_a = PROFILE.replace("1 1000 1 3 3 1", "1 1000 1 3 3 3");
+        assert!(IesProfile::parse(&type_a).is_err());
+    }
+
+    // A profile that falls off with the cosine gives off light like a plain diffuse emitter.
+    #[test]
+    fn cosine_profile_is_lambertian() {
+        let profile = IesProfile::parse(
+            "TILT=NONE
+1 1000 1 10 1 1 2 0 0 0
+1 1 100
+0 10 20 30 40 50 60 70 80 90
+0
+1 0.9848078 0.9396926 0.8660254 0.7660444 0.6427876 0.5 0.3420201 0.1736482 0
+",
+        )
+        .unwrap();
+
+        assert!((profile.radiance_scale(0.0, 0.0) - 1.0).abs() < 1e-6);
+        assert!((profile.radiance_scale(60.0, 0.0) - 1.0).abs() < 1e-6);
+        assert!((profile.hemisphere_average() - 1.0).abs() < 0.02);
+    }
+}
@@ -0,0 +1,310 @@
+use std::sync::Arc;
+
+use crate::hittables::{b_box_compare, BoundingBox, EmissionCone, Hit, HitRecord};
+use crate::linalg::{Point3, Ray, Vec3};
+use crate::sampler;
+
+// What a node of the light BVH knows about the lights below it.
+#[derive(Clone)]
+struct LightBounds {
+    bounding_box: BoundingBox,
+    power: f64,
+    cone: EmissionCone,
+}
+
+impl LightBounds {
+    fn combine(&self, other: &LightBounds) -> Self {
+        Self {
+            bounding_box: self.bounding_box.combine(&other.bounding_box),
+            power: self.power + other.power,
+            cone: self.cone.combine(&other.cone),
+        }
+    }
+
+    // Conservative estimate of how much light the lights in the bounds contribute to a point, which
+    // is their power over the squared distance, reduced by how far the point is from the directions
+    // that the lights emit in.
+    fn importance(&self, point: Point3) -> f64 {
+        if self.power <= 0.0 {
+            return 0.0;
+        }
+
+        let min_corner = self.bounding_box.min_corner();
+        let max_corner = self.bounding_box.max_corner();
+        let center = (min_corner + max_corner).mul_scalar(0.5);
+        let radius = (max_corner - min_corner).length() / 2.0;
+
+        let to_point = point - center;
+        let distance_sq = to_point.length_sq().max(radius * radius);
+        let distance = to_point.length();
+        if distance <= radius {
+            return self.power / distance_sq;
+        }
+
+        // Angle between the axis of the cone and the point, minus the spread of the normals and the
+        // angle that the bounds subtend as seen from the point.
+        let theta = self
+            .cone
+            .axis()
+            .dot(to_point.div_scalar(distance))
+            .clamp(-1.0, 1.0)
+            .acos();
+        let theta_u = (radius / distance).asin();
+        let theta_prime = (theta - self.cone.theta_o() - theta_u).max(0.0);
+        if theta_prime >= self.cone.theta_e() {
+            return 0.0;
+        }
+
+        self.power * theta_prime.cos().max(0.0) / distance_sq
+    }
+}
+
+enum LightBvhNode {
+    Leaf {
+        light: Arc<dyn Hit>,
+        bounds: LightBounds,
+    },
+    Interior {
+        children: Box<[LightBvhNode; 2]>,
+        bounds: LightBounds,
+    },
+}
+
+impl LightBvhNode {
+    // Splits the lights at the median of the longest axis of their bounds, like `BvhNode` does for
+    // the objects in the scene.
+    fn new(lights: &mut [Arc<dyn Hit>]) -> Self {
+        if lights.len() == 1 {
+            let light = lights[0].clone();
+            let bounds = LightBounds {
+                bounding_box: light.bounding_box(0.0, 1.0).unwrap(),
+                power: light.emitted_power(),
+                cone: light.emission_cone(),
+            };
+            return LightBvhNode::Leaf { light, bounds };
+        }
+
+        let mut bounding_box = lights[0].bounding_box(0.0, 1.0).unwrap();
+        for light in lights.iter() {
+            bounding_box = bounding_box.combine(&light.bounding_box(0.0, 1.0).unwrap());
+        }
+        let extent = bounding_box.max_corner() - bounding_box.min_corner();
+        let axis = if extent.x() >= extent.y() && extent.x() >= extent.z() {
+            0
+        } else if extent.y() >= extent.z() {
+            1
+        } else {
+            2
+        };
+
+        lights.sort_unstable_by(|light_a, light_b| {
+            b_box_compare(light_a.clone(), light_b.clone(), axis)
+        });
+        let mid = lights.len() / 2;
+        let (lights_left, lights_right) = lights.split_at_mut(mid);
+        let left = Self::new(lights_left);
+        let right = Self::new(lights_right);
+        let bounds = left.bounds().combine(right.bounds());
+
+        LightBvhNode::Interior {
+            children: Box::new([left, right]),
+            bounds,
+        }
+    }
+
+    fn bounds(&self) -> &LightBounds {
+        match self {
+            LightBvhNode::Leaf { bounds, .. } => bounds,
+            LightBvhNode::Interior { bounds, .. } => bounds,
+        }
+    }
+
+    // Probability of picking the left child of a node when sampling the lights for a point.
+    fn left_probability(children: &[LightBvhNode; 2], point: Point3) -> f64 {
+        let left_importance = children[0].bounds().importance(point);
+        let right_importance = children[1].bounds().importance(point);
+        if left_importance + right_importance > 0.0 {
+            left_importance / (left_importance + right_importance)
+        } else {
+            0.5
+        }
+    }
+
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        match self {
+            LightBvhNode::Leaf { light, .. } => light.hit(ray, t_min, t_max),
+            LightBvhNode::Interior { children, bounds } => {
+                bounds.bounding_box.hit(ray, t_min, t_max)?;
+                let hit_left = children[0].hit(ray, t_min, t_max);
+                let t_max = hit_left.as_ref().map_or(t_max, |hit_record| hit_record.t);
+                children[1].hit(ray, t_min, t_max).or(hit_left)
+            }
+        }
+    }
+
+    // Only the subtrees whose bounds the direction goes through can contain lights with a non-zero
+    // PDF for it.
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        match self {
+            LightBvhNode::Leaf { light, .. } => light.pdf_value(hit_point, direction),
+            LightBvhNode::Interior { children, bounds } => {
+                if bounds
+                    .bounding_box
+                    .hit(Ray::new(hit_point, direction), 0.001, f64::INFINITY)
+                    .is_none()
+                {
+                    return 0.0;
+                }
+
+                let left_probability = Self::left_probability(children, hit_point);
+                let mut pdf = 0.0;
+                if left_probability > 0.0 {
+                    pdf += left_probability * children[0].pdf_value(hit_point, direction);
+                }
+                if left_probability < 1.0 {
+                    pdf += (1.0 - left_probability) * children[1].pdf_value(hit_point, direction);
+                }
+
+                pdf
+            }
+        }
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        match self {
+            LightBvhNode::Leaf { light, .. } => light.random(origin),
+            LightBvhNode::Interior { children, .. } => {
+                if sampler::random() < Self::left_probability(children, origin) {
+                    children[0].random(origin)
+                } else {
+                    children[1].random(origin)
+                }
+            }
+        }
+    }
+}
+
+// Bounding volume hierarchy over lights that picks a light in proportion to an estimate of how much
+// it contributes to the point being shaded, instead of only by its power. Only lights with a
+// bounding box can be placed in it.
+pub struct LightBvh {
+    root: Option<LightBvhNode>,
+}
+
+impl LightBvh {
+    pub fn new(mut lights: Vec<Arc<dyn Hit>>) -> Self {
+        let root = if lights.is_empty() {
+            None
+        } else {
+            Some(LightBvhNode::new(&mut lights))
+        };
+
+        Self { root }
+    }
+}
+
+impl Hit for LightBvh {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.root.as_ref()?.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _t_min: f64, _t_max: f64) -> Option<BoundingBox> {
+        Some(self.root.as_ref()?.bounds().bounding_box.clone())
+    }
+
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        match &self.root {
+            Some(root) => root.pdf_value(hit_point, direction),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        match &self.root {
+            Some(root) => root.random(origin),
+            None => Vec3::new(1.0, 0.0, 0.0),
+        }
+    }
+
+    fn emitted_power(&self) -> f64 {
+        self.root.as_ref().map_or(0.0, |root| root.bounds().power)
+    }
+
+    fn emission_cone(&self) -> EmissionCone {
+        self.root
+            .as_ref()
+            .map_or(EmissionCone::omnidirectional(), |root| root.bounds().cone)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::hittables::{FlipFace, XZRect};
+    use crate::linalg::Color;
+    use crate::materials::DiffuseLight;
+
+    #[test]
+    fn pdf_matches_sampling() {
+        // A row of small ceiling lights facing down.
+        let mut lights: Vec<Arc<dyn Hit>> = Vec::new();
+        for idx in 0..8 {
+            let x = idx as f64 * 2.0;
+            let light = Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0)));
+            lights.push(Arc::new(FlipFace::new(Arc::new(XZRect::new(
+                x,
+                x + 0.5,
+                0.0,
+                0.5,
+                3.0,
+                light,
+            )))));
+        }
+        let light_bvh = LightBvh::new(lights);
+
+        let point = Point3::new(0.25, 0.0, 0.25);
+
+        // Nothing above the lights is lit by them.
+        let above = Point3::new(7.25, 100.0, 0.25);
+        assert_eq!(
+            LightBounds::importance(light_bvh.root.as_ref().unwrap().bounds(), above),
+            0.0
+        );
+
+        // Every sampled direction must have a non-zero PDF.
+        let sample_count = 20000;
+        let mut near_count = 0;
+        let mut far_count = 0;
+        for _ in 0..sample_count {
+            let direction = light_bvh.random(point);
+            assert!(light_bvh.pdf_value(point, direction) > 0.0);
+            let slope = direction.x() / direction.y();
+            if slope < 0.1 {
+                near_count += 1;
+            } else if slope > 4.0 {
+                far_count += 1;
+            }
+        }
+
+        // The PDF must integrate to 1. The lights cover small solid angles, so the sphere of
+        // directions is stratified to keep the estimate from being too noisy.
+        let (z_steps, phi_steps) = (300, 600);
+        let mut integral = 0.0;
+        for z_step in 0..z_steps {
+            for phi_step in 0..phi_steps {
+                let z = -1.0 + 2.0 * (z_step as f64 + sampler::random()) / z_steps as f64;
+                let phi = 2.0 * PI * (phi_step as f64 + sampler::random()) / phi_steps as f64;
+                let r = (1.0 - z * z).sqrt();
+                let direction = Vec3::new(r * phi.cos(), z, r * phi.sin());
+                integral += light_bvh.pdf_value(point, direction);
+            }
+        }
+        integral *= 4.0 * PI / (z_steps * phi_steps) as f64;
+        assert!((integral - 1.0).abs() < 0.05);
+
+        // Lights closer to the point are picked more often, but the others are still picked.
+        assert!(near_count > far_count && far_count > 0);
+    }
+}
@@ -0,0 +1,245 @@
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
+use crate::hittables::{collect_emitters, BoundingBox, Hit, HitRecord, World};
+use crate::linalg::{Point3, Ray, Vec3};
+use crate::pdfs::Distribution1D;
+use crate::sampler;
+
+use super::LightBvh;
+
+// How a light is picked from the list whenever a single light has to be importance sampled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightSelection {
+    Uniform,
+    Power,
+    // Lights with a bounding box are placed in a light BVH, which picks them by their estimated
+    // contribution to the point being shaded. The BVH and the remaining lights are picked by power.
+    Bvh,
+}
+
+impl FromStr for LightSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(LightSelection::Uniform),
+            "power" => Ok(LightSelection::Power),
+            "bvh" => Ok(LightSelection::Bvh),
+            _ => Err(format!(
+                "Invalid light selection '{}'. Use one of [uniform, power, bvh]",
+                s
+            )),
+        }
+    }
+}
+
+// Lights that a light list picks from, which only differ from its lights when they are placed in a
+// light BVH, and the distribution it picks them with.
+#[derive(Clone)]
+struct LightSampling {
+    lights: Vec<Arc<dyn Hit>>,
+    distribution: Distribution1D,
+}
+
+// Collection of emitters that can be importance sampled as a single light. Each sample picks one
+// light and samples it, so the PDF of a direction is the average of the PDFs of all the lights,
+// weighted by how likely each light is to be picked.
+#[derive(Clone)]
+pub struct LightList {
+    lights: Vec<Arc<dyn Hit>>,
+    selection: LightSelection,
+    // Built the first time the list is sampled after its lights change, so that adding many lights
+    // one by one doesn't rebuild it each time.
+    sampling: OnceLock<LightSampling>,
+}
+
+impl LightList {
+    pub fn new(selection: LightSelection) -> Self {
+        Self {
+            lights: Vec::new(),
+            selection,
+            sampling: OnceLock::new(),
+        }
+    }
+
+    // Collects every emissive object in the world as a light, down to the individual triangles of
+    // emissive meshes.
+    pub fn from_world(world: &World, selection: LightSelection) -> Self {
+        let mut emitters = Vec::new();
+        for object in world.objects() {
+            collect_emitters(object, &mut emitters);
+        }
+
+        let mut light_list = Self::new(selection);
+        light_list.extend(emitters);
+        light_list
+    }
+
+    pub fn add(&mut self, light: Arc<dyn Hit>) {
+        self.lights.push(light);
+        self.sampling = OnceLock::new();
+    }
+
+    pub fn extend(&mut self, lights: impl IntoIterator<Item = Arc<dyn Hit>>) {
+        self.lights.extend(lights);
+        self.sampling = OnceLock::new();
+    }
+
+    pub fn remove(&mut self, light: &Arc<dyn Hit>) {
+        self.lights.retain(|other| !Arc::ptr_eq(other, light));
+        self.sampling = OnceLock::new();
+    }
+
+    fn sampling(&self) -> &LightSampling {
+        self.sampling.get_or_init(|| self.build_sampling())
+    }
+
+    fn build_sampling(&self) -> LightSampling {
+        let lights: Vec<Arc<dyn Hit>> = if self.selection == LightSelection::Bvh {
+            let (bounded_lights, mut unbounded_lights): (Vec<_>, Vec<_>) = self
+                .lights
+                .iter()
+                .cloned()
+                .partition(|light| light.bounding_box(0.0, 1.0).is_some());
+            if !bounded_lights.is_empty() {
+                unbounded_lights.push(Arc::new(LightBvh::new(bounded_lights)));
+            }
+            unbounded_lights
+        } else {
+            self.lights.clone()
+        };
+
+        let weights: Vec<f64> = match self.selection {
+            LightSelection::Uniform => vec![1.0; lights.len()],
+            LightSelection::Power | LightSelection::Bvh => {
+                lights.iter().map(|light| light.emitted_power()).collect()
+            }
+        };
+
+        LightSampling {
+            distribution: Distribution1D::new(&weights),
+            lights,
+        }
+    }
+
+    pub fn lights(&self) -> &Vec<Arc<dyn Hit>> {
+        &self.lights
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn selection(&self) -> LightSelection {
+        self.selection
+    }
+
+    pub fn set_selection(&mut self, selection: LightSelection) {
+        self.selection = selection;
+        self.sampling = OnceLock::new();
+    }
+}
+
+impl Default for LightList {
+    fn default() -> Self {
+        Self::new(LightSelection::Power)
+    }
+}
+
+impl Hit for LightList {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut ret: Option<HitRecord> = None;
+
+        let mut closest_so_far = t_max;
+        for light in &self.lights {
+            if let Some(hit_record) = light.hit(ray, t_min, closest_so_far) {
+                closest_so_far = hit_record.t;
+                ret = Some(hit_record);
+            }
+        }
+
+        ret
+    }
+
+    fn bounding_box(&self, t_min: f64, t_max: f64) -> Option<BoundingBox> {
+        let mut bounding_box: Option<BoundingBox> = None;
+        for light in &self.lights {
+            let local_bounding_box = light.bounding_box(t_min, t_max)?;
+            bounding_box = Some(match bounding_box {
+                Some(bounding_box) => bounding_box.combine(&local_bounding_box),
+                None => local_bounding_box,
+            });
+        }
+
+        bounding_box
+    }
+
+    fn pdf_value(&self, hit_point: Point3, direction: Vec3) -> f64 {
+        let sampling = self.sampling();
+        sampling
+            .lights
+            .iter()
+            .enumerate()
+            .map(|(idx, light)| {
+                sampling.distribution.discrete_pdf(idx) * light.pdf_value(hit_point, direction)
+            })
+            .sum()
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        let sampling = self.sampling();
+        if sampling.lights.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let (idx, _) = sampling.distribution.sample_discrete(sampler::random());
+        sampling.lights[idx].random(origin)
+    }
+
+    fn emitted_power(&self) -> f64 {
+        self.lights.iter().map(|light| light.emitted_power()).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittables::{FlipFace, XZRect};
+    use crate::linalg::Color;
+    use crate::materials::DiffuseLight;
+
+    fn ceiling_light(x: f64) -> Arc<dyn Hit> {
+        let light = Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0)));
+        Arc::new(FlipFace::new(Arc::new(XZRect::new(
+            x,
+            x + 1.0,
+            0.0,
+            1.0,
+            3.0,
+            light,
+        ))))
+    }
+
+    #[test]
+    fn sampling_follows_added_and_removed_lights() {
+        let point = Point3::new(0.5, 0.0, 0.5);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+
+        let first = ceiling_light(0.0);
+        let second = ceiling_light(10.0);
+        let mut light_list = LightList::new(LightSelection::Power);
+        light_list.add(first.clone());
+        let alone = light_list.pdf_value(point, up);
+        assert!((alone - first.pdf_value(point, up)).abs() < 1e-12);
+
+        // The second light is picked half of the time once it is added after sampling.
+        light_list.add(second.clone());
+        assert!((light_list.pdf_value(point, up) - alone / 2.0).abs() < 1e-12);
+
+        light_list.remove(&second);
+        assert!((light_list.pdf_value(point, up) - alone).abs() < 1e-12);
+
+        light_list.extend(vec![second, ceiling_light(20.0)]);
+        assert!((light_list.pdf_value(point, up) - alone / 3.0).abs() < 1e-12);
+    }
+}
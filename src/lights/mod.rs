@@ -0,0 +1,142 @@
+pub use directional::DirectionalLight;
+pub use environment_map::EnvironmentMap;
+pub use ies::IesProfile;
+pub use light_bvh::LightBvh;
+pub use light_list::{LightList, LightSelection};
+pub use point::PointLight;
+pub use sky::PhysicalSky;
+pub use spot::SpotLight;
+
+use std::sync::Arc;
+
+use crate::hittables::Hit;
+use crate::linalg::{Color, Point3, Vec3};
+
+pub mod directional;
+pub mod environment_map;
+mod exr;
+pub mod ies;
+pub mod light_bvh;
+pub mod light_list;
+pub mod point;
+pub mod sky;
+pub mod spot;
+
+// Light arriving at a point from a delta light, along with where it comes from.
+pub struct LightSample {
+    // Unit vector from the point towards the light.
+    pub direction: Vec3,
+    // Distance to the light, which is infinite for lights that are infinitely far away.
+    pub distance: f64,
+    pub radiance: Color,
+}
+
+impl LightSample {
+    pub fn new(direction: Vec3, distance: f64, radiance: Color) -> Self {
+        Self {
+            direction,
+            distance,
+            radiance,
+        }
+    }
+}
+
+// Lights with no surface, which rays can never hit. The only way to account for them is to sample
+// them directly from every point that they could illuminate and trace a shadow ray towards them.
+pub trait DeltaLight: Send + Sync {
+    fn sample(&self, point: Point3) -> Option<LightSample>;
+
+    fn light_links(&self) -> LightGroups {
+        LightGroups::all()
+    }
+}
+
+// Delta light that only illuminates the objects in some of the light-linking groups.
+pub struct LinkedLight {
+    light: Arc<dyn DeltaLight>,
+    light_links: LightGroups,
+}
+
+impl LinkedLight {
+    pub fn new(light: Arc<dyn DeltaLight>, light_links: LightGroups) -> Self {
+        Self { light, light_links }
+    }
+}
+
+impl DeltaLight for LinkedLight {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        self.light.sample(point)
+    }
+
+    fn light_links(&self) -> LightGroups {
+        self.light_links
+    }
+}
+
+// Set of up to 64 light-linking groups. Objects belong to groups, and lights only illuminate the
+// objects in the groups that they are linked to. By default objects are in group 0 and lights are
+// linked to every group.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LightGroups(u64);
+
+impl LightGroups {
+    pub fn all() -> Self {
+        Self(u64::MAX)
+    }
+
+    pub fn none() -> Self {
+        Self(0)
+    }
+
+    pub fn from_groups(groups: &[u32]) -> Self {
+        Self(groups.iter().fold(0, |mask, &group| {
+            assert!(group < 64, "light-linking group {} is not below 64", group);
+            mask | (1 << group)
+        }))
+    }
+
+    pub fn intersects(self, other: LightGroups) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Default for LightGroups {
+    fn default() -> Self {
+        Self::from_groups(&[0])
+    }
+}
+
+// Radiance arriving from infinitely far away along the rays that escape the scene.
+pub trait Background: Send + Sync {
+    fn radiance(&self, direction: Vec3) -> Color;
+
+    // Light that importance samples the background, for a scene that fits in a sphere of the given
+    // radius. Backgrounds without one are only found by rays that happen to escape.
+    fn light(&self, _scene_radius: f64) -> Option<Arc<dyn Hit>> {
+        None
+    }
+}
+
+impl Background for Color {
+    fn radiance(&self, _direction: Vec3) -> Color {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn groups_are_bits_of_the_mask() {
+        let groups = LightGroups::from_groups(&[0, 63]);
+        assert!(groups.intersects(LightGroups::from_groups(&[63])));
+        assert!(!groups.intersects(LightGroups::from_groups(&[1, 62])));
+    }
+
+    #[test]
+    #[should_panic]
+    fn groups_past_63_are_rejected() {
+        LightGroups::from_groups(&[64]);
+    }
+}
@@ -0,0 +1,35 @@
+use crate::linalg::{Color, Point3};
+
+use super::{DeltaLight, LightSample};
+
+// Light emitted equally in all directions from a single point.
+pub struct PointLight {
+    position: Point3,
+    intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl DeltaLight for PointLight {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance_sq = to_light.length_sq();
+        if distance_sq == 0.0 {
+            return None;
+        }
+
+        let distance = distance_sq.sqrt();
+        Some(LightSample::new(
+            to_light.div_scalar(distance),
+            distance,
+            self.intensity.div_scalar(distance_sq),
+        ))
+    }
+}
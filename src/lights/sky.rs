@@ -0,0 +1,256 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::hittables::{BoundingBox, Hit, HitRecord};
+use crate::linalg::{Color, Point3, Ray, Vec3, ONB};
+
+use super::Background;
+
+// Radiance is expressed in units of 10 kcd/m^2, in which the roughly 100 klx that the sun delivers
+// before going through the atmosphere become an irradiance of 10.
+const LUMINANCE_UNIT: f64 = 10.0;
+const SUN_IRRADIANCE: f64 = 10.0;
+
+// Clear sky following the analytic model by Preetham et al., "A Practical Analytic Model for
+// Daylight", together with a sun disk of finite size. The sun is at the given elevation above the
+// horizon and azimuth around the y axis, starting at +x and turning towards +z. Turbidity goes
+// from about 2 for a very clear sky to about 10 for a hazy one.
+pub struct PhysicalSky {
+    sun_direction: Vec3,
+    turbidity: f64,
+    cos_sun_radius: f64,
+    sun_radiance: Color,
+    // Luminance and chromaticity (Y, x, y) at the zenith.
+    zenith: [f64; 3],
+    // Coefficients of the Perez distribution for each of Y, x and y.
+    perez: [[f64; 5]; 3],
+}
+
+impl PhysicalSky {
+    pub fn new(sun_elevation_degrees: f64, sun_azimuth_degrees: f64, turbidity: f64) -> Self {
+        let elevation = sun_elevation_degrees.to_radians();
+        let azimuth = sun_azimuth_degrees.to_radians();
+        let sun_direction = Vec3::new(
+            elevation.cos() * azimuth.cos(),
+            elevation.sin(),
+            elevation.cos() * azimuth.sin(),
+        );
+
+        let t = turbidity;
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let mut sky = Self {
+            sun_direction,
+            turbidity,
+            cos_sun_radius: 0.0,
+            sun_radiance: Color::default(),
+            zenith: [0.0; 3],
+            perez,
+        };
+        sky.zenith = sky.zenith_values();
+        sky.with_sun_angular_diameter(0.53)
+    }
+
+    pub fn with_sun_angular_diameter(mut self, degrees: f64) -> Self {
+        self.cos_sun_radius = (degrees.to_radians() / 2.0).cos();
+        let solid_angle = 2.0 * PI * (1.0 - self.cos_sun_radius);
+        self.sun_radiance = self
+            .sun_transmittance()
+            .mul_scalar(SUN_IRRADIANCE / solid_angle);
+        self
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    // Angle between the sun and the zenith, kept above the horizon where the model is valid.
+    fn sun_theta(&self) -> f64 {
+        self.sun_direction.y().clamp(0.0, 1.0).acos()
+    }
+
+    fn zenith_values(&self) -> [f64; 3] {
+        let t = self.turbidity;
+        let theta = self.sun_theta();
+        let theta_2 = theta * theta;
+        let theta_3 = theta_2 * theta;
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let x = t * t * (0.00166 * theta_3 - 0.00375 * theta_2 + 0.00209 * theta)
+            + t * (-0.02903 * theta_3 + 0.06377 * theta_2 - 0.03202 * theta + 0.00394)
+            + (0.11693 * theta_3 - 0.21196 * theta_2 + 0.06052 * theta + 0.25886);
+        let y = t * t * (0.00275 * theta_3 - 0.00610 * theta_2 + 0.00317 * theta)
+            + t * (-0.04214 * theta_3 + 0.08970 * theta_2 - 0.04153 * theta + 0.00516)
+            + (0.15346 * theta_3 - 0.26756 * theta_2 + 0.06670 * theta + 0.26688);
+
+        [luminance.max(0.0), x, y]
+    }
+
+    fn perez(coefficients: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = *coefficients;
+        (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+
+    fn sky_radiance(&self, direction: Vec3) -> Color {
+        // The model only describes the sky above the horizon, so directions below it take the color
+        // of the horizon, which is also kept from grazing angles where the Perez distribution blows
+        // up.
+        let cos_theta = direction.y().max(0.01);
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+        let sun_theta = self.sun_theta();
+
+        let mut values = [0.0; 3];
+        for (idx, value) in values.iter_mut().enumerate() {
+            let coefficients = &self.perez[idx];
+            *value = self.zenith[idx] * Self::perez(coefficients, cos_theta, gamma)
+                / Self::perez(coefficients, 1.0, sun_theta);
+        }
+
+        let [luminance, x, y] = values;
+        xyy_to_rgb(luminance / LUMINANCE_UNIT, x, y)
+    }
+
+    // Fraction of each channel that makes it through the atmosphere from the sun, due to Rayleigh
+    // scattering by the air and Mie scattering by aerosols.
+    fn sun_transmittance(&self) -> Color {
+        let theta_degrees = self.sun_theta().to_degrees();
+        let air_mass = 1.0
+            / (self.sun_theta().cos() + 0.15 * (93.885 - theta_degrees).max(0.001).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        // Wavelengths in micrometers that stand in for the red, green and blue channels.
+        let transmittance = |wavelength: f64| {
+            let rayleigh = (-0.008735 * wavelength.powf(-4.08) * air_mass).exp();
+            let mie = (-beta * wavelength.powf(-1.3) * air_mass).exp();
+            rayleigh * mie
+        };
+
+        Color::new(
+            transmittance(0.65),
+            transmittance(0.57),
+            transmittance(0.475),
+        )
+    }
+
+    fn sun_is_visible(&self) -> bool {
+        self.sun_direction.y() > 0.0
+    }
+}
+
+fn xyy_to_rgb(luminance: f64, x: f64, y: f64) -> Color {
+    if y <= 0.0 {
+        return Color::default();
+    }
+
+    let cie_x = x / y * luminance;
+    let cie_z = (1.0 - x - y) / y * luminance;
+    Color::new(
+        (3.2406 * cie_x - 1.5372 * luminance - 0.4986 * cie_z).max(0.0),
+        (-0.9689 * cie_x + 1.8758 * luminance + 0.0415 * cie_z).max(0.0),
+        (0.0557 * cie_x - 0.2040 * luminance + 1.0570 * cie_z).max(0.0),
+    )
+}
+
+impl Background for PhysicalSky {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let direction = direction.into_unit_vec();
+        let mut radiance = self.sky_radiance(direction);
+        if self.sun_is_visible() && direction.dot(self.sun_direction) >= self.cos_sun_radius {
+            radiance += self.sun_radiance;
+        }
+
+        radiance
+    }
+
+    // Only the sun is importance sampled, since the rest of the sky is smooth enough to be found by
+    // sampling the materials.
+    fn light(&self, scene_radius: f64) -> Option<Arc<dyn Hit>> {
+        if !self.sun_is_visible() {
+            return None;
+        }
+
+        Some(Arc::new(SunLight {
+            direction: self.sun_direction,
+            cos_radius: self.cos_sun_radius,
+            radiance: self.sun_radiance,
+            scene_radius,
+        }))
+    }
+}
+
+// Disk of the sun, seen from anywhere in the scene as the same cone of directions.
+struct SunLight {
+    direction: Vec3,
+    cos_radius: f64,
+    radiance: Color,
+    scene_radius: f64,
+}
+
+impl Hit for SunLight {
+    // The sun is infinitely far away, so rays never hit it.
+    fn hit(&self, _ray: Ray, _t_min: f64, _t_max: f64) -> Option<HitRecord> {
+        None
+    }
+
+    fn bounding_box(&self, _t_min: f64, _t_max: f64) -> Option<BoundingBox> {
+        None
+    }
+
+    fn pdf_value(&self, _hit_point: Point3, direction: Vec3) -> f64 {
+        if direction.into_unit_vec().dot(self.direction) < self.cos_radius {
+            return 0.0;
+        }
+
+        1.0 / (2.0 * PI * (1.0 - self.cos_radius))
+    }
+
+    fn random(&self, _origin: Vec3) -> Vec3 {
+        let sin_radius = (1.0 - self.cos_radius * self.cos_radius).sqrt();
+        let onb = ONB::new(self.direction);
+        onb.local(Vec3::random_to_sphere(sin_radius, 1.0))
+    }
+
+    fn emitted_power(&self) -> f64 {
+        let solid_angle = 2.0 * PI * (1.0 - self.cos_radius);
+        self.radiance.luminance() * solid_angle * PI * self.scene_radius * self.scene_radius
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sky_is_brighter_near_the_sun() {
+        let sky = PhysicalSky::new(30.0, 0.0, 3.0);
+        let near_sun = sky.radiance(Vec3::new(1.0, 0.7, 0.1));
+        let away_from_sun = sky.radiance(Vec3::new(-1.0, 0.7, 0.0));
+        assert!(near_sun.luminance() > away_from_sun.luminance());
+
+        // A clear sky away from the sun is blue.
+        assert!(away_from_sun.b() > away_from_sun.r());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::linalg::{Color, Point3, Vec3};
+
+use super::{DeltaLight, LightSample};
+
+// Point light restricted to a cone. The intensity is full inside the inner cone and fades out to
+// nothing at the edge of the outer cone, with the falloff exponent shaping the transition.
+pub struct SpotLight {
+    position: Point3,
+    direction: Vec3,
+    intensity: Color,
+    cos_inner: f64,
+    cos_outer: f64,
+    falloff: f64,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point3,
+        direction: Vec3,
+        intensity: Color,
+        inner_angle_degrees: f64,
+        outer_angle_degrees: f64,
+        falloff: f64,
+    ) -> Self {
+        let outer_angle_degrees = outer_angle_degrees.max(inner_angle_degrees);
+        Self {
+            position,
+            direction: direction.into_unit_vec(),
+            intensity,
+            cos_inner: inner_angle_degrees.to_radians().cos(),
+            cos_outer: outer_angle_degrees.to_radians().cos(),
+            falloff,
+        }
+    }
+
+    fn cone_attenuation(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        let smooth_t = t * t * (3.0 - 2.0 * t);
+        smooth_t.powf(self.falloff)
+    }
+}
+
+impl DeltaLight for SpotLight {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance_sq = to_light.length_sq();
+        if distance_sq == 0.0 {
+            return None;
+        }
+
+        let distance = distance_sq.sqrt();
+        let direction = to_light.div_scalar(distance);
+        let attenuation = self.cone_attenuation(-direction.dot(self.direction));
+        if attenuation <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample::new(
+            direction,
+            distance,
+            self.intensity.mul_scalar(attenuation / distance_sq),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cone_attenuation() {
+        let light = SpotLight::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+            20.0,
+            40.0,
+            1.0,
+        );
+
+        let inside = light.sample(Point3::new(0.1, -1.0, 0.0)).unwrap();
+        assert!((inside.radiance.r() - 1.0 / 1.01).abs() < 1e-9);
+
+        let penumbra = light.sample(Point3::new(0.5, -1.0, 0.0)).unwrap();
+        assert!(penumbra.radiance.r() > 0.0 && penumbra.radiance.r() < 1.0 / 1.25);
+
+        assert!(light.sample(Point3::new(1.0, -1.0, 0.0)).is_none());
+        assert!(light.sample(Point3::new(0.0, 1.0, 0.0)).is_none());
+    }
+}
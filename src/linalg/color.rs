@@ -1,7 +1,7 @@
 use std::fmt;
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign};
 
-use rand::Rng;
+use crate::sampler;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Color {
@@ -64,14 +64,72 @@ impl Color {
     }
 
     pub fn random_from_bounds(low: f64, high: f64) -> Self {
-        let mut rng = rand::thread_rng();
         Self {
-            r: rng.gen_range(low, high),
-            g: rng.gen_range(low, high),
-            b: rng.gen_range(low, high),
+            r: sampler::random_range(low, high),
+            g: sampler::random_range(low, high),
+            b: sampler::random_range(low, high),
         }
     }
 
+    // Linear RGB color of the light given off by a blackbody at the given temperature, scaled to a
+    // luminance of 1 so that it only sets the hue of a light and not its brightness.
+    pub fn from_blackbody(kelvin: f64) -> Self {
+        const PLANCK: f64 = 6.626_070_15e-34;
+        const LIGHT_SPEED: f64 = 2.997_924_58e8;
+        const BOLTZMANN: f64 = 1.380_649e-23;
+
+        // Piecewise Gaussian fit of the CIE 1931 color matching functions by Wyman et al., "Simple
+        // Analytic Approximations to the CIE XYZ Color Matching Functions".
+        let lobe = |wavelength: f64, mean: f64, sigma_low: f64, sigma_high: f64| {
+            let sigma = if wavelength < mean {
+                sigma_low
+            } else {
+                sigma_high
+            };
+            (-0.5 * ((wavelength - mean) / sigma).powi(2)).exp()
+        };
+
+        let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+        for step in 0..=80 {
+            let wavelength_nm = 380.0 + 5.0 * step as f64;
+            let wavelength = wavelength_nm * 1e-9;
+            let radiance = 2.0 * PLANCK * LIGHT_SPEED * LIGHT_SPEED
+                / (wavelength.powi(5)
+                    * ((PLANCK * LIGHT_SPEED / (wavelength * BOLTZMANN * kelvin)).exp() - 1.0));
+
+            x += radiance
+                * (1.056 * lobe(wavelength_nm, 599.8, 37.9, 31.0)
+                    + 0.362 * lobe(wavelength_nm, 442.0, 16.0, 26.7)
+                    - 0.065 * lobe(wavelength_nm, 501.1, 20.4, 26.2));
+            y += radiance
+                * (0.821 * lobe(wavelength_nm, 568.8, 46.9, 40.5)
+                    + 0.286 * lobe(wavelength_nm, 530.9, 16.3, 31.1));
+            z += radiance
+                * (1.217 * lobe(wavelength_nm, 437.0, 11.8, 36.0)
+                    + 0.681 * lobe(wavelength_nm, 459.0, 26.0, 13.8));
+        }
+
+        let color = Self::new(
+            (3.2406 * x - 1.5372 * y - 0.4986 * z).max(0.0),
+            (-0.9689 * x + 1.8758 * y + 0.0415 * z).max(0.0),
+            (0.0557 * x - 0.2040 * y + 1.0570 * z).max(0.0),
+        );
+        let luminance = color.luminance();
+        if luminance > 0.0 && luminance.is_finite() {
+            color.div_scalar(luminance)
+        } else {
+            Self::default()
+        }
+    }
+
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub fn max_component(self) -> f64 {
+        self.r.max(self.g).max(self.b)
+    }
+
     pub fn into_rgb8(self) -> [u8; 3] {
         let ir = (255.99 * self.r) as u8;
         let ig = (255.99 * self.g) as u8;
@@ -384,6 +442,19 @@ mod test {
         );
     }
 
+    #[test]
+    fn color_from_blackbody() {
+        let warm = Color::from_blackbody(2700.0);
+        assert!((warm.luminance() - 1.0).abs() < 1e-9);
+        assert!(warm.r > warm.g && warm.g > warm.b);
+
+        let daylight = Color::from_blackbody(6500.0);
+        assert!((daylight.r - daylight.b).abs() < 0.15);
+
+        let cold = Color::from_blackbody(12000.0);
+        assert!(cold.b > cold.r);
+    }
+
     #[test]
     fn color_div() {
         let lhs = Color::new(0.0, 1.0, 2.0);
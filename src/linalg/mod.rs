@@ -1,6 +1,6 @@
 pub use self::color::Color;
 pub use self::onb::ONB;
-pub use self::ray::Ray;
+pub use self::ray::{Ray, RayType};
 pub use self::vec3::{Point3, Vec3};
 
 pub mod color;
@@ -1,14 +1,33 @@
 use super::{Point3, Vec3};
 
+// What a ray is being traced for, so that objects can choose which rays they are visible to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RayType {
+    Camera,
+    Shadow,
+    Diffuse,
+    Specular,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
+    ray_type: RayType,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            ray_type: RayType::Camera,
+        }
+    }
+
+    pub fn with_type(mut self, ray_type: RayType) -> Self {
+        self.ray_type = ray_type;
+        self
     }
 
     pub fn origin(&self) -> &Point3 {
@@ -19,7 +38,11 @@ impl Ray {
         &self.direction
     }
 
+    pub fn ray_type(&self) -> RayType {
+        self.ray_type
+    }
+
     pub fn at(&self, ray_parameter: f64) -> Point3 {
-        self.origin + self.direction.clone().mul_scalar(ray_parameter)
+        self.origin + self.direction.mul_scalar(ray_parameter)
     }
 }
@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign};
 
-use rand::Rng;
+use crate::sampler;
 
 use super::Color;
 
@@ -32,9 +32,12 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_disk() -> Self {
-        let mut rng = rand::thread_rng();
         loop {
-            let vec = Vec3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), 0.0);
+            let vec = Vec3::new(
+                sampler::random_range(-1.0, 1.0),
+                sampler::random_range(-1.0, 1.0),
+                0.0,
+            );
             if vec.length_sq() >= 1.0 {
                 continue;
             }
@@ -53,9 +56,8 @@ impl Vec3 {
     }
 
     pub fn random_unit_vector() -> Self {
-        let mut rng = rand::thread_rng();
-        let a: f64 = rng.gen_range(0.0, std::f64::consts::PI);
-        let z: f64 = rng.gen_range(-1.0, 1.0);
+        let a: f64 = sampler::random_range(0.0, 2.0 * std::f64::consts::PI);
+        let z: f64 = sampler::random_range(-1.0, 1.0);
         let r = (1.0 - z.powi(2)).sqrt();
 
         Self {
@@ -66,9 +68,8 @@ impl Vec3 {
     }
 
     pub fn random_cosine_dir() -> Self {
-        let mut rng = rand::thread_rng();
-        let r1: f64 = rng.gen_range(0.0, 1.0);
-        let r2: f64 = rng.gen_range(0.0, 1.0);
+        let r1: f64 = sampler::random();
+        let r2: f64 = sampler::random();
         let z = (1.0 - r2).sqrt();
 
         let phi = 2.0 * std::f64::consts::PI * r1;
@@ -78,6 +79,20 @@ impl Vec3 {
         Self { x, y, z }
     }
 
+    // Random direction towards a sphere of the given radius, whose center lies at the given squared
+    // distance along the z axis.
+    pub fn random_to_sphere(radius: f64, distance_sq: f64) -> Self {
+        let r1: f64 = sampler::random();
+        let r2: f64 = sampler::random();
+        let z = 1.0 + r2 * ((1.0 - radius * radius / distance_sq).sqrt() - 1.0);
+
+        let phi = 2.0 * std::f64::consts::PI * r1;
+        let x = phi.cos() * (1.0 - z * z).sqrt();
+        let y = phi.sin() * (1.0 - z * z).sqrt();
+
+        Self { x, y, z }
+    }
+
     pub fn x(self) -> f64 {
         self.x
     }
@@ -163,11 +178,10 @@ impl Vec3 {
     }
 
     pub fn random_from_bounds(low: f64, high: f64) -> Self {
-        let mut rng = rand::thread_rng();
         Self {
-            x: rng.gen_range(low, high),
-            y: rng.gen_range(low, high),
-            z: rng.gen_range(low, high),
+            x: sampler::random_range(low, high),
+            y: sampler::random_range(low, high),
+            z: sampler::random_range(low, high),
         }
     }
 
@@ -484,7 +498,7 @@ mod test {
     #[test]
     fn vec3_length() {
         let lhs = Vec3::new(1.0, 2.0, 3.0);
-        let length_squared = 14.0 as f64;
+        let length_squared = 14.0_f64;
         assert_eq!(lhs.length(), length_squared.sqrt());
     }
 
@@ -518,7 +532,7 @@ mod test {
     #[test]
     fn vec3_into_unit_vec() {
         let lhs = Vec3::new(1.0, 2.0, 3.0);
-        let len = 14.0 as f64;
+        let len = 14.0_f64;
         assert_eq!(
             lhs.into_unit_vec(),
             Vec3 {
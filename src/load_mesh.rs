@@ -1,34 +1,128 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 use std::sync::Arc;
 
-use obj::{load_obj, Obj};
+use obj::raw::material::{parse_mtl, MtlColor};
+use obj::raw::object::{parse_obj, Polygon, RawObj};
 
 use crate::hittables::{BvhNode, Triangle, World};
-use crate::linalg::Point3;
-use crate::materials::Material;
+use crate::linalg::{Color, Point3};
+use crate::materials::{DiffuseLight, Material};
+
+// Emissive materials (those with a non-black `Ke` color) from the .mtl files referenced by a mesh,
+// keyed by their name. Material libraries that are missing or fail to parse are ignored, since they
+// only affect which faces of the mesh emit light.
+fn load_emissive_materials(mesh_path: &Path, model: &RawObj) -> HashMap<String, Arc<dyn Material>> {
+    let mut emissive_materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+    let mesh_dir = mesh_path.parent().unwrap_or_else(|| Path::new("."));
+    for library in &model.material_libraries {
+        let input = match File::open(mesh_dir.join(library)) {
+            Ok(file) => BufReader::new(file),
+            Err(_) => continue,
+        };
+        let materials = match parse_mtl(input) {
+            Ok(raw_mtl) => raw_mtl.materials,
+            Err(_) => continue,
+        };
+
+        for (name, material) in materials {
+            if let Some(MtlColor::Rgb(r, g, b)) = material.emissive {
+                let color = Color::new(r.into(), g.into(), b.into());
+                if color != Color::default() {
+                    emissive_materials.insert(name, Arc::new(DiffuseLight::new(color)));
+                }
+            }
+        }
+    }
+
+    emissive_materials
+}
 
 pub fn load_mesh(mesh_path: &Path, material: Arc<dyn Material>) -> BvhNode {
+    load_triangles(mesh_path, material, false)
+}
+
+// Loads a mesh whose triangles can also be hit from behind, for closed meshes that rays travel
+// inside of, such as the boundary of a `Subsurface` medium.
+pub fn load_closed_mesh(mesh_path: &Path, material: Arc<dyn Material>) -> BvhNode {
+    load_triangles(mesh_path, material, true)
+}
+
+fn load_triangles(mesh_path: &Path, material: Arc<dyn Material>, double_sided: bool) -> BvhNode {
     let input =
         BufReader::new(File::open(mesh_path).expect("Path to specified .obj file is invalid"));
-    let model: Obj = load_obj(input).expect("Failed to parse specified .obj file");
+    let model: RawObj = parse_obj(input).expect("Failed to parse specified .obj file");
+
+    // Faces use the given material, unless they were assigned an emissive one in the mesh's .mtl files.
+    let emissive_materials = load_emissive_materials(mesh_path, &model);
+    let mut face_materials = vec![material; model.polygons.len()];
+    for (name, group) in &model.meshes {
+        if let Some(emissive_material) = emissive_materials.get(name) {
+            for range in &group.polygons {
+                for face_material in &mut face_materials[range.start..range.end] {
+                    *face_material = emissive_material.clone();
+                }
+            }
+        }
+    }
 
     let mut hittable_list = World::default();
 
-    let tri_num = model.indices.len() / 3;
-    for i in 0..tri_num {
-        let mut tri_verts = [Point3::default(), Point3::default(), Point3::default()];
-        for j in 0..3 {
-            let index: usize = model.indices[3 * i + j].into();
-            let model_vert = model.vertices[index];
-            tri_verts[j] = Point3::new(
-                model_vert.position[0].into(),
-                model_vert.position[1].into(),
-                model_vert.position[2].into(),
-            );
+    for (polygon, face_material) in model.polygons.iter().zip(face_materials) {
+        // Position indices of the vertices, along with their texture coordinate indices if the
+        // polygon has any.
+        let indices: Vec<(usize, Option<usize>)> = match polygon {
+            Polygon::P(vertices) => vertices.iter().map(|vertex| (*vertex, None)).collect(),
+            Polygon::PT(vertices) => vertices
+                .iter()
+                .map(|vertex| (vertex.0, Some(vertex.1)))
+                .collect(),
+            Polygon::PN(vertices) => vertices.iter().map(|vertex| (vertex.0, None)).collect(),
+            Polygon::PTN(vertices) => vertices
+                .iter()
+                .map(|vertex| (vertex.0, Some(vertex.1)))
+                .collect(),
+        };
+        let position = |index: usize| {
+            let model_vert = model.positions[index];
+            Point3::new(
+                model_vert.0.into(),
+                model_vert.1.into(),
+                model_vert.2.into(),
+            )
+        };
+        let texture_coords = |index: Option<usize>| {
+            index
+                .and_then(|index| model.tex_coords.get(index))
+                .map(|tex_coord| {
+                    let u: f64 = tex_coord.0.into();
+                    let v: f64 = tex_coord.1.into();
+                    (u, v)
+                })
+        };
+
+        // Split polygons with more than three vertices into a fan of triangles.
+        for idx in 1..indices.len().saturating_sub(1) {
+            let tri_indices = [indices[0], indices[idx], indices[idx + 1]];
+            let tri_verts = [
+                position(tri_indices[0].0),
+                position(tri_indices[1].0),
+                position(tri_indices[2].0),
+            ];
+            let mut triangle =
+                Triangle::new(tri_verts, face_material.clone()).with_double_sided(double_sided);
+            if let (Some(uv0), Some(uv1), Some(uv2)) = (
+                texture_coords(tri_indices[0].1),
+                texture_coords(tri_indices[1].1),
+                texture_coords(tri_indices[2].1),
+            ) {
+                triangle = triangle.with_texture_coords([uv0, uv1, uv2]);
+            }
+            hittable_list.add(Arc::new(triangle));
         }
-        hittable_list.add(Arc::new(Triangle::new(tri_verts, material.clone())));
     }
 
     // Place all the triangles inside a BVH structure to make the ray-triangle intersection checks faster.
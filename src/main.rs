@@ -3,10 +3,17 @@ extern crate tracey;
 use std::path::Path;
 use std::sync::Arc;
 
-use tracey::hittables::World;
+use tracey::hittables::{Subsurface, World};
+use tracey::integrators::{
+    AmbientOcclusion, BidirectionalPathTracer, DebugIntegrator, DirectLighting, GuidedPathTracer,
+    Integrator, IntegratorKind, IrradianceCaching, LightTracer, MetropolisLightTransport,
+    PathTracer, PhotonMapper, Whitted,
+};
+use tracey::lights::{EnvironmentMap, LightSelection, PhysicalSky};
 use tracey::linalg::Color;
-use tracey::load_mesh::load_mesh;
-use tracey::materials::Lambertian;
+use tracey::load_mesh::{load_closed_mesh, load_mesh};
+use tracey::materials::{Dielectric, Lambertian};
+use tracey::pdfs::MisHeuristic;
 use tracey::render::render;
 use tracey::scene::{get_cornell_box_scene, get_random_spheres_scene, Scene};
 
@@ -30,12 +37,161 @@ struct TraceyArgs {
     /// Sample scene number
     #[structopt(long, conflicts_with = "mesh-path")]
     sample_scene: Option<u32>,
+
+    /// Output an RGBA image, where the background, holdouts and shadow catchers are transparent
+    #[structopt(long)]
+    alpha: bool,
+
+    /// Path to an equirectangular .hdr or .exr image used to light the scene from all directions
+    #[structopt(long, conflicts_with = "sky")]
+    environment: Option<String>,
+
+    /// Rotation of the environment map around the vertical axis in degrees
+    #[structopt(long, default_value = "0")]
+    environment_rotation: f64,
+
+    /// Light the scene with a physically based sky and sun
+    #[structopt(long)]
+    sky: bool,
+
+    /// Elevation of the sun above the horizon in degrees
+    #[structopt(long, default_value = "45")]
+    sun_elevation: f64,
+
+    /// Azimuth of the sun around the vertical axis in degrees
+    #[structopt(long, default_value = "0")]
+    sun_azimuth: f64,
+
+    /// Haziness of the sky, from about 2 for a clear sky to 10 for a hazy one
+    #[structopt(long, default_value = "3")]
+    turbidity: f64,
+
+    /// How lights are picked for light sampling: uniform, power or bvh
+    #[structopt(long, default_value = "power")]
+    light_selection: LightSelection,
+
+    /// How light and material samples are weighted against each other: balance or power
+    #[structopt(long, default_value = "power")]
+    mis_heuristic: MisHeuristic,
+
+    /// Fraction of the scattered rays aimed at the lights, between 0 and 1, instead of letting each material choose
+    #[structopt(long)]
+    light_sampling_weight: Option<f64>,
+
+    /// Rendering algorithm: path, bdpt, photon, mlt, light, irradiance, ao, direct, whitted, or normals, uvs, depth and materials for debugging
+    #[structopt(long, default_value = "path")]
+    integrator: IntegratorKind,
+
+    /// Guide the paths of the path integrator with the light that previous passes found arriving at each region of the scene
+    #[structopt(long)]
+    path_guiding: bool,
+
+    /// Number of passes of the guided path integrator, each guided by what the previous one learned
+    #[structopt(long, default_value = "8")]
+    guiding_passes: u32,
+
+    /// Maximum number of rays traced for each path
+    #[structopt(long, default_value = "50")]
+    max_depth: i32,
+
+    /// Number of rays traced for each path before it can be terminated early with Russian roulette
+    #[structopt(long, default_value = "5")]
+    rr_depth: i32,
+
+    /// Number of photons shot from the lights in each pass of the photon integrator
+    #[structopt(long, default_value = "100000")]
+    photons: usize,
+
+    /// Number of passes of the photon integrator, each with new photons and a smaller lookup radius
+    #[structopt(long, default_value = "16")]
+    photon_passes: u32,
+
+    /// Radius of the photon lookups in the first pass, a hundredth of the scene radius by default
+    #[structopt(long)]
+    photon_radius: Option<f64>,
+
+    /// Number of Markov chains of the mlt integrator, which splits the samples of all pixels between them
+    #[structopt(long, default_value = "1000")]
+    mlt_chains: usize,
+
+    /// Probability that the mlt integrator replaces a path with an independent one instead of perturbing it
+    #[structopt(long, default_value = "0.3")]
+    large_step_probability: f64,
+
+    /// Largest error that the irradiance integrator interpolates its cached irradiance with, where lower values are slower and more accurate
+    #[structopt(long, default_value = "0.2")]
+    irradiance_error: f64,
 }
 
 fn main() {
     let args = TraceyArgs::from_args();
-    let scene = scene_from_args(args.mesh_path, args.sample_scene);
-    render(args.width, args.rays, scene);
+    let mut scene = scene_from_args(args.mesh_path.clone(), args.sample_scene);
+    if let Some(environment) = &args.environment {
+        let environment_map =
+            EnvironmentMap::load(Path::new(environment), args.environment_rotation)
+                .unwrap_or_else(|err| panic!("Failed to load the environment map: {}", err));
+        scene.set_background(Arc::new(environment_map));
+    } else if args.sky {
+        let sky = PhysicalSky::new(args.sun_elevation, args.sun_azimuth, args.turbidity);
+        scene.set_background(Arc::new(sky));
+    }
+    Arc::make_mut(&mut scene.lights).set_selection(args.light_selection);
+    scene.light_sampling_weight = args.light_sampling_weight;
+    let mut integrator = integrator_from_args(&args, &scene);
+    render(
+        args.width,
+        args.rays,
+        scene,
+        args.alpha,
+        integrator.as_mut(),
+    );
+}
+
+fn integrator_from_args(args: &TraceyArgs, scene: &Scene) -> Box<dyn Integrator> {
+    let mis_heuristic = args.mis_heuristic;
+    let max_depth = args.max_depth;
+    match args.integrator {
+        IntegratorKind::Path if args.path_guiding => Box::new(
+            GuidedPathTracer::new(scene, mis_heuristic, max_depth)
+                .with_rr_depth(args.rr_depth)
+                .with_passes(args.guiding_passes),
+        ),
+        IntegratorKind::Path => {
+            Box::new(PathTracer::new(mis_heuristic, max_depth).with_rr_depth(args.rr_depth))
+        }
+        IntegratorKind::Bidirectional => Box::new(
+            BidirectionalPathTracer::new(scene, mis_heuristic, max_depth)
+                .with_rr_depth(args.rr_depth),
+        ),
+        IntegratorKind::Photon => {
+            let mut photon_mapper = PhotonMapper::new(scene, mis_heuristic, max_depth)
+                .with_rr_depth(args.rr_depth)
+                .with_photons(args.photons)
+                .with_passes(args.photon_passes);
+            if let Some(photon_radius) = args.photon_radius {
+                photon_mapper = photon_mapper.with_radius(photon_radius);
+            }
+            Box::new(photon_mapper)
+        }
+        IntegratorKind::Metropolis => Box::new(
+            MetropolisLightTransport::new(mis_heuristic, max_depth)
+                .with_rr_depth(args.rr_depth)
+                .with_chains(args.mlt_chains)
+                .with_large_step_probability(args.large_step_probability),
+        ),
+        IntegratorKind::LightTracing => {
+            Box::new(LightTracer::new(scene, mis_heuristic, max_depth).with_rr_depth(args.rr_depth))
+        }
+        IntegratorKind::IrradianceCaching => Box::new(
+            IrradianceCaching::new(scene, mis_heuristic, max_depth)
+                .with_rr_depth(args.rr_depth)
+                .with_error(args.irradiance_error),
+        ),
+        IntegratorKind::AmbientOcclusion => Box::new(AmbientOcclusion::new(4)),
+        IntegratorKind::Direct => Box::new(DirectLighting::new(mis_heuristic, max_depth)),
+        IntegratorKind::Whitted => Box::new(Whitted::new(max_depth)),
+        IntegratorKind::Debug(output) => Box::new(DebugIntegrator::new(output)),
+    }
 }
 
 fn scene_from_args(mesh_path: Option<String>, sample_scene: Option<u32>) -> Scene {
@@ -90,7 +246,20 @@ fn scene_from_args(mesh_path: Option<String>, sample_scene: Option<u32>) -> Scen
                 get_cornell_box_scene(objects)
             }
             2 => get_random_spheres_scene(),
-            _ => panic!("Invalid sample-scene value used. Use one of [0, 1, 2]"),
+            3 => {
+                let glass_mat = Arc::new(Dielectric::new(1.6));
+                let monkey_mesh =
+                    load_closed_mesh(Path::new("./sample_meshes/blender_monkey.obj"), glass_mat);
+
+                // Jade-like subsurface scattering inside the monkey mesh.
+                let jade_monkey =
+                    Subsurface::new(Arc::new(monkey_mesh), 1.6, Color::new(0.55, 0.9, 0.6), 8.0);
+
+                let mut objects = World::default();
+                objects.add(Arc::new(jade_monkey));
+                get_cornell_box_scene(objects)
+            }
+            _ => panic!("Invalid sample-scene value used. Use one of [0, 1, 2, 3]"),
         }
     }
 }
@@ -1,11 +1,6 @@
 use crate::materials::Material;
 
+#[derive(Default)]
 pub struct DefaultMaterial {}
 
 impl Material for DefaultMaterial {}
-
-impl Default for DefaultMaterial {
-    fn default() -> Self {
-        Self {}
-    }
-}
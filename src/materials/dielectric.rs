@@ -1,18 +1,26 @@
-use rand::Rng;
-
 use crate::hittables::HitRecord;
 use crate::linalg::{Color, Ray};
 use crate::materials::Material;
+use crate::sampler;
 
-use super::{reflect, refract, schlick, ScatterRecord};
+use super::{reflect, refract, schlick, ScatterRecord, ThinFilm};
 
 pub struct Dielectric {
     refractive_index: f64,
+    thin_film: Option<ThinFilm>,
 }
 
 impl Dielectric {
     pub fn new(refractive_index: f64) -> Self {
-        Self { refractive_index }
+        Self {
+            refractive_index,
+            thin_film: None,
+        }
+    }
+
+    pub fn with_thin_film(mut self, thin_film: ThinFilm) -> Self {
+        self.thin_film = Some(thin_film);
+        self
     }
 }
 
@@ -28,10 +36,45 @@ impl Material for Dielectric {
 
         let cos_theta = (-unit_direction.dot(hit_record.normal)).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+
+        if let Some(thin_film) = self.thin_film.as_ref() {
+            let (outer_index, inner_index) = if hit_record.front_face {
+                (1.0, self.refractive_index)
+            } else {
+                (self.refractive_index, 1.0)
+            };
+
+            // The film reflects each channel differently, so reflection is chosen with the average
+            // reflectance and the attenuation compensates for the per-channel difference.
+            let reflectance = if refractive_index_ratio * sin_theta > 1.0 {
+                Color::from_scalar(1.0)
+            } else {
+                thin_film.dielectric_reflectance(cos_theta, outer_index, inner_index)
+            };
+            let reflect_prob = (reflectance.r() + reflectance.g() + reflectance.b()) / 3.0;
+
+            let (direction, attenuation) = if sampler::random() < reflect_prob {
+                (
+                    reflect(unit_direction, hit_record.normal),
+                    reflectance.div_scalar(reflect_prob),
+                )
+            } else {
+                (
+                    refract(unit_direction, hit_record.normal, refractive_index_ratio),
+                    (Color::from_scalar(1.0) - reflectance).div_scalar(1.0 - reflect_prob),
+                )
+            };
+
+            return Some(ScatterRecord::new(
+                Some(Ray::new(hit_record.hit_point, direction)),
+                None,
+                attenuation,
+            ));
+        }
+
         let reflect_prob = schlick(cos_theta, refractive_index_ratio);
-        let mut rng = rand::thread_rng();
         let direction =
-            if refractive_index_ratio * sin_theta > 1.0 || rng.gen_range(0.0, 1.0) < reflect_prob {
+            if refractive_index_ratio * sin_theta > 1.0 || sampler::random() < reflect_prob {
                 reflect(unit_direction, hit_record.normal)
             } else {
                 refract(unit_direction, hit_record.normal, refractive_index_ratio)
@@ -1,23 +1,134 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
 use crate::hittables::HitRecord;
-use crate::linalg::Color;
+use crate::lights::IesProfile;
+use crate::linalg::{Color, Ray};
 use crate::materials::Material;
+use crate::textures::Texture;
 
 pub struct DiffuseLight {
-    color: Color,
+    emission: Arc<dyn Texture>,
+    two_sided: bool,
+    ies_profile: Option<Arc<IesProfile>>,
 }
 
 impl DiffuseLight {
     pub fn new(color: Color) -> Self {
-        Self { color }
+        Self::textured(Arc::new(color))
+    }
+
+    // Emitter that gives off the given power from one side of a surface with the given area, in
+    // squared scene units, with a color scaled to a luminance of 1 (e.g. a blackbody color). Shapes
+    // can pass their own area with `area_light`.
+    pub fn from_watts(watts: f64, color: Color, area: f64) -> Self {
+        Self::new(color.mul_scalar(watts / (PI * area)))
+    }
+
+    // Lumens are converted with the peak luminous efficacy of 683 lm/W, since the color has a
+    // luminance of 1.
+    pub fn from_lumens(lumens: f64, color: Color, area: f64) -> Self {
+        Self::from_watts(lumens / 683.0, color, area)
+    }
+
+    // Emitter whose radiance at each point comes from a texture, e.g. a screen or a window.
+    pub fn textured(emission: Arc<dyn Texture>) -> Self {
+        Self {
+            emission,
+            two_sided: false,
+            ies_profile: None,
+        }
+    }
+
+    // Emits from the back face as well as the front face.
+    pub fn two_sided(mut self) -> Self {
+        self.two_sided = true;
+        self
+    }
+
+    // Shapes the emission around the normal of the surface with a photometric profile, so that the
+    // brightest direction of the profile gives off as much light as the texture does head-on.
+    pub fn with_ies_profile(mut self, ies_profile: Arc<IesProfile>) -> Self {
+        self.ies_profile = Some(ies_profile);
+        self
     }
 }
 
 impl Material for DiffuseLight {
-    fn emit(&self, _u: f64, _v: f64, hit_record: &mut HitRecord) -> Color {
-        if hit_record.front_face {
-            self.color
+    fn emit(&self, ray_in: Ray, hit_record: &HitRecord) -> Color {
+        if !hit_record.front_face && !self.two_sided {
+            return Color::default();
+        }
+
+        let color = self
+            .emission
+            .value(hit_record.u, hit_record.v, hit_record.hit_point);
+        if let Some(ies_profile) = &self.ies_profile {
+            // The normal faces the incoming ray, so it is the axis of whichever side is seen.
+            let direction = ray_in.direction().into_unit_vec().mul_scalar(-1.0);
+            let bitangent = hit_record.normal.cross(hit_record.tangent);
+            let vertical = direction.dot(hit_record.normal).clamp(-1.0, 1.0).acos();
+            let horizontal = direction
+                .dot(bitangent)
+                .atan2(direction.dot(hit_record.tangent));
+
+            color.mul_scalar(
+                ies_profile.radiance_scale(vertical.to_degrees(), horizontal.to_degrees()),
+            )
         } else {
-            Color::default()
+            color
+        }
+    }
+
+    fn emits_both_sides(&self) -> bool {
+        self.two_sided
+    }
+
+    fn emitted_radiance(&self) -> Color {
+        let mut radiance = self.emission.average();
+        if let Some(ies_profile) = &self.ies_profile {
+            radiance = radiance.mul_scalar(ies_profile.hemisphere_average());
+        }
+        if self.two_sided {
+            // Both sides give off light, which doubles the power of the surface.
+            radiance = radiance.mul_scalar(2.0);
+        }
+
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittables::{area_light, Hit, Sphere, XYRect, XZRect, YZRect};
+    use crate::linalg::Point3;
+
+    #[test]
+    fn power_does_not_depend_on_area() {
+        let warm = Color::from_blackbody(3000.0);
+        for size in &[1.0, 10.0, 130.0] {
+            let rect = area_light(
+                |light| XZRect::new(0.0, *size, 0.0, *size * 2.0, 0.0, light),
+                |area| DiffuseLight::from_watts(100.0, warm, area),
+            );
+            assert!((rect.emitted_power() - 100.0).abs() < 1e-6);
+
+            let rect = area_light(
+                |light| YZRect::new(0.0, *size, 0.0, *size, 0.0, light),
+                |area| DiffuseLight::from_lumens(683.0, warm, area),
+            );
+            assert!((rect.emitted_power() - 1.0).abs() < 1e-6);
+
+            let sphere = area_light(
+                |light| Sphere::new(Point3::default(), *size, light),
+                |area| DiffuseLight::from_watts(100.0, Color::from_blackbody(5000.0), area),
+            );
+            assert!((sphere.emitted_power() - 100.0).abs() < 1e-6);
+
+            let light = Arc::new(DiffuseLight::from_watts(100.0, warm, size * size));
+            let rect = XYRect::new(0.0, *size, 0.0, *size, 0.0, light);
+            assert!((rect.emitted_power() - 100.0).abs() < 1e-6);
         }
     }
 }
@@ -0,0 +1,81 @@
+use crate::hittables::HitRecord;
+use crate::linalg::{Color, Ray};
+use crate::materials::Material;
+use crate::pdfs::CosinePDF;
+
+use super::ScatterRecord;
+
+use std::sync::Arc;
+
+// Translucent material that scatters light diffusely through the surface, into the hemisphere on
+// the opposite side to the one the incoming ray came from.
+pub struct DiffuseTransmission {
+    albedo: Color,
+}
+
+impl DiffuseTransmission {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for DiffuseTransmission {
+    fn scatter(&self, _ray_in: Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord::new(
+            None,
+            Some(Arc::new(CosinePDF::new(hit_record.normal.mul_scalar(-1.0)))),
+            self.albedo,
+        ))
+    }
+
+    fn scattering_pdf(&self, _ray_in: Ray, ray_scattered: Ray, hit_record: &HitRecord) -> f64 {
+        let cos_theta = -hit_record
+            .normal
+            .dot(ray_scattered.direction().into_unit_vec());
+        if cos_theta < 0.0 {
+            0.0
+        } else {
+            cos_theta / std::f64::consts::PI
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittables::{Hit, Sphere};
+    use crate::linalg::{Point3, Vec3};
+
+    #[test]
+    fn scattering_pdf_covers_the_back_hemisphere() {
+        let material = Arc::new(DiffuseTransmission::new(Color::from_scalar(0.8)));
+        let sphere = Sphere::new(Point3::default(), 1.0, material.clone());
+        let ray_in = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = sphere.hit(ray_in, 0.001, f64::INFINITY).unwrap();
+
+        // Integrate over the sphere of directions with the midpoint rule, in the z coordinate and
+        // the azimuth, which spreads the points evenly over the solid angle.
+        let steps = 500;
+        let mut integral = 0.0;
+        for z_step in 0..steps {
+            for phi_step in 0..steps {
+                let z = -1.0 + 2.0 * (z_step as f64 + 0.5) / steps as f64;
+                let phi = 2.0 * std::f64::consts::PI * (phi_step as f64 + 0.5) / steps as f64;
+                let r = (1.0 - z * z).sqrt();
+                let direction = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+                let pdf = material.scattering_pdf(
+                    ray_in,
+                    Ray::new(hit_record.hit_point, direction),
+                    &hit_record,
+                );
+                if direction.dot(hit_record.normal) > 0.0 {
+                    assert_eq!(pdf, 0.0);
+                }
+                integral += pdf;
+            }
+        }
+        integral *= 4.0 * std::f64::consts::PI / (steps * steps) as f64;
+
+        assert!((integral - 1.0).abs() < 1e-3, "integral {}", integral);
+    }
+}
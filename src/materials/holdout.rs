@@ -0,0 +1,12 @@
+use crate::materials::{Compositing, Material};
+
+// Cuts a fully transparent hole into the image wherever it is seen by the camera, so that the
+// footage the render is composited over shows through.
+#[derive(Default)]
+pub struct Holdout {}
+
+impl Material for Holdout {
+    fn compositing(&self) -> Compositing {
+        Compositing::Holdout
+    }
+}
@@ -0,0 +1,37 @@
+use crate::hittables::HitRecord;
+use crate::linalg::{Color, Ray};
+use crate::materials::Material;
+use crate::pdfs::SpherePDF;
+
+use super::ScatterRecord;
+
+use std::sync::Arc;
+
+// Phase function for participating media that scatters light equally in all directions.
+pub struct Isotropic {
+    albedo: Color,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _ray_in: Ray, _hit_record: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord::new(
+            None,
+            Some(Arc::new(SpherePDF::default())),
+            self.albedo,
+        ))
+    }
+
+    fn scattering_pdf(&self, _ray_in: Ray, _ray_scattered: Ray, _hit_record: &HitRecord) -> f64 {
+        1.0 / (4.0 * std::f64::consts::PI)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
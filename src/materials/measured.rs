@@ -0,0 +1,323 @@
+use std::convert::TryInto;
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::hittables::HitRecord;
+use crate::linalg::{Color, Ray, Vec3, ONB};
+use crate::materials::Material;
+use crate::pdfs::{CosinePDF, Distribution2D, PDF};
+use crate::sampler;
+
+use super::ScatterRecord;
+
+// Resolution of the MERL tables, which are indexed by the half and difference angles of the incoming
+// and outgoing directions. Only half of the difference azimuths are stored, due to reciprocity.
+const THETA_HALF_RES: usize = 90;
+const THETA_DIFF_RES: usize = 90;
+const PHI_DIFF_RES: usize = 180;
+const CHANNEL_SIZE: usize = THETA_HALF_RES * THETA_DIFF_RES * PHI_DIFF_RES;
+const CHANNEL_SCALES: [f64; 3] = [1.0 / 1500.0, 1.15 / 1500.0, 1.66 / 1500.0];
+
+// Resolution of the tables used to importance sample the BRDF. There is one 2D table over the
+// scattered direction's polar and azimuthal angles for each bucket of outgoing polar angles.
+const SAMPLING_THETA_OUT_RES: usize = 32;
+const SAMPLING_THETA_RES: usize = 32;
+const SAMPLING_PHI_RES: usize = 64;
+
+// Isotropic BRDF measured by the Mitsubishi Electric Research Laboratories, loaded from one of their
+// `.binary` files.
+pub struct MeasuredMaterial {
+    brdf: Vec<f64>,
+    sampling_tables: Vec<Arc<Distribution2D>>,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn rotate(v: Vec3, axis: Vec3, angle: f64) -> Vec3 {
+    let (sin_angle, cos_angle) = angle.sin_cos();
+    v.mul_scalar(cos_angle)
+        + axis.mul_scalar(axis.dot(v) * (1.0 - cos_angle))
+        + axis.cross(v).mul_scalar(sin_angle)
+}
+
+// Table index for a pair of directions given in the local frame of the surface.
+fn brdf_index(dir_in: Vec3, dir_out: Vec3) -> usize {
+    let half = (dir_in + dir_out).into_unit_vec();
+    let theta_half = half.z().min(1.0).acos();
+    let phi_half = half.y().atan2(half.x());
+
+    let tmp = rotate(dir_in, Vec3::new(0.0, 0.0, 1.0), -phi_half);
+    let diff = rotate(tmp, Vec3::new(0.0, 1.0, 0.0), -theta_half);
+    let theta_diff = diff.z().clamp(-1.0, 1.0).acos();
+    let mut phi_diff = diff.y().atan2(diff.x());
+    if phi_diff < 0.0 {
+        phi_diff += PI;
+    }
+
+    // The half angle is sampled non-linearly, with more resolution around the specular peak.
+    let theta_half_idx =
+        ((theta_half / (PI / 2.0)) * THETA_HALF_RES as f64 * THETA_HALF_RES as f64).sqrt() as usize;
+    let theta_diff_idx = (theta_diff / (PI / 2.0) * THETA_DIFF_RES as f64) as usize;
+    let phi_diff_idx = (phi_diff / PI * PHI_DIFF_RES as f64) as usize;
+
+    phi_diff_idx.min(PHI_DIFF_RES - 1)
+        + theta_diff_idx.min(THETA_DIFF_RES - 1) * PHI_DIFF_RES
+        + theta_half_idx.min(THETA_HALF_RES - 1) * PHI_DIFF_RES * THETA_DIFF_RES
+}
+
+fn mean(color: Color) -> f64 {
+    (color.r() + color.g() + color.b()) / 3.0
+}
+
+impl MeasuredMaterial {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 12 {
+            return Err(invalid_data("MERL file is too short to contain a header"));
+        }
+
+        // The tables are indexed by half angle, difference angle and difference azimuth, in that
+        // order, so dimensions that only multiply up to the right size would scramble them.
+        let mut dims = [0i32; 3];
+        for (idx, dim) in dims.iter_mut().enumerate() {
+            *dim = i32::from_le_bytes(bytes[4 * idx..4 * idx + 4].try_into().unwrap());
+        }
+        if dims
+            != [
+                THETA_HALF_RES as i32,
+                THETA_DIFF_RES as i32,
+                PHI_DIFF_RES as i32,
+            ]
+        {
+            return Err(invalid_data("MERL file has unexpected table dimensions"));
+        }
+        if bytes.len() != 12 + 3 * CHANNEL_SIZE * 8 {
+            return Err(invalid_data(
+                "MERL file size does not match its table dimensions",
+            ));
+        }
+
+        let brdf = bytes[12..]
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        Ok(Self::from_table(brdf))
+    }
+
+    fn from_table(brdf: Vec<f64>) -> Self {
+        let mut material = Self {
+            brdf,
+            sampling_tables: Vec::new(),
+        };
+
+        for out_idx in 0..SAMPLING_THETA_OUT_RES {
+            let theta_out = (out_idx as f64 + 0.5) / SAMPLING_THETA_OUT_RES as f64 * (PI / 2.0);
+            let dir_out = Vec3::new(theta_out.sin(), 0.0, theta_out.cos());
+
+            let mut func = Vec::with_capacity(SAMPLING_THETA_RES * SAMPLING_PHI_RES);
+            for theta_idx in 0..SAMPLING_THETA_RES {
+                let theta = (theta_idx as f64 + 0.5) / SAMPLING_THETA_RES as f64 * (PI / 2.0);
+                for phi_idx in 0..SAMPLING_PHI_RES {
+                    let phi = (phi_idx as f64 + 0.5) / SAMPLING_PHI_RES as f64 * (2.0 * PI);
+                    let dir_in = Vec3::new(
+                        theta.sin() * phi.cos(),
+                        theta.sin() * phi.sin(),
+                        theta.cos(),
+                    );
+
+                    // The sine accounts for the size of each bucket in solid angle.
+                    func.push(mean(material.eval(dir_in, dir_out)) * theta.cos() * theta.sin());
+                }
+            }
+
+            material
+                .sampling_tables
+                .push(Arc::new(Distribution2D::new(&func, SAMPLING_PHI_RES)));
+        }
+
+        material
+    }
+
+    // Evaluates the BRDF for directions given in the local frame of the surface.
+    fn eval(&self, dir_in: Vec3, dir_out: Vec3) -> Color {
+        if dir_in.z() <= 0.0 || dir_out.z() <= 0.0 {
+            return Color::default();
+        }
+
+        let idx = brdf_index(dir_in, dir_out);
+        let mut color = Color::default();
+        for (channel, scale) in CHANNEL_SCALES.iter().enumerate() {
+            // Negative values mark measurements that are missing from the table.
+            color[channel] = (self.brdf[idx + channel * CHANNEL_SIZE] * scale).max(0.0);
+        }
+
+        color
+    }
+}
+
+impl Material for MeasuredMaterial {
+    fn scatter(&self, ray_in: Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+        let dir_out = ray_in.direction().into_unit_vec().mul_scalar(-1.0);
+        let cos_theta_out = dir_out.dot(hit_record.normal).clamp(0.0, 1.0);
+        let table_idx =
+            ((cos_theta_out.acos() / (PI / 2.0)) * SAMPLING_THETA_OUT_RES as f64) as usize;
+        let table = &self.sampling_tables[table_idx.min(SAMPLING_THETA_OUT_RES - 1)];
+
+        // Fall back to cosine-weighted sampling where the BRDF reflects no light at all.
+        let pdf: Arc<dyn PDF> = if table.integral() > 0.0 {
+            Arc::new(MeasuredPDF::new(table.clone(), hit_record.normal, dir_out))
+        } else {
+            Arc::new(CosinePDF::new(hit_record.normal))
+        };
+
+        Some(ScatterRecord::new(None, Some(pdf), Color::from_scalar(1.0)))
+    }
+
+    fn scattering_pdf(&self, ray_in: Ray, ray_scattered: Ray, hit_record: &HitRecord) -> f64 {
+        mean(self.scattering_color(ray_in, ray_scattered, hit_record, Color::from_scalar(1.0)))
+    }
+
+    fn scattering_color(
+        &self,
+        ray_in: Ray,
+        ray_scattered: Ray,
+        hit_record: &HitRecord,
+        attenuation: Color,
+    ) -> Color {
+        let onb = ONB::new(hit_record.normal);
+        let to_local = |v: Vec3| {
+            let v = v.into_unit_vec();
+            Vec3::new(v.dot(onb.u()), v.dot(onb.v()), v.dot(onb.w()))
+        };
+        let dir_in = to_local(*ray_scattered.direction());
+        let dir_out = to_local(ray_in.direction().mul_scalar(-1.0));
+
+        attenuation * self.eval(dir_in, dir_out).mul_scalar(dir_in.z().max(0.0))
+    }
+
+    // Measured materials are often glossy, and their tabulated sampling follows the lobes closely.
+    fn light_sampling_weight(&self) -> f64 {
+        0.25
+    }
+}
+
+// Samples scattered directions from the tabulated BRDF, in a frame whose x axis is aligned with the
+// projection of the outgoing direction onto the surface.
+struct MeasuredPDF {
+    table: Arc<Distribution2D>,
+    x_axis: Vec3,
+    y_axis: Vec3,
+    normal: Vec3,
+}
+
+impl MeasuredPDF {
+    fn new(table: Arc<Distribution2D>, normal: Vec3, dir_out: Vec3) -> Self {
+        let projected = dir_out - normal.mul_scalar(dir_out.dot(normal));
+        let x_axis = if projected.length_sq() > 1e-12 {
+            projected.into_unit_vec()
+        } else {
+            ONB::new(normal).u()
+        };
+        let y_axis = normal.cross(x_axis);
+
+        Self {
+            table,
+            x_axis,
+            y_axis,
+            normal,
+        }
+    }
+}
+
+impl PDF for MeasuredPDF {
+    fn value(&self, direction: Vec3) -> f64 {
+        let direction = direction.into_unit_vec();
+        let cos_theta = direction.dot(self.normal);
+        let sin_theta = (1.0 - cos_theta.powi(2)).max(0.0).sqrt();
+        if cos_theta <= 0.0 || sin_theta < 1e-8 {
+            return 0.0;
+        }
+
+        let mut phi = direction.dot(self.y_axis).atan2(direction.dot(self.x_axis));
+        if phi < 0.0 {
+            phi += 2.0 * PI;
+        }
+
+        let table_pdf = self
+            .table
+            .pdf(phi / (2.0 * PI), cos_theta.acos() / (PI / 2.0));
+        table_pdf / ((PI / 2.0) * (2.0 * PI) * sin_theta)
+    }
+
+    fn generate(&self) -> Vec3 {
+        let ((column, row), _) = self
+            .table
+            .sample_continuous(sampler::random(), sampler::random());
+        let theta = row * (PI / 2.0);
+        let phi = column * (2.0 * PI);
+
+        self.x_axis.mul_scalar(theta.sin() * phi.cos())
+            + self.y_axis.mul_scalar(theta.sin() * phi.sin())
+            + self.normal.mul_scalar(theta.cos())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{MeasuredMaterial, MeasuredPDF, CHANNEL_SIZE};
+    use crate::linalg::Vec3;
+    use crate::pdfs::PDF;
+
+    #[test]
+    fn measured_material_missing_file_is_an_error() {
+        assert!(MeasuredMaterial::load(Path::new("./does_not_exist.binary")).is_err());
+    }
+
+    #[test]
+    fn measured_material_with_permuted_dimensions_is_an_error() {
+        let path = std::env::temp_dir().join("tracey_permuted_dims.binary");
+        let mut bytes = Vec::new();
+        for dim in &[180i32, 90, 90] {
+            bytes.extend_from_slice(&dim.to_le_bytes());
+        }
+        bytes.resize(12 + 3 * CHANNEL_SIZE * 8, 0);
+        std::fs::write(&path, bytes).unwrap();
+
+        let result = MeasuredMaterial::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn measured_pdf_matches_generated_directions() {
+        // A constant table describes a Lambertian BRDF.
+        let material = MeasuredMaterial::from_table(vec![1.0; 3 * CHANNEL_SIZE]);
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let dir_out = Vec3::new(0.6, 0.0, 0.8);
+        let pdf = MeasuredPDF::new(material.sampling_tables[10].clone(), normal, dir_out);
+
+        // Estimate the integral of the PDF over the hemisphere using uniform hemisphere sampling.
+        let sample_num = 20000;
+        let mut integral = 0.0;
+        for _ in 0..sample_num {
+            let direction = Vec3::random_in_hemisphere(normal);
+            integral += pdf.value(direction) * 2.0 * std::f64::consts::PI;
+        }
+        integral /= sample_num as f64;
+        assert!((integral - 1.0).abs() < 0.05);
+
+        for _ in 0..100 {
+            let direction = pdf.generate();
+            assert!(direction.dot(normal) > 0.0);
+            assert!(pdf.value(direction) > 0.0);
+        }
+    }
+}
@@ -2,11 +2,14 @@ use crate::hittables::HitRecord;
 use crate::linalg::{Color, Ray, Vec3};
 use crate::materials::Material;
 
-use super::{reflect, ScatterRecord};
+use super::{reflect, ScatterRecord, ThinFilm};
 
 pub struct Metal {
     albedo: Color,
     fuzz_factor: f64,
+    // Fuzz applied along the surface tangent and bitangent respectively, for anisotropic metals.
+    anisotropic_fuzz: Option<(f64, f64)>,
+    thin_film: Option<ThinFilm>,
 }
 
 impl Metal {
@@ -14,22 +17,60 @@ impl Metal {
         Self {
             albedo,
             fuzz_factor,
+            anisotropic_fuzz: None,
+            thin_film: None,
+        }
+    }
+
+    // Brushed metal, whose reflections are stretched along the surface tangent when `tangent_fuzz`
+    // is larger than `bitangent_fuzz`.
+    pub fn anisotropic(albedo: Color, tangent_fuzz: f64, bitangent_fuzz: f64) -> Self {
+        Self {
+            albedo,
+            fuzz_factor: tangent_fuzz.min(bitangent_fuzz),
+            anisotropic_fuzz: Some((tangent_fuzz, bitangent_fuzz)),
+            thin_film: None,
+        }
+    }
+
+    pub fn with_thin_film(mut self, thin_film: ThinFilm) -> Self {
+        self.thin_film = Some(thin_film);
+        self
+    }
+
+    fn fuzz(&self, hit_record: &HitRecord) -> Vec3 {
+        let offset = Vec3::random_in_unit_sphere();
+        if let Some((tangent_fuzz, bitangent_fuzz)) = self.anisotropic_fuzz {
+            let bitangent = hit_record.normal.cross(hit_record.tangent);
+            hit_record.tangent.mul_scalar(offset.x() * tangent_fuzz)
+                + bitangent.mul_scalar(offset.y() * bitangent_fuzz)
+                + hit_record.normal.mul_scalar(offset.z() * self.fuzz_factor)
+        } else {
+            offset.mul_scalar(self.fuzz_factor)
         }
     }
 }
 
 impl Material for Metal {
     fn scatter(&self, ray_in: Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
-        let reflected_direction = reflect(ray_in.direction().into_unit_vec(), hit_record.normal);
+        let unit_direction = ray_in.direction().into_unit_vec();
+        let reflected_direction = reflect(unit_direction, hit_record.normal);
         let reflected_ray = Ray::new(
             hit_record.hit_point,
-            reflected_direction + Vec3::random_in_unit_sphere().mul_scalar(self.fuzz_factor),
+            reflected_direction + self.fuzz(hit_record),
         );
 
+        let attenuation = if let Some(thin_film) = self.thin_film.as_ref() {
+            let cos_theta = (-unit_direction.dot(hit_record.normal)).min(1.0);
+            thin_film.metal_reflectance(cos_theta, self.albedo)
+        } else {
+            self.albedo
+        };
+
         // TODO(dpopacristobal): There is probably a much neater way of doing this...
         let mut ret: Option<ScatterRecord> = None;
         if reflected_ray.direction().dot(hit_record.normal) > 0.0 {
-            ret = Some(ScatterRecord::new(Some(reflected_ray), None, self.albedo));
+            ret = Some(ScatterRecord::new(Some(reflected_ray), None, attenuation));
         }
 
         ret
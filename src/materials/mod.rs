@@ -1,8 +1,15 @@
 pub use default::DefaultMaterial;
 pub use dielectric::Dielectric;
 pub use diffuse_light::DiffuseLight;
+pub use diffuse_transmission::DiffuseTransmission;
+pub use holdout::Holdout;
+pub use isotropic::Isotropic;
 pub use lambertian::Lambertian;
+pub use measured::MeasuredMaterial;
 pub use metal::Metal;
+pub use oren_nayar::OrenNayar;
+pub use shadow_catcher::ShadowCatcher;
+pub use thin_film::ThinFilm;
 
 use crate::hittables::HitRecord;
 use crate::linalg::{Color, Ray, Vec3};
@@ -11,8 +18,15 @@ use crate::pdfs::PDF;
 pub mod default;
 pub mod dielectric;
 pub mod diffuse_light;
+pub mod diffuse_transmission;
+pub mod holdout;
+pub mod isotropic;
 pub mod lambertian;
+pub mod measured;
 pub mod metal;
+pub mod oren_nayar;
+pub mod shadow_catcher;
+pub mod thin_film;
 
 use std::sync::Arc;
 
@@ -32,18 +46,65 @@ impl ScatterRecord {
     }
 }
 
+// How surfaces seen directly by the camera are treated when rendering images with an alpha channel,
+// for compositing over other footage.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Compositing {
+    Opaque,
+    Holdout,
+    ShadowCatcher,
+}
+
 pub trait Material: Send + Sync {
     fn scatter(&self, _ray_in: Ray, _hit_record: &HitRecord) -> Option<ScatterRecord> {
         None
     }
 
-    fn emit(&self, _u: f64, _v: f64, _hit_record: &mut HitRecord) -> Color {
+    // Radiance emitted from the hit point back along the incoming ray.
+    fn emit(&self, _ray_in: Ray, _hit_record: &HitRecord) -> Color {
+        Color::default()
+    }
+
+    // Average radiance emitted by the material, used to estimate how much light a surface emits.
+    fn emitted_radiance(&self) -> Color {
         Color::default()
     }
 
+    // Whether light is emitted from the back faces of surfaces as well as the front faces.
+    fn emits_both_sides(&self) -> bool {
+        false
+    }
+
     fn scattering_pdf(&self, _ray_in: Ray, _ray_scattered: Ray, _hit_record: &HitRecord) -> f64 {
         0.0
     }
+
+    // Per-channel version of `attenuation * scattering_pdf`, for materials whose color depends on
+    // the direction that the light is scattered in.
+    fn scattering_color(
+        &self,
+        ray_in: Ray,
+        ray_scattered: Ray,
+        hit_record: &HitRecord,
+        attenuation: Color,
+    ) -> Color {
+        attenuation.mul_scalar(self.scattering_pdf(ray_in, ray_scattered, hit_record))
+    }
+
+    // Fraction of the scattered directions that should come from sampling the lights rather than
+    // the material. Materials that scatter light into narrow lobes do better with lower values.
+    fn light_sampling_weight(&self) -> f64 {
+        0.5
+    }
+
+    fn compositing(&self) -> Compositing {
+        Compositing::Opaque
+    }
+
+    // Whether the material scatters light inside a participating medium rather than off a surface.
+    fn is_volumetric(&self) -> bool {
+        false
+    }
 }
 
 pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
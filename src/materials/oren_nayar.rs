@@ -0,0 +1,102 @@
+use crate::hittables::HitRecord;
+use crate::linalg::{Color, Ray, Vec3, ONB};
+use crate::materials::Material;
+use crate::pdfs::CosinePDF;
+
+use super::ScatterRecord;
+
+use std::sync::Arc;
+
+pub struct OrenNayar {
+    albedo: Color,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    // The roughness sigma is the standard deviation of the microfacet slope angle, in degrees.
+    pub fn new(albedo: Color, sigma: f64) -> Self {
+        let sigma_sq = sigma.to_radians().powi(2);
+        let a = 1.0 - sigma_sq / (2.0 * (sigma_sq + 0.33));
+        let b = 0.45 * sigma_sq / (sigma_sq + 0.09);
+
+        Self { albedo, a, b }
+    }
+}
+
+fn sin_theta(local_dir: Vec3) -> f64 {
+    (1.0 - local_dir.z().powi(2)).max(0.0).sqrt()
+}
+
+impl Material for OrenNayar {
+    fn scatter(&self, _ray_in: Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord::new(
+            None,
+            Some(Arc::new(CosinePDF::new(hit_record.normal))),
+            self.albedo,
+        ))
+    }
+
+    fn scattering_pdf(&self, ray_in: Ray, ray_scattered: Ray, hit_record: &HitRecord) -> f64 {
+        let onb = ONB::new(hit_record.normal);
+        let to_local = |v: Vec3| Vec3::new(v.dot(onb.u()), v.dot(onb.v()), v.dot(onb.w()));
+        let dir_out = to_local(ray_in.direction().into_unit_vec().mul_scalar(-1.0));
+        let dir_in = to_local(ray_scattered.direction().into_unit_vec());
+
+        let cos_theta_in = dir_in.z();
+        if cos_theta_in <= 0.0 {
+            return 0.0;
+        }
+
+        let sin_theta_in = sin_theta(dir_in);
+        let sin_theta_out = sin_theta(dir_out);
+
+        // Cosine of the azimuthal angle between the two directions.
+        let max_cos = if sin_theta_in > 1e-4 && sin_theta_out > 1e-4 {
+            let cos_delta_phi = (dir_in.x() * dir_out.x() + dir_in.y() * dir_out.y())
+                / (sin_theta_in * sin_theta_out);
+            cos_delta_phi.max(0.0)
+        } else {
+            0.0
+        };
+
+        let cos_theta_out = dir_out.z().abs().max(1e-8);
+        let (sin_alpha, tan_beta) = if cos_theta_in.abs() > cos_theta_out {
+            (sin_theta_out, sin_theta_in / cos_theta_in.abs())
+        } else {
+            (sin_theta_in, sin_theta_out / cos_theta_out)
+        };
+
+        cos_theta_in * (self.a + self.b * max_cos * sin_alpha * tan_beta) / std::f64::consts::PI
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittables::{Hit, Sphere};
+    use crate::linalg::Point3;
+    use crate::materials::Lambertian;
+
+    #[test]
+    fn smooth_oren_nayar_is_lambertian() {
+        let albedo = Color::from_scalar(0.5);
+        let sphere = Sphere::new(Point3::default(), 1.0, Arc::new(Lambertian::new(albedo)));
+        let ray_in = Ray::new(Point3::new(0.3, 0.2, 5.0), Vec3::new(-0.3, -0.2, -4.0));
+        let hit_record = sphere.hit(ray_in, 0.001, f64::INFINITY).unwrap();
+
+        let oren_nayar = OrenNayar::new(albedo, 0.0);
+        let lambertian = Lambertian::new(albedo);
+        for _ in 0..1000 {
+            let ray_scattered = Ray::new(hit_record.hit_point, Vec3::random_unit_vector());
+            let expected = lambertian.scattering_pdf(ray_in, ray_scattered, &hit_record);
+            let pdf = oren_nayar.scattering_pdf(ray_in, ray_scattered, &hit_record);
+            assert!(
+                (pdf - expected).abs() < 1e-12,
+                "{} instead of {}",
+                pdf,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use crate::hittables::HitRecord;
+use crate::linalg::{Color, Ray};
+use crate::materials::{Compositing, Lambertian, Material};
+
+use super::ScatterRecord;
+
+// Stand-in for a surface in the footage that the render is composited over, usually the ground. It
+// is shaded as a Lambertian surface wherever it is not seen directly by the camera, while camera hits
+// only record the shadows and reflections that the rest of the scene casts onto it.
+pub struct ShadowCatcher {
+    lambertian: Lambertian,
+}
+
+impl ShadowCatcher {
+    pub fn new(albedo: Color) -> Self {
+        Self {
+            lambertian: Lambertian::new(albedo),
+        }
+    }
+}
+
+impl Material for ShadowCatcher {
+    fn scatter(&self, ray_in: Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+        self.lambertian.scatter(ray_in, hit_record)
+    }
+
+    fn scattering_pdf(&self, ray_in: Ray, ray_scattered: Ray, hit_record: &HitRecord) -> f64 {
+        self.lambertian
+            .scattering_pdf(ray_in, ray_scattered, hit_record)
+    }
+
+    fn compositing(&self) -> Compositing {
+        Compositing::ShadowCatcher
+    }
+}
@@ -0,0 +1,142 @@
+use crate::linalg::Color;
+
+// Wavelengths (in nanometres) at which the interference is evaluated for the red, green and blue
+// channels respectively.
+const WAVELENGTHS: [f64; 3] = [650.0, 532.0, 450.0];
+
+// Thin transparent coating (soap bubbles, oil slicks, anodised metals) whose reflections from its top
+// and bottom interfaces interfere with each other, producing iridescent colors.
+#[derive(Copy, Clone, Debug)]
+pub struct ThinFilm {
+    thickness: f64,
+    refractive_index: f64,
+}
+
+// Fresnel amplitude coefficients for s- and p-polarized light at an interface.
+fn fresnel_amplitudes(eta_i: f64, cos_i: f64, eta_t: f64, cos_t: f64) -> (f64, f64) {
+    let r_s = (eta_i * cos_i - eta_t * cos_t) / (eta_i * cos_i + eta_t * cos_t);
+    let r_p = (eta_t * cos_i - eta_i * cos_t) / (eta_t * cos_i + eta_i * cos_t);
+
+    (r_s, r_p)
+}
+
+// Reflectance of a film from the sum of all its internal reflections, given the amplitude
+// coefficients of its two interfaces and the phase difference between consecutive reflections.
+fn airy_reflectance(r_top: f64, r_bottom: f64, phase: f64) -> f64 {
+    let cross_term = 2.0 * r_top * r_bottom * phase.cos();
+    (r_top.powi(2) + r_bottom.powi(2) + cross_term)
+        / (1.0 + (r_top * r_bottom).powi(2) + cross_term)
+}
+
+impl ThinFilm {
+    // The thickness of the film is given in nanometres.
+    pub fn new(thickness: f64, refractive_index: f64) -> Self {
+        Self {
+            thickness,
+            refractive_index,
+        }
+    }
+
+    // Returns the cosine of the refraction angle inside the film and the amplitude coefficients of
+    // the top interface, or None if the light is totally reflected before entering the film.
+    fn enter_film(&self, cos_theta: f64, outer_index: f64) -> Option<(f64, (f64, f64))> {
+        let sin_theta_sq = 1.0 - cos_theta.powi(2);
+        let sin_film_sq = (outer_index / self.refractive_index).powi(2) * sin_theta_sq;
+        if sin_film_sq >= 1.0 {
+            return None;
+        }
+
+        let cos_film = (1.0 - sin_film_sq).sqrt();
+        let amplitudes =
+            fresnel_amplitudes(outer_index, cos_theta, self.refractive_index, cos_film);
+
+        Some((cos_film, amplitudes))
+    }
+
+    fn phase(&self, cos_film: f64, wavelength: f64) -> f64 {
+        4.0 * std::f64::consts::PI * self.refractive_index * self.thickness * cos_film / wavelength
+    }
+
+    // Reflectance of the film lying on top of a dielectric substrate, for light arriving from a medium
+    // with refractive index `outer_index`.
+    pub fn dielectric_reflectance(
+        &self,
+        cos_theta: f64,
+        outer_index: f64,
+        substrate_index: f64,
+    ) -> Color {
+        let (cos_film, (r_top_s, r_top_p)) = match self.enter_film(cos_theta, outer_index) {
+            Some(film) => film,
+            None => return Color::from_scalar(1.0),
+        };
+
+        let sin_substrate_sq =
+            (self.refractive_index / substrate_index).powi(2) * (1.0 - cos_film.powi(2));
+        let (r_bottom_s, r_bottom_p) = if sin_substrate_sq >= 1.0 {
+            (1.0, 1.0)
+        } else {
+            let cos_substrate = (1.0 - sin_substrate_sq).sqrt();
+            fresnel_amplitudes(
+                self.refractive_index,
+                cos_film,
+                substrate_index,
+                cos_substrate,
+            )
+        };
+
+        let mut reflectance = Color::default();
+        for (idx, wavelength) in WAVELENGTHS.iter().enumerate() {
+            let phase = self.phase(cos_film, *wavelength);
+            reflectance[idx] = 0.5
+                * (airy_reflectance(r_top_s, r_bottom_s, phase)
+                    + airy_reflectance(r_top_p, r_bottom_p, phase));
+        }
+
+        reflectance
+    }
+
+    // Reflectance of the film lying on top of a metal with the given albedo. Metals are approximated
+    // as reflecting with a phase shift of half a wavelength.
+    pub fn metal_reflectance(&self, cos_theta: f64, albedo: Color) -> Color {
+        let (cos_film, (r_top_s, r_top_p)) = match self.enter_film(cos_theta, 1.0) {
+            Some(film) => film,
+            None => return Color::from_scalar(1.0),
+        };
+
+        let mut reflectance = Color::default();
+        for (idx, wavelength) in WAVELENGTHS.iter().enumerate() {
+            let phase = self.phase(cos_film, *wavelength);
+            let r_bottom = -albedo[idx].max(0.0).sqrt();
+            reflectance[idx] = 0.5
+                * (airy_reflectance(r_top_s, r_bottom, phase)
+                    + airy_reflectance(r_top_p, r_bottom, phase));
+        }
+
+        reflectance
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ThinFilm;
+
+    #[test]
+    fn thin_film_without_thickness_matches_fresnel() {
+        let film = ThinFilm::new(0.0, 1.5);
+        let reflectance = film.dielectric_reflectance(1.0, 1.0, 1.5);
+        let fresnel = (0.5_f64 / 2.5).powi(2);
+        for idx in 0..3 {
+            assert!((reflectance[idx] - fresnel).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn thin_film_reflectance_varies_with_wavelength() {
+        let film = ThinFilm::new(250.0, 1.33);
+        let reflectance = film.dielectric_reflectance(1.0, 1.0, 1.0);
+        assert!((reflectance.r() - reflectance.b()).abs() > 0.01);
+        for idx in 0..3 {
+            assert!(reflectance[idx] >= 0.0 && reflectance[idx] <= 1.0);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// Piecewise-constant 1D distribution over [0, 1), built from a tabulated (not necessarily
+// normalized) function, that can be sampled by inverting its CDF.
+#[derive(Clone, Debug)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    func_integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: &[f64]) -> Self {
+        let count = func.len();
+        let func: Vec<f64> = func.iter().map(|value| value.max(0.0)).collect();
+
+        let mut cdf = vec![0.0; count + 1];
+        for idx in 0..count {
+            cdf[idx + 1] = cdf[idx] + func[idx] / count as f64;
+        }
+
+        let func_integral = cdf[count];
+        for (idx, value) in cdf.iter_mut().enumerate().skip(1) {
+            // Fall back to a uniform distribution if the function is zero everywhere.
+            *value = if func_integral > 0.0 {
+                *value / func_integral
+            } else {
+                idx as f64 / count as f64
+            };
+        }
+
+        Self {
+            func,
+            cdf,
+            func_integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.func_integral
+    }
+
+    fn find_interval(&self, u: f64) -> usize {
+        // Index of the last CDF entry that is smaller than or equal to u.
+        let idx = self.cdf.partition_point(|value| *value <= u);
+        idx.saturating_sub(1).min(self.count() - 1)
+    }
+
+    // Returns a sample in [0, 1), its probability density and the index of the bucket it fell in.
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        let offset = self.find_interval(u);
+
+        let mut du = u - self.cdf[offset];
+        let bucket_size = self.cdf[offset + 1] - self.cdf[offset];
+        if bucket_size > 0.0 {
+            du /= bucket_size;
+        }
+
+        let pdf = self.pdf_at(offset);
+        let x = ((offset as f64 + du) / self.count() as f64).min(1.0 - f64::EPSILON);
+
+        (x, pdf, offset)
+    }
+
+    // Returns the index of a sampled bucket and the probability of having picked it.
+    pub fn sample_discrete(&self, u: f64) -> (usize, f64) {
+        let offset = self.find_interval(u);
+        (offset, self.discrete_pdf(offset))
+    }
+
+    pub fn discrete_pdf(&self, idx: usize) -> f64 {
+        self.cdf[idx + 1] - self.cdf[idx]
+    }
+
+    fn pdf_at(&self, idx: usize) -> f64 {
+        if self.func_integral > 0.0 {
+            self.func[idx] / self.func_integral
+        } else {
+            1.0
+        }
+    }
+
+    // Probability density of a continuous sample at x in [0, 1).
+    pub fn pdf(&self, x: f64) -> f64 {
+        let idx = ((x * self.count() as f64) as usize).min(self.count() - 1);
+        self.pdf_at(idx)
+    }
+}
+
+// Piecewise-constant 2D distribution over [0, 1)^2, sampled with a marginal distribution over rows
+// and a conditional distribution over the columns of each row.
+#[derive(Clone, Debug)]
+pub struct Distribution2D {
+    conditionals: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // The function is given in row-major order, with `width` values per row.
+    pub fn new(func: &[f64], width: usize) -> Self {
+        let conditionals: Vec<Distribution1D> =
+            func.chunks(width).map(Distribution1D::new).collect();
+        let row_integrals: Vec<f64> = conditionals.iter().map(|row| row.integral()).collect();
+        let marginal = Distribution1D::new(&row_integrals);
+
+        Self {
+            conditionals,
+            marginal,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.marginal.integral()
+    }
+
+    // Returns a sample (column, row) in [0, 1)^2 together with its probability density.
+    pub fn sample_continuous(&self, u: f64, v: f64) -> ((f64, f64), f64) {
+        let (row, row_pdf, row_idx) = self.marginal.sample_continuous(v);
+        let (column, column_pdf, _) = self.conditionals[row_idx].sample_continuous(u);
+
+        ((column, row), row_pdf * column_pdf)
+    }
+
+    pub fn pdf(&self, column: f64, row: f64) -> f64 {
+        let row_count = self.conditionals.len();
+        let row_idx = ((row * row_count as f64) as usize).min(row_count - 1);
+        self.marginal.pdf(row) * self.conditionals[row_idx].pdf(column)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Distribution1D, Distribution2D};
+
+    #[test]
+    fn distribution_1d_sampling() {
+        let distribution = Distribution1D::new(&[1.0, 3.0, 0.0, 4.0]);
+        assert_eq!(distribution.integral(), 2.0);
+
+        let (idx, pmf) = distribution.sample_discrete(0.2);
+        assert_eq!((idx, pmf), (1, 0.375));
+        let (idx, pmf) = distribution.sample_discrete(0.6);
+        assert_eq!((idx, pmf), (3, 0.5));
+
+        let (x, pdf, offset) = distribution.sample_continuous(0.25);
+        assert_eq!(offset, 1);
+        assert!((x - (1.0 + 1.0 / 3.0) / 4.0).abs() < 1e-12);
+        assert_eq!(pdf, 1.5);
+        assert_eq!(distribution.pdf(x), pdf);
+        assert_eq!(distribution.pdf(0.6), 0.0);
+    }
+
+    #[test]
+    fn distribution_2d_sampling() {
+        let distribution = Distribution2D::new(&[1.0, 1.0, 2.0, 0.0], 2);
+
+        let ((column, row), pdf) = distribution.sample_continuous(0.5, 0.75);
+        assert!(row >= 0.5 && column < 0.5);
+        assert!((pdf - distribution.pdf(column, row)).abs() < 1e-12);
+        assert!((pdf - 2.0 / 1.0).abs() < 1e-12);
+    }
+}
@@ -1,34 +1,155 @@
 use std::sync::Arc;
 
-use rand::Rng;
-
-use super::PDF;
+use super::{Distribution1D, PDF};
 
 use crate::linalg::Vec3;
+use crate::sampler;
 
+// Weighted sum of several PDFs, sampled by picking one of them with a probability equal to its
+// weight.
 pub struct MixturePDF {
-    pdfs: [Arc<dyn PDF>; 2],
+    pdfs: Vec<Arc<dyn PDF>>,
+    weights: Distribution1D,
 }
 
 impl MixturePDF {
-    pub fn new(pdfs: [Arc<dyn PDF>; 2]) -> Self {
-        Self { pdfs }
+    // Mixture that picks each of the PDFs with equal probability.
+    pub fn new(pdfs: Vec<Arc<dyn PDF>>) -> Self {
+        let weights = vec![1.0; pdfs.len()];
+        Self::with_weights(pdfs, &weights)
+    }
+
+    // Mixture that picks each of the PDFs proportionally to its weight. The weights don't need to be
+    // normalized, but they need to be non-negative.
+    pub fn with_weights(pdfs: Vec<Arc<dyn PDF>>, weights: &[f64]) -> Self {
+        assert!(!pdfs.is_empty(), "A mixture needs at least one PDF.");
+        assert_eq!(
+            pdfs.len(),
+            weights.len(),
+            "A mixture needs exactly one weight per PDF."
+        );
+
+        Self {
+            pdfs,
+            weights: Distribution1D::new(weights),
+        }
+    }
+
+    // Normalized weight of the PDF at the given index.
+    pub fn weight(&self, idx: usize) -> f64 {
+        self.weights.discrete_pdf(idx)
     }
 }
 
-// TODO(dpopacristobal): Should the user be allowed to specify the weight assigned to each PDF?
 impl PDF for MixturePDF {
     fn value(&self, direction: Vec3) -> f64 {
-        0.5 * self.pdfs[0].value(direction) + 0.5 * self.pdfs[1].value(direction)
+        self.pdfs
+            .iter()
+            .enumerate()
+            .map(|(idx, pdf)| {
+                let weight = self.weight(idx);
+                if weight > 0.0 {
+                    weight * pdf.value(direction)
+                } else {
+                    0.0
+                }
+            })
+            .sum()
     }
 
     fn generate(&self) -> Vec3 {
-        let mut rng = rand::thread_rng();
-        let rand: f64 = rng.gen_range(0.0, 1.0);
-        if rand < 0.5 {
-            self.pdfs[0].generate()
-        } else {
-            self.pdfs[1].generate()
+        let (idx, _) = self.weights.sample_discrete(sampler::random());
+        self.pdfs[idx].generate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pdfs::{CosinePDF, SpherePDF};
+    use std::f64::consts::PI;
+
+    // Bins directions by their z coordinate and their azimuth, so that all bins cover the same
+    // solid angle.
+    const Z_BINS: usize = 10;
+    const PHI_BINS: usize = 20;
+
+    fn bin(direction: Vec3) -> usize {
+        let direction = direction.into_unit_vec();
+        let z = ((direction.z() + 1.0) / 2.0 * Z_BINS as f64) as usize;
+        let phi = direction.y().atan2(direction.x()) + PI;
+        let phi = (phi / (2.0 * PI) * PHI_BINS as f64) as usize;
+        z.min(Z_BINS - 1) * PHI_BINS + phi.min(PHI_BINS - 1)
+    }
+
+    // Probability of each bin, integrating the PDF over the bin with the midpoint rule.
+    fn expected_probabilities(pdf: &dyn PDF) -> Vec<f64> {
+        let steps = 8;
+        let bin_solid_angle = 4.0 * PI / (Z_BINS * PHI_BINS) as f64;
+        let mut probabilities = vec![0.0; Z_BINS * PHI_BINS];
+        for z_bin in 0..Z_BINS {
+            for phi_bin in 0..PHI_BINS {
+                let mut sum = 0.0;
+                for z_step in 0..steps {
+                    for phi_step in 0..steps {
+                        let z = -1.0
+                            + 2.0 * (z_bin as f64 + (z_step as f64 + 0.5) / steps as f64)
+                                / Z_BINS as f64;
+                        let phi =
+                            2.0 * PI * (phi_bin as f64 + (phi_step as f64 + 0.5) / steps as f64)
+                                / PHI_BINS as f64
+                                - PI;
+                        let r = (1.0 - z * z).sqrt();
+                        sum += pdf.value(Vec3::new(r * phi.cos(), r * phi.sin(), z));
+                    }
+                }
+                probabilities[z_bin * PHI_BINS + phi_bin] =
+                    sum / (steps * steps) as f64 * bin_solid_angle;
+            }
+        }
+
+        probabilities
+    }
+
+    #[test]
+    fn generate_matches_value() {
+        let pdf = MixturePDF::with_weights(
+            vec![
+                Arc::new(CosinePDF::new(Vec3::new(0.0, 0.0, 1.0))),
+                Arc::new(SpherePDF::default()),
+                Arc::new(CosinePDF::new(Vec3::new(0.0, 0.0, -1.0))),
+            ],
+            &[3.0, 1.0, 0.0],
+        );
+        assert!((pdf.weight(0) - 0.75).abs() < 1e-12);
+
+        let sample_num = 200_000;
+        let mut observed = vec![0.0; Z_BINS * PHI_BINS];
+        for _ in 0..sample_num {
+            observed[bin(pdf.generate())] += 1.0;
+        }
+
+        let expected = expected_probabilities(&pdf);
+        assert!((expected.iter().sum::<f64>() - 1.0).abs() < 1e-3);
+
+        // Bins with too few expected samples are pooled together, as is usual for the test.
+        let mut chi_square = 0.0;
+        let mut pooled_observed = 0.0;
+        let mut pooled_expected = 0.0;
+        for (observed, probability) in observed.iter().zip(&expected) {
+            let expected = probability * sample_num as f64;
+            if expected < 5.0 {
+                pooled_observed += observed;
+                pooled_expected += expected;
+            } else {
+                chi_square += (observed - expected).powi(2) / expected;
+            }
+        }
+        if pooled_expected > 0.0 {
+            chi_square += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
         }
+
+        // About 199 degrees of freedom, for which the 99.9% quantile is around 264.
+        assert!(chi_square < 300.0, "chi-square statistic {}", chi_square);
     }
 }
@@ -1,14 +1,74 @@
+use std::str::FromStr;
+
 use crate::linalg::Vec3;
 
 pub use cosine::CosinePDF;
+pub use distribution::{Distribution1D, Distribution2D};
 pub use hittable::HittablePDF;
 pub use mixture::MixturePDF;
+pub use sphere::SpherePDF;
 
 pub mod cosine;
+pub mod distribution;
 pub mod hittable;
 pub mod mixture;
+pub mod sphere;
 
 pub trait PDF: Send + Sync {
     fn value(&self, direction: Vec3) -> f64;
     fn generate(&self) -> Vec3;
 }
+
+// How the samples of two strategies that can produce the same direction are weighted against each
+// other in multiple importance sampling.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MisHeuristic {
+    Balance,
+    Power,
+}
+
+impl MisHeuristic {
+    // Weight of a sample drawn with a PDF of `pdf`, given that the other strategy would have drawn
+    // it with a PDF of `other_pdf`.
+    pub fn weight(self, pdf: f64, other_pdf: f64) -> f64 {
+        let (pdf, other_pdf) = match self {
+            MisHeuristic::Balance => (pdf, other_pdf),
+            MisHeuristic::Power => (pdf * pdf, other_pdf * other_pdf),
+        };
+        if pdf + other_pdf > 0.0 {
+            pdf / (pdf + other_pdf)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl FromStr for MisHeuristic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "balance" => Ok(MisHeuristic::Balance),
+            "power" => Ok(MisHeuristic::Power),
+            _ => Err(format!(
+                "Invalid MIS heuristic '{}'. Use one of [balance, power]",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mis_weights_sum_to_one() {
+        for heuristic in [MisHeuristic::Balance, MisHeuristic::Power] {
+            let weight = heuristic.weight(0.3, 1.7) + heuristic.weight(1.7, 0.3);
+            assert!((weight - 1.0).abs() < 1e-12);
+        }
+        assert!((MisHeuristic::Power.weight(1.0, 3.0) - 0.1).abs() < 1e-12);
+        assert_eq!(MisHeuristic::Power.weight(0.0, 0.0), 0.0);
+    }
+}
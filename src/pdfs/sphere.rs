@@ -0,0 +1,17 @@
+use crate::linalg::Vec3;
+
+use super::PDF;
+
+// Uniform distribution over the whole sphere of directions.
+#[derive(Default)]
+pub struct SpherePDF {}
+
+impl PDF for SpherePDF {
+    fn value(&self, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * std::f64::consts::PI)
+    }
+
+    fn generate(&self) -> Vec3 {
+        Vec3::random_unit_vector()
+    }
+}
@@ -1,104 +1,360 @@
 use std::fs;
 use std::sync::Arc;
 
-use rand::Rng;
 use rayon::prelude::*;
 
-use crate::hittables::{Hit, World};
-use crate::linalg::{Color, Ray};
+use crate::film::Film;
+use crate::hittables::{Hit, HitRecord, World};
+use crate::integrators::{
+    delta_light_samples, light_sampling_weight, linked_emission, sampled_lights, Integrator,
+};
+use crate::lights::{Background, LightGroups};
+use crate::linalg::{Color, Ray, RayType};
+use crate::materials::{Compositing, ScatterRecord};
 use crate::pdfs::{HittablePDF, MixturePDF, PDF};
 use crate::scene::Scene;
 
-fn ray_color(
-    ray: Ray,
-    background: Color,
+// Picks the direction that a diffuse scatter continues in, importance sampling the lights if there
+// are any, and returns it together with the value of the PDF it was sampled from.
+fn sample_scatter_ray(
+    hit_record: &HitRecord,
+    scatter_record: ScatterRecord,
+    lights: Option<&Arc<dyn Hit>>,
+    light_weight: f64,
+) -> (Ray, f64) {
+    if let Some(lights) = lights {
+        let light_pdf: Arc<dyn PDF> =
+            Arc::new(HittablePDF::new(lights.clone(), hit_record.hit_point));
+        let mixture_pdf = MixturePDF::with_weights(
+            vec![light_pdf, scatter_record.pdf.unwrap()],
+            &[light_weight, 1.0 - light_weight],
+        );
+
+        let scatter_ray =
+            Ray::new(hit_record.hit_point, mixture_pdf.generate()).with_type(RayType::Diffuse);
+        let pdf_val = mixture_pdf.value(*scatter_ray.direction());
+
+        (scatter_ray, pdf_val)
+    } else {
+        let pdf = scatter_record.pdf.unwrap();
+        let scatter_ray =
+            Ray::new(hit_record.hit_point, pdf.generate()).with_type(RayType::Diffuse);
+        let pdf_val = pdf.value(*scatter_ray.direction());
+
+        (scatter_ray, pdf_val)
+    }
+}
+
+// Radiance arriving along a ray from lights and the background alone, as if all the other objects in
+// the scene were not there.
+fn unoccluded_color(
+    mut ray: Ray,
+    background: &dyn Background,
     world: &World,
-    light: Option<Arc<dyn Hit>>,
-    depth: i32,
+    light_groups: LightGroups,
 ) -> Color {
-    if depth <= 0 {
-        return Color::default();
+    // Bounds the number of surfaces skipped, in case the ray gets stuck in a degenerate mesh.
+    for _ in 0..64 {
+        if let Some(hit_record) = world.hit(ray, 0.001, f64::INFINITY) {
+            let emitted_color = linked_emission(ray, &hit_record, light_groups);
+            if emitted_color != Color::default() {
+                return emitted_color;
+            }
+            ray = Ray::new(hit_record.hit_point, *ray.direction()).with_type(ray.ray_type());
+        } else {
+            return background.radiance(*ray.direction());
+        }
     }
 
-    let hit_result = world.hit(ray, 0.001, f64::INFINITY);
-    if let Some(mut hit_record) = hit_result {
-        let material = hit_record.material.clone();
-        let emitted_color = material.emit(0.0, 0.0, &mut hit_record);
-        let scatter_record_opt = hit_record.material.scatter(ray, &hit_record);
-        if let Some(scatter_record) = scatter_record_opt {
-            if let Some(specular_ray) = scatter_record.specular_ray {
-                scatter_record.attenuation
-                    * ray_color(specular_ray, background, world, light, depth - 1)
-            } else {
-                let (scatter_ray, pdf_val) = if let Some(light) = light.as_ref() {
-                    let light_pdf: Arc<dyn PDF> =
-                        Arc::new(HittablePDF::new(light.clone(), hit_record.hit_point));
-                    let mixture_pdf = MixturePDF::new([light_pdf, scatter_record.pdf.unwrap()]);
-
-                    let scatter_ray = Ray::new(hit_record.hit_point, mixture_pdf.generate());
-                    let pdf_val = mixture_pdf.value(*scatter_ray.direction());
-
-                    (scatter_ray, pdf_val)
-                } else {
-                    let pdf = scatter_record.pdf.unwrap();
-                    let scatter_ray = Ray::new(hit_record.hit_point, pdf.generate());
-                    let pdf_val = pdf.value(*scatter_ray.direction());
+    Color::default()
+}
 
-                    (scatter_ray, pdf_val)
-                };
-                emitted_color
-                    + scatter_record.attenuation.mul_scalar(
-                        hit_record
-                            .material
-                            .scattering_pdf(ray, scatter_ray, &hit_record)
-                            / pdf_val,
-                    ) * ray_color(scatter_ray, background, world, light, depth - 1)
-            }
-        } else {
-            emitted_color
+// Closest hit along a ray that is not a shadow catcher, since shadow catchers do not cast shadows
+// onto each other.
+fn occluder_hit(mut ray: Ray, world: &World, mut t_max: f64) -> Option<HitRecord> {
+    for _ in 0..64 {
+        let hit_record = world.hit(ray, 0.001, t_max)?;
+        if hit_record.material.compositing() != Compositing::ShadowCatcher {
+            return Some(hit_record);
         }
-    } else {
-        background
+        t_max -= hit_record.t;
+        ray = Ray::new(hit_record.hit_point, *ray.direction()).with_type(ray.ray_type());
     }
+
+    None
 }
 
-pub fn render(image_width: u32, samples_per_pixel: u32, scene: Scene) {
-    // TODO(dpopacristobal): Would it be worth exposing this in the CLI?
-    let max_depth = 20;
-    let image_height = (image_width as f64 / scene.aspect_ratio) as u32;
+// A single camera sample for images with an alpha channel.
+struct FilmSample {
+    // Color premultiplied by the alpha of the sample.
+    color: Color,
+    alpha: f64,
+    // Light that a shadow catcher reflects from the occluders, which has no alpha of its own.
+    reflection: Color,
+    // Luminance of the light that a shadow catcher would receive without any occluders, together
+    // with the part of it that is blocked by the objects in the scene.
+    shadow: Option<(f64, f64)>,
+}
+
+fn camera_sample(ray: Ray, scene: &Scene, integrator: &dyn Integrator, film: &Film) -> FilmSample {
+    let lights = sampled_lights(scene);
+    let hit_record = match scene.world.hit(ray, 0.001, f64::INFINITY) {
+        Some(hit_record) => hit_record,
+        None => {
+            integrator.skipped_camera_sample(scene, film);
+            return FilmSample {
+                color: Color::default(),
+                alpha: 0.0,
+                reflection: Color::default(),
+                shadow: None,
+            };
+        }
+    };
+
+    match hit_record.material.compositing() {
+        Compositing::Opaque => FilmSample {
+            color: integrator.camera_ray_color(ray, scene, film),
+            alpha: 1.0,
+            reflection: Color::default(),
+            shadow: None,
+        },
+        Compositing::Holdout => {
+            integrator.skipped_camera_sample(scene, film);
+            FilmSample {
+                color: Color::default(),
+                alpha: 0.0,
+                reflection: Color::default(),
+                shadow: None,
+            }
+        }
+        Compositing::ShadowCatcher => {
+            integrator.skipped_camera_sample(scene, film);
+            let scatter_record = match hit_record.material.scatter(ray, &hit_record) {
+                Some(scatter_record) => scatter_record,
+                None => {
+                    return FilmSample {
+                        color: Color::default(),
+                        alpha: 0.0,
+                        reflection: Color::default(),
+                        shadow: Some((0.0, 0.0)),
+                    }
+                }
+            };
+
+            let light_weight = light_sampling_weight(scene, &hit_record);
+            let attenuation = scatter_record.attenuation;
+            let (scatter_ray, pdf_val) =
+                sample_scatter_ray(&hit_record, scatter_record, lights.as_ref(), light_weight);
+            let weight = hit_record
+                .material
+                .scattering_color(ray, scatter_ray, &hit_record, attenuation)
+                .div_scalar(pdf_val);
 
-    let mut image_buffer: image::RgbImage = image::ImageBuffer::new(image_width, image_height);
+            let unoccluded = weight
+                * unoccluded_color(
+                    scatter_ray,
+                    scene.background.as_ref(),
+                    &scene.world,
+                    hit_record.light_groups,
+                );
+            let occluded = match occluder_hit(scatter_ray, &scene.world, f64::INFINITY) {
+                Some(occluder) => {
+                    linked_emission(scatter_ray, &occluder, hit_record.light_groups)
+                        == Color::default()
+                }
+                None => false,
+            };
 
-    // Render the scene.
-    let mut pixels: Vec<(u32, u32, &mut image::Rgb<u8>)> = Vec::new();
-    pixels.reserve((image_width * image_height) as usize);
-    for (i, j, pixel) in image_buffer.enumerate_pixels_mut() {
-        pixels.push((i, j, pixel));
+            let mut unoccluded_luminance = unoccluded.luminance();
+            let mut occluded_luminance = 0.0;
+            let mut reflection = Color::default();
+            if occluded {
+                // Light bounced off the occluder is what the occluder reflects onto the surface.
+                occluded_luminance = unoccluded_luminance;
+                reflection =
+                    weight * integrator.ray_color(scatter_ray, scene, hit_record.light_groups);
+            }
+
+            // Delta lights cast shadows onto the catcher just like the area lights do.
+            for (shadow_ray, distance, light_color) in
+                delta_light_samples(ray, &hit_record, attenuation, &scene.delta_lights)
+            {
+                unoccluded_luminance += light_color.luminance();
+                if occluder_hit(shadow_ray, &scene.world, distance).is_some() {
+                    occluded_luminance += light_color.luminance();
+                }
+            }
+
+            FilmSample {
+                color: Color::default(),
+                alpha: 0.0,
+                reflection,
+                shadow: Some((unoccluded_luminance, occluded_luminance)),
+            }
+        }
     }
+}
+
+// Renders a pixel with an alpha channel, returning its premultiplied color, its alpha, and the light
+// that shadow catchers reflect from the occluders onto it.
+fn render_alpha_pixel(
+    i: u32,
+    j: u32,
+    samples_per_pixel: u32,
+    scene: &Scene,
+    integrator: &dyn Integrator,
+    film: &Film,
+) -> (Color, f64, Color) {
+    let mut color_accumulator = Color::default();
+    let mut alpha_accumulator = 0.0;
+    let mut reflection_accumulator = Color::default();
+    let mut shadow_sample_num = 0;
+    let mut unoccluded_accumulator = 0.0;
+    let mut occluded_accumulator = 0.0;
 
-    pixels.par_iter_mut().for_each(|(i, j, pixel)| {
-        let mut pixel_color_accumulator = Color::default();
-        for _ in 0..samples_per_pixel {
-            let mut rng = rand::thread_rng();
-            let u = (*i as f64 + rng.gen_range(0.0, 1.0)) / (image_width - 1) as f64;
-            let v =
-                ((image_height - *j) as f64 + rng.gen_range(0.0, 1.0)) / (image_height - 1) as f64;
-            let ray = scene.camera.get_ray(u, v);
-            pixel_color_accumulator.accumulate_sample(ray_color(
-                ray,
-                scene.background,
-                &scene.world,
-                scene.light.clone(),
-                max_depth,
-            ));
+    for _ in 0..samples_per_pixel {
+        let (u, v) = film.sample_pixel(i, j);
+        let ray = scene.camera.get_ray(u, v);
+
+        let sample = camera_sample(ray, scene, integrator, film);
+        color_accumulator.accumulate_sample(sample.color);
+        alpha_accumulator += sample.alpha;
+        reflection_accumulator.accumulate_sample(sample.reflection);
+        if let Some((unoccluded, occluded)) = sample.shadow {
+            shadow_sample_num += 1;
+            unoccluded_accumulator += unoccluded;
+            occluded_accumulator += occluded;
         }
+    }
 
-        let pixel_color: Color = pixel_color_accumulator.average_samples(samples_per_pixel);
-        **pixel = image::Rgb(pixel_color.gamma_2_correct().into_rgb8());
-    });
+    // The shadow opacity is the fraction of the light that is blocked, over all the samples that hit
+    // a shadow catcher.
+    if unoccluded_accumulator > 0.0 {
+        alpha_accumulator +=
+            shadow_sample_num as f64 * (occluded_accumulator / unoccluded_accumulator).min(1.0);
+    }
+
+    (
+        color_accumulator.average_samples(samples_per_pixel),
+        alpha_accumulator / samples_per_pixel as f64,
+        reflection_accumulator.average_samples(samples_per_pixel),
+    )
+}
+
+fn render_pixel(
+    i: u32,
+    j: u32,
+    samples_per_pixel: u32,
+    scene: &Scene,
+    integrator: &dyn Integrator,
+    film: &Film,
+) -> Color {
+    let mut pixel_color_accumulator = Color::default();
+    for _ in 0..samples_per_pixel {
+        let (u, v) = film.sample_pixel(i, j);
+        let ray = scene.camera.get_ray(u, v);
+        pixel_color_accumulator.accumulate_sample(integrator.camera_ray_color(ray, scene, film));
+    }
+
+    pixel_color_accumulator.average_samples(samples_per_pixel)
+}
+
+// Straight color and alpha of a pixel, from its premultiplied color and the light that shadow
+// catchers reflect onto it. That light has no alpha of its own, and dividing it by the opacity of a
+// faint shadow would blow it up, so the pixel is made as opaque as the light needs to fit into the
+// color instead.
+fn unpremultiply(color: Color, alpha: f64, reflection: Color) -> (Color, f64) {
+    let alpha = alpha.max(reflection.max_component().min(1.0));
+    if alpha > 0.0 {
+        ((color + reflection).div_scalar(alpha), alpha)
+    } else {
+        (Color::default(), 0.0)
+    }
+}
+
+pub fn render(
+    image_width: u32,
+    samples_per_pixel: u32,
+    scene: Scene,
+    alpha: bool,
+    integrator: &mut dyn Integrator,
+) {
+    let image_height = (image_width as f64 / scene.aspect_ratio) as u32;
 
-    // Output the rendered image to .png.
     fs::create_dir_all("out")
         .expect("Output directory does not exist and failed trying to create it");
-    image_buffer.save("out/rendered_image.png").unwrap();
+
+    // Render the scene. The light splatted onto the film can land on any pixel, so it is only added
+    // once all of them are done.
+    let film = Film::new(image_width, image_height).with_alpha(alpha);
+    let mut pixels =
+        vec![(Color::default(), 0.0, Color::default()); (image_width * image_height) as usize];
+    let pass_num = integrator.pass_count().clamp(1, samples_per_pixel.max(1));
+    for pass in 0..pass_num {
+        let pass_samples =
+            samples_per_pixel / pass_num + u32::from(pass < samples_per_pixel % pass_num);
+        integrator.begin_pass(&scene, &film, pass, pass_samples);
+        let integrator: &dyn Integrator = integrator;
+
+        pixels.par_iter_mut().enumerate().for_each(
+            |(index, (color_sum, alpha_sum, reflection_sum))| {
+                let (i, j) = (index as u32 % image_width, index as u32 / image_width);
+                let (color, alpha, reflection) = if alpha {
+                    render_alpha_pixel(i, j, pass_samples, &scene, integrator, &film)
+                } else {
+                    let color = render_pixel(i, j, pass_samples, &scene, integrator, &film);
+                    (color, 1.0, Color::default())
+                };
+                *color_sum += color.mul_scalar(pass_samples as f64);
+                *alpha_sum += alpha * pass_samples as f64;
+                *reflection_sum += reflection.mul_scalar(pass_samples as f64);
+            },
+        );
+    }
+    let pixel_color = |i: u32, j: u32| {
+        let (color_sum, alpha_sum, reflection_sum) = pixels[(j * image_width + i) as usize];
+        (
+            color_sum.div_scalar(samples_per_pixel as f64)
+                + film.splat_color(i, j, samples_per_pixel),
+            alpha_sum / samples_per_pixel as f64,
+            reflection_sum.div_scalar(samples_per_pixel as f64),
+        )
+    };
+
+    // Output the rendered image to .png.
+    if alpha {
+        let image_buffer = image::RgbaImage::from_fn(image_width, image_height, |i, j| {
+            let (color, alpha, reflection) = pixel_color(i, j);
+            let (color, alpha) = unpremultiply(color, alpha, reflection);
+            let [r, g, b] = color.gamma_2_correct().into_rgb8();
+            image::Rgba([r, g, b, (255.99 * alpha) as u8])
+        });
+        image_buffer.save("out/rendered_image.png").unwrap();
+    } else {
+        let image_buffer = image::RgbImage::from_fn(image_width, image_height, |i, j| {
+            image::Rgb(pixel_color(i, j).0.gamma_2_correct().into_rgb8())
+        });
+        image_buffer.save("out/rendered_image.png").unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shadow_catcher_reflections_keep_their_light_over_faint_shadows() {
+        let reflection = Color::new(0.3, 0.2, 0.1);
+        let (color, alpha) = unpremultiply(Color::default(), 0.02, reflection);
+
+        assert!(color.max_component() <= 1.0 + 1e-12);
+        // Composited over black, the pixel gives back the reflected light.
+        let composited = color.mul_scalar(alpha);
+        for channel in 0..3 {
+            assert!((composited[channel] - reflection[channel]).abs() < 1e-12);
+        }
+
+        let (color, alpha) = unpremultiply(Color::from_scalar(0.25), 0.5, Color::default());
+        assert_eq!((color, alpha), (Color::from_scalar(0.5), 0.5));
+    }
 }
@@ -0,0 +1,80 @@
+use std::any::Any;
+use std::cell::RefCell;
+
+use rand::Rng;
+
+// Source of the random numbers used while rendering. Every random decision taken to trace a path
+// (camera lens, light selection, material and pdf sampling, Russian roulette...) draws its numbers
+// through `random` and `random_range`, which use the thread's random number generator unless a
+// sampler has been installed on the thread with `with_sampler`. This lets an integrator replay or
+// mutate the numbers that built a path.
+pub trait Sampler: Any {
+    // Next number of the sequence, in [0, 1).
+    fn next_1d(&mut self) -> f64;
+}
+
+thread_local! {
+    static SAMPLER: RefCell<Option<Box<dyn Sampler>>> = RefCell::new(None);
+}
+
+// Uniform random number in [0, 1).
+pub fn random() -> f64 {
+    SAMPLER
+        .with(|sampler| {
+            sampler
+                .borrow_mut()
+                .as_mut()
+                .map(|sampler| sampler.next_1d())
+        })
+        .unwrap_or_else(|| rand::thread_rng().gen_range(0.0, 1.0))
+}
+
+// Uniform random number in [low, high).
+pub fn random_range(low: f64, high: f64) -> f64 {
+    low + (high - low) * random()
+}
+
+// Uniform random index in [0, len).
+pub fn random_index(len: usize) -> usize {
+    ((random() * len as f64) as usize).min(len - 1)
+}
+
+// Runs `f` drawing its random numbers from `sampler`, which is given back afterwards.
+pub fn with_sampler<S: Sampler, R, F: FnOnce() -> R>(sampler: S, f: F) -> (S, R) {
+    let previous = SAMPLER.with(|current| current.replace(Some(Box::new(sampler))));
+    let result = f();
+    let sampler: Box<dyn Any> = SAMPLER
+        .with(|current| current.replace(previous))
+        .expect("the sampler was removed while in use");
+
+    match sampler.downcast::<S>() {
+        Ok(sampler) => (*sampler, result),
+        Err(_) => panic!("the sampler was replaced while in use"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Sequence(Vec<f64>, usize);
+
+    impl Sampler for Sequence {
+        fn next_1d(&mut self) -> f64 {
+            self.1 += 1;
+            self.0[(self.1 - 1) % self.0.len()]
+        }
+    }
+
+    #[test]
+    fn installed_sampler_drives_the_random_numbers() {
+        let (sequence, numbers) = with_sampler(Sequence(vec![0.25, 0.5], 0), || {
+            (random(), random_range(2.0, 4.0), random_index(4))
+        });
+        assert_eq!(numbers, (0.25, 3.0, 1));
+        assert_eq!(sequence.1, 3);
+
+        let number = random();
+        assert!((0.0..1.0).contains(&number));
+    }
+}
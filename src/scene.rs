@@ -3,33 +3,76 @@ use std::sync::Arc;
 use rand::Rng;
 
 use crate::camera::Camera;
-use crate::hittables::{BvhNode, FlipFace, Hit, Sphere, World, XYRect, XZRect, YZRect};
+use crate::hittables::{area_light, BvhNode, FlipFace, Hit, Sphere, World, XYRect, XZRect, YZRect};
+use crate::lights::{Background, DeltaLight, LightList, LightSelection};
 use crate::linalg::{Color, Point3, Vec3};
 use crate::materials::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
 
 pub struct Scene {
     pub world: World,
-    pub light: Option<Arc<dyn Hit>>,
+    pub lights: Arc<LightList>,
+    pub delta_lights: Vec<Arc<dyn DeltaLight>>,
     pub camera: Camera,
     pub aspect_ratio: f64,
-    pub background: Color,
+    pub background: Arc<dyn Background>,
+    // Fraction of the scattered directions sampled from the lights instead of the materials, or None
+    // to let each material choose.
+    pub light_sampling_weight: Option<f64>,
+    // Light added to the light list to importance sample the background, if it has one.
+    background_light: Option<Arc<dyn Hit>>,
 }
 
 impl Scene {
     pub fn new(
         world: World,
-        light: Option<Arc<dyn Hit>>,
+        lights: LightList,
         camera: Camera,
         aspect_ratio: f64,
-        background: Color,
+        background: Arc<dyn Background>,
     ) -> Self {
-        Self {
+        let mut scene = Self {
             world,
-            light,
+            lights: Arc::new(lights),
+            delta_lights: Vec::new(),
             camera,
             aspect_ratio,
-            background,
+            background: Arc::new(Color::default()),
+            light_sampling_weight: None,
+            background_light: None,
+        };
+        scene.set_background(background);
+        scene
+    }
+
+    // Replaces the background, importance sampling it together with the other lights if possible.
+    pub fn set_background(&mut self, background: Arc<dyn Background>) {
+        let scene_radius = self.radius();
+        let lights = Arc::make_mut(&mut self.lights);
+        if let Some(background_light) = self.background_light.take() {
+            lights.remove(&background_light);
+        }
+
+        self.background_light = background.light(scene_radius);
+        if let Some(background_light) = &self.background_light {
+            lights.add(background_light.clone());
         }
+
+        self.background = background;
+    }
+
+    // Radius of a sphere that bounds the objects of the scene.
+    pub fn radius(&self) -> f64 {
+        match self.world.bounding_box(0.0, 1.0) {
+            Some(bounding_box) => {
+                (bounding_box.max_corner() - bounding_box.min_corner()).length() / 2.0
+            }
+            None => 1.0,
+        }
+    }
+
+    // Point, spot and directional lights are sampled with shadow rays on top of the area lights.
+    pub fn add_delta_light(&mut self, light: Arc<dyn DeltaLight>) {
+        self.delta_lights.push(light);
     }
 }
 
@@ -53,7 +96,7 @@ pub fn get_cornell_box_scene(objects: World) -> Scene {
         focus_dist,
     );
 
-    let background = Color::new(0.0, 0.0, 0.0);
+    let background = Arc::new(Color::new(0.0, 0.0, 0.0));
 
     let mut hittable_list = World::default();
     for object in objects.objects() {
@@ -63,17 +106,14 @@ pub fn get_cornell_box_scene(objects: World) -> Scene {
     let red_mat = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
     let white_mat = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
     let green_mat = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
-    let light_mat = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
-
-    // Light that illuminates the Cornell box.
-    hittable_list.add(Arc::new(FlipFace::new(Arc::new(XZRect::new(
-        213.0,
-        343.0,
-        227.0,
-        332.0,
-        554.0,
-        light_mat.clone(),
-    )))));
+
+    // White light that illuminates the Cornell box, with its power given for areas in squared scene
+    // units, which gives it a radiance of about 15.
+    let light = area_light(
+        |light| XZRect::new(213.0, 343.0, 227.0, 332.0, 554.0, light),
+        |area| DiffuseLight::from_watts(643_000.0, Color::from_scalar(1.0), area),
+    );
+    hittable_list.add(Arc::new(FlipFace::new(Arc::new(light))));
 
     // Left wall of the Cornell box.
     hittable_list.add(Arc::new(YZRect::new(
@@ -119,11 +159,10 @@ pub fn get_cornell_box_scene(objects: World) -> Scene {
     let mut world = World::default();
     world.add(Arc::new(bvh_node));
 
-    let light: Option<Arc<dyn Hit>> = Some(Arc::new(XZRect::new(
-        213.0, 343.0, 227.0, 332.0, 554.0, light_mat,
-    )));
+    // Every emissive object in the box is importance sampled, including any emissive meshes.
+    let lights = LightList::from_world(&world, LightSelection::Power);
 
-    Scene::new(world, light, camera, aspect_ratio, background)
+    Scene::new(world, lights, camera, aspect_ratio, background)
 }
 
 pub fn get_random_spheres_scene() -> Scene {
@@ -146,7 +185,7 @@ pub fn get_random_spheres_scene() -> Scene {
         focus_dist,
     );
 
-    let background = Color::new(0.7, 0.8, 1.0);
+    let background = Arc::new(Color::new(0.7, 0.8, 1.0));
 
     let mut hittable_list = World::default();
 
@@ -218,5 +257,7 @@ pub fn get_random_spheres_scene() -> Scene {
     let mut world = World::default();
     world.add(Arc::new(bvh_node));
 
-    Scene::new(world, None, camera, aspect_ratio, background)
+    let lights = LightList::from_world(&world, LightSelection::Power);
+
+    Scene::new(world, lights, camera, aspect_ratio, background)
 }
@@ -0,0 +1,68 @@
+use std::io;
+use std::path::Path;
+
+use crate::linalg::{Color, Point3};
+
+use super::Texture;
+
+// Texture given by an image, where (u, v) = (0, 0) is the bottom left corner of the image.
+pub struct ImageTexture {
+    pixels: Vec<Color>,
+    width: usize,
+    height: usize,
+    average: Color,
+}
+
+impl ImageTexture {
+    pub fn new(pixels: Vec<Color>, width: usize, height: usize) -> Self {
+        let mut average = Color::default();
+        for pixel in &pixels {
+            average += *pixel;
+        }
+        average = average.div_scalar(pixels.len().max(1) as f64);
+
+        Self {
+            pixels,
+            width,
+            height,
+            average,
+        }
+    }
+
+    // Loads an 8-bit image, undoing the same gamma 2 encoding that rendered images are saved with.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let image = image::open(path)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?
+            .to_rgb();
+        let (width, height) = image.dimensions();
+        let pixels = image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b] = pixel.0;
+                let color = Color::new(r as f64, g as f64, b as f64).div_scalar(255.0);
+                color * color
+            })
+            .collect();
+
+        Ok(Self::new(pixels, width as usize, height as usize))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: Point3) -> Color {
+        if self.pixels.is_empty() {
+            return Color::default();
+        }
+
+        // Coordinates outside of [0, 1] wrap around, so that textures can be repeated.
+        let u = u.rem_euclid(1.0);
+        let v = 1.0 - v.rem_euclid(1.0);
+        let column = ((u * self.width as f64) as usize).min(self.width - 1);
+        let row = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[row * self.width + column]
+    }
+
+    fn average(&self) -> Color {
+        self.average
+    }
+}
@@ -0,0 +1,23 @@
+pub use image_texture::ImageTexture;
+
+use crate::linalg::{Color, Point3};
+
+pub mod image_texture;
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, point: Point3) -> Color;
+
+    // Average color over the whole texture, used to estimate how much light a textured emitter gives
+    // off.
+    fn average(&self) -> Color;
+}
+
+impl Texture for Color {
+    fn value(&self, _u: f64, _v: f64, _point: Point3) -> Color {
+        *self
+    }
+
+    fn average(&self) -> Color {
+        *self
+    }
+}